@@ -1,6 +1,6 @@
 use cli::Cli;
 use common::run::{exec_run, Io, Result};
-use common::transfer::{run_transfer, TransferMode};
+use common::transfer::run_transfer;
 
 mod cli;
 
@@ -9,5 +9,5 @@ fn main() {
 }
 
 fn run(cli: &Cli, io: &Io) -> Result {
-    run_transfer(cli, io, TransferMode::Copy)
+    run_transfer(cli, io, cli.transfer_mode())
 }
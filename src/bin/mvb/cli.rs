@@ -1,8 +1,17 @@
+use std::path::{Path, PathBuf};
+use std::thread;
+
 use clap::{crate_version, AppSettings, Clap};
 use common::color::{parse_color, COLOR_CHOICES};
+use common::encoding::{parse_encoding_policy, EncodingPolicy, ENCODING_POLICIES};
 use common::help::highlight_static;
+use common::input::parse_single_byte_char;
 use common::run::Options;
-use common::transfer::TransferOptions;
+use common::transfer::{
+    parse_backup_mode, parse_input_format, parse_report_format, BackupMode, InputFormat,
+    PreserveAttrs, ReportFormat, TransferOptions, Trash, BACKUP_MODES, INPUT_FORMATS,
+    REPORT_FORMATS,
+};
 use indoc::indoc;
 use termcolor::ColorChoice;
 
@@ -13,7 +22,7 @@ use termcolor::ColorChoice;
     long_about = highlight_static(indoc!{"
         Bulk move (rename) files and directories
 
-        `mvb` reads instructions from standard input in the following format:
+        `mvb` reads instructions from standard input (or files) in the following format:
        
             <src_path_1
             >dst_path_1
@@ -27,6 +36,10 @@ use termcolor::ColorChoice;
        
             $> find -name '*.jpeg' | rew -d '{B}.jpg' | mvb # Rename all *.jpeg files to *.jpg
        
+        Paths containing newlines can be safely passed as JSON lines generated by `rew -j, --json-lines` flag:
+       
+            $> find -name '*.jpeg' | rew -j '{B}.jpg' | mvb --input-format=jsonl
+       
         Each pair of source and destination path must be either both files or both directories. Mixing these types will result in error.
        
         Source path must exist. Using non-existent source path will result in error.
@@ -36,6 +49,14 @@ use termcolor::ColorChoice;
         Missing parent directories in destination path will be created as needed.
        
         Nothing will be done if source and destination paths point to the same file or directory.
+       
+        Paths which cannot be renamed (e.g., because they are on different file systems) are copied together with their attributes (mode, timestamps, ownership and extended attributes) and then removed.
+       
+        All instructions are read and checked before any of them is executed. Nothing will be done if multiple source paths have the same destination path.
+       
+        Instructions are reordered so that no source path is overwritten before being used (e.g., `b -> c` is done before `a -> b`). Cycles (e.g., `a -> b` and `b -> a`) are resolved by temporarily renaming one of their source paths.
+       
+        Interrupt signal (e.g., `Ctrl-C`) stops processing once the current instruction is finished, a second one terminates immediately. Number of completed, skipped, failed and pending instructions is printed and exit code is 130.
     "}),
     after_help = highlight_static("Use `-h` for short descriptions and `--help` for more details."),
     setting(AppSettings::ColoredHelp),
@@ -45,18 +66,233 @@ use termcolor::ColorChoice;
 )]
 /// Bulk move (rename) files and directories
 pub struct Cli {
+    /// Files with instructions (read from standard input by default)
+    ///
+    /// Instructions from multiple files are concatenated.
+    /// Use `-` to read standard input in place of a file.
+    #[clap(value_name = "file", conflicts_with = "undo")]
+    pub files: Vec<PathBuf>,
+
+    /// Read instructions terminated by a specific character, not newline
+    #[clap(
+        short = 't',
+        long,
+        value_name = "char",
+        conflicts_with = "read-nul",
+        parse(try_from_str = parse_single_byte_char),
+    )]
+    pub read: Option<u8>,
+
     /// Read instructions terminated by NUL character, not newline
     #[clap(short = 'z', long)]
     pub read_nul: bool,
 
+    /// Format of instructions
+    ///
+    /// Format `diff` (default) expects `<src_path` and `>dst_path` items as described above.
+    /// Format `jsonl` expects a JSON object `{"in":"src_path","out":"dst_path"}` per line (see `rew -j`).
+    /// Format `tsv` expects `src_path` and `dst_path` separated by a tab character per line.
+    #[clap(
+        long,
+        value_name = "format",
+        possible_values = INPUT_FORMATS,
+        parse(try_from_str = parse_input_format),
+    )]
+    pub input_format: Option<InputFormat>,
+
+    /// How to handle instructions which are not valid UTF-8
+    ///
+    /// - `fail` - Fail with an error (default).
+    /// - `lossy` - Replace invalid sequences with `U+FFFD` character.
+    /// - `escape` - Accept any bytes as they are, only paths in `jsonl` input format
+    ///   have their `%xx` escape sequences decoded (see `rew --invalid-utf8`).
+    #[clap(
+        long,
+        value_name = "policy",
+        possible_values = ENCODING_POLICIES,
+        parse(try_from_str = parse_encoding_policy),
+    )]
+    pub invalid_utf8: Option<EncodingPolicy>,
+
     /// Continue processing after an error, fail at end
-    #[clap(short = 'F', long)]
+    #[clap(short = 'F', long, conflicts_with = "atomic")]
     pub fail_at_end: bool,
 
+    /// Revert all completed transfers after an error
+    ///
+    /// Existing destination files are temporarily backed up and restored during rollback.
+    /// Merging into an existing destination directory is not allowed in this mode.
+    #[clap(long, conflicts_with = "fail-at-end")]
+    pub atomic: bool,
+
+    /// Record completed transfers to a journal file
+    ///
+    /// Each transfer is recorded in the same format as instructions but with inverted direction.
+    /// Paths are recorded as absolute.
+    /// Transfer whose destination existed before is preceded by a line containing only `!`.
+    /// Use `--undo` option to undo transfers recorded in the journal file.
+    #[clap(long, value_name = "path", conflicts_with = "undo")]
+    pub journal: Option<PathBuf>,
+
+    /// Undo transfers recorded in a journal file
+    ///
+    /// Destinations of recorded transfers are moved back to their sources.
+    /// Destination which existed before is copied back instead and reported as an error,
+    /// because its original content cannot be restored.
+    /// Standard input is not read when this option is used.
+    #[clap(long, value_name = "path")]
+    pub undo: Option<PathBuf>,
+
+    /// Do not overwrite existing files
+    #[clap(
+        short = 'N',
+        long,
+        conflicts_with_all = &["fail-if-exists", "backup", "trash", "update"]
+    )]
+    pub no_clobber: bool,
+
+    /// Fail if a destination path already exists
+    #[clap(
+        short = 'E',
+        long,
+        conflicts_with_all = &["backup", "trash", "update"]
+    )]
+    pub fail_if_exists: bool,
+
+    /// Make a backup of each overwritten file
+    ///
+    /// Mode `suffix` (default) appends `~` to name of the backup file.
+    /// Mode `numbered` appends `.~N~` where N is number following the highest existing one.
+    #[clap(
+        short = 'B',
+        long,
+        value_name = "mode",
+        possible_values = BACKUP_MODES,
+        require_equals = true,
+        conflicts_with = "update",
+    )]
+    pub backup: Option<Option<String>>,
+
+    /// Move each overwritten file to trash
+    ///
+    /// Without a value, the home trash `$XDG_DATA_HOME/Trash` is used.
+    /// Files can be later restored using standard desktop tools.
+    /// With a value, files are moved to the specified directory.
+    #[clap(
+        long,
+        value_name = "dir",
+        require_equals = true,
+        conflicts_with_all = &["backup", "update"]
+    )]
+    pub trash: Option<Option<PathBuf>>,
+
+    /// Overwrite only files older than their source
+    #[clap(short = 'u', long)]
+    pub update: bool,
+
+    /// Fail instead of merging a directory into an existing directory
+    #[clap(short = 'M', long)]
+    pub no_merge: bool,
+
+    /// Move targets of symbolic links instead of links themselves
+    ///
+    /// Links (including those inside moved directories) are replaced by copies of their targets.
+    /// Targets themselves are kept in place, only the links are removed.
+    #[clap(short = 'L', long)]
+    pub dereference: bool,
+
+    /// Compare content of files copied across file systems with their source
+    ///
+    /// Such files are copied and then removed, because they cannot be renamed.
+    /// Source is removed only when its copy has the same content.
+    #[clap(long)]
+    pub verify: bool,
+
+    /// Ask for confirmation of each instruction
+    ///
+    /// Answers are read from the terminal, because standard input contains instructions.
+    /// Instructions are asked in their planned order together with their planned action
+    /// (`staged` source of a cycle, `overwrite` or `merge` of an existing destination).
+    /// Confirmed instructions are executed after all of them are answered.
+    /// Answer `quit` (or closed terminal) aborts without executing any instruction.
+    #[clap(short = 'i', long, conflicts_with_all = &["undo", "dry-run"])]
+    pub interactive: bool,
+
+    /// Write unfinished instructions to a file
+    ///
+    /// Instructions which failed or were not executed (due to an error or interruption)
+    /// are written in the same format as the input, so they can be executed later.
+    /// Paths are written as absolute.
+    #[clap(long, value_name = "path", conflicts_with_all = &["atomic", "undo"])]
+    pub remaining: Option<PathBuf>,
+
+    /// Record finished instructions to a state file and skip them when executed again
+    ///
+    /// Instructions are identified by their position in the input,
+    /// so the same instructions have to be used for each execution.
+    /// Finished instruction is executed again when its destination path no longer exists.
+    /// Staged instructions (part of a cycle) are resumed from their temporary path.
+    /// Resuming fails when a source of unfinished instruction no longer exists.
+    #[clap(
+        long,
+        value_name = "path",
+        conflicts_with_all = &["atomic", "undo", "interactive"]
+    )]
+    pub resume: Option<PathBuf>,
+
+    /// Only print what would be done, do not change anything
+    ///
+    /// Each instruction is checked against the current state of the file system
+    /// (including changes made by previous instructions) and printed
+    /// together with its planned action or an error.
+    #[clap(
+        short = 'n',
+        long,
+        conflicts_with_all = &["atomic", "journal", "remaining", "resume"]
+    )]
+    pub dry_run: bool,
+
     /// Explain what is being done
     #[clap(short = 'v', long)]
     pub verbose: bool,
 
+    /// Print progress to standard error
+    ///
+    /// Progress consists of the current instruction number, copied bytes, throughput and remaining time.
+    /// Files inside copied directories are reported individually.
+    /// When standard error is not a terminal, progress is printed as plain log lines.
+    #[clap(short = 'p', long, conflicts_with_all = &["verbose", "dry-run"])]
+    pub progress: bool,
+
+    /// Print machine-readable report of results to standard output
+    ///
+    /// Format `jsonl` prints a JSON object per line for each instruction
+    /// with its `src`, `dst`, `action`, `result` and `error` message.
+    /// Paths are encoded according to `--invalid-utf8` option.
+    /// The last line contains `summary` with numbers of processed instructions and copied bytes.
+    #[clap(
+        long,
+        value_name = "format",
+        possible_values = REPORT_FORMATS,
+        parse(try_from_str = parse_report_format),
+        conflicts_with_all = &["verbose", "dry-run"],
+    )]
+    pub report: Option<ReportFormat>,
+
+    /// Number of instructions to execute in parallel
+    ///
+    /// An instruction is not started until all preceding instructions
+    /// working with the same paths (or their parent and child paths) are finished.
+    /// Output is still printed in the order of instructions.
+    /// Value 0 uses the number of available CPUs. [default: 1]
+    #[clap(
+        short = 'j',
+        long,
+        value_name = "count",
+        conflicts_with_all = &["atomic", "progress"]
+    )]
+    pub jobs: Option<usize>,
+
     /// When to use colors
     #[clap(
         long,
@@ -82,10 +318,26 @@ impl Options for Cli {
 }
 
 impl TransferOptions for Cli {
+    fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    fn read(&self) -> Option<u8> {
+        self.read
+    }
+
     fn read_nul(&self) -> bool {
         self.read_nul
     }
 
+    fn input_format(&self) -> InputFormat {
+        self.input_format.unwrap_or(InputFormat::Diff)
+    }
+
+    fn encoding(&self) -> EncodingPolicy {
+        self.invalid_utf8.unwrap_or_default()
+    }
+
     fn verbose(&self) -> bool {
         self.verbose
     }
@@ -93,6 +345,95 @@ impl TransferOptions for Cli {
     fn fail_at_end(&self) -> bool {
         self.fail_at_end
     }
+
+    fn atomic(&self) -> bool {
+        self.atomic
+    }
+
+    fn journal(&self) -> Option<&Path> {
+        self.journal.as_deref()
+    }
+
+    fn undo(&self) -> Option<&Path> {
+        self.undo.as_deref()
+    }
+
+    fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    fn no_clobber(&self) -> bool {
+        self.no_clobber
+    }
+
+    fn fail_if_exists(&self) -> bool {
+        self.fail_if_exists
+    }
+
+    fn backup(&self) -> Option<BackupMode> {
+        self.backup.as_ref().map(|backup_mode| {
+            backup_mode
+                .as_deref()
+                .map_or(Ok(BackupMode::Suffix), parse_backup_mode)
+                .expect("Expected backup mode validated by clap")
+        })
+    }
+
+    fn trash(&self) -> Option<Trash> {
+        self.trash.as_ref().map(|trash_dir| match trash_dir {
+            Some(trash_dir) => Trash::Dir(trash_dir.clone()),
+            None => Trash::Home,
+        })
+    }
+
+    fn update(&self) -> bool {
+        self.update
+    }
+
+    fn no_merge(&self) -> bool {
+        self.no_merge
+    }
+
+    fn interactive(&self) -> bool {
+        self.interactive
+    }
+
+    fn dereference(&self) -> bool {
+        self.dereference
+    }
+
+    fn preserve(&self) -> PreserveAttrs {
+        // Moved files should be indistinguishable from their originals.
+        PreserveAttrs::all()
+    }
+
+    fn verify(&self) -> bool {
+        self.verify
+    }
+
+    fn remaining(&self) -> Option<&Path> {
+        self.remaining.as_deref()
+    }
+
+    fn resume(&self) -> Option<&Path> {
+        self.resume.as_deref()
+    }
+
+    fn progress(&self) -> bool {
+        self.progress
+    }
+
+    fn report(&self) -> Option<ReportFormat> {
+        self.report
+    }
+
+    fn jobs(&self) -> usize {
+        match self.jobs {
+            Some(0) => thread::available_parallelism().map_or(1, |count| count.get()),
+            Some(count) => count,
+            None => 1,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -107,12 +448,43 @@ mod tests {
         assert_eq!(run(args).color(), result);
     }
 
+    #[test_case(&[],              &[]              ; "none")]
+    #[test_case(&["a"],           &["a"]           ; "single")]
+    #[test_case(&["a", "-", "b"], &["a", "-", "b"] ; "multiple")]
+    fn files(args: &[&str], result: &[&str]) {
+        assert_eq!(
+            run(args).files(),
+            result.iter().map(PathBuf::from).collect::<Vec<PathBuf>>()
+        );
+    }
+
+    #[test_case(&[],           None       ; "none")]
+    #[test_case(&["--read=|"], Some(b'|') ; "some")]
+    fn read(args: &[&str], result: Option<u8>) {
+        assert_eq!(run(args).read(), result);
+    }
+
     #[test_case(&[],             false ; "off")]
     #[test_case(&["--read-nul"], true  ; "on")]
     fn read_nul(args: &[&str], result: bool) {
         assert_eq!(run(args).read_nul(), result);
     }
 
+    #[test_case(&[],                       InputFormat::Diff      ; "default")]
+    #[test_case(&["--input-format=diff"],  InputFormat::Diff      ; "diff")]
+    #[test_case(&["--input-format=jsonl"], InputFormat::JsonLines ; "json lines")]
+    #[test_case(&["--input-format=tsv"],   InputFormat::Tsv       ; "tsv")]
+    fn input_format(args: &[&str], result: InputFormat) {
+        assert_eq!(run(args).input_format(), result);
+    }
+
+    #[test_case(&[],                        EncodingPolicy::Fail   ; "default")]
+    #[test_case(&["--invalid-utf8=lossy"],  EncodingPolicy::Lossy  ; "lossy")]
+    #[test_case(&["--invalid-utf8=escape"], EncodingPolicy::Escape ; "escape")]
+    fn encoding(args: &[&str], result: EncodingPolicy) {
+        assert_eq!(run(args).encoding(), result);
+    }
+
     #[test_case(&[],            false ; "off")]
     #[test_case(&["--verbose"], true  ; "on")]
     fn verbose(args: &[&str], result: bool) {
@@ -125,6 +497,127 @@ mod tests {
         assert_eq!(run(args).fail_at_end(), result);
     }
 
+    #[test_case(&[],           false ; "off")]
+    #[test_case(&["--atomic"], true  ; "on")]
+    fn atomic(args: &[&str], result: bool) {
+        assert_eq!(run(args).atomic(), result);
+    }
+
+    #[test_case(&[],                    None         ; "none")]
+    #[test_case(&["--journal", "file"], Some("file") ; "some")]
+    fn journal(args: &[&str], result: Option<&str>) {
+        assert_eq!(run(args).journal(), result.map(Path::new));
+    }
+
+    #[test_case(&[],                 None         ; "none")]
+    #[test_case(&["--undo", "file"], Some("file") ; "some")]
+    fn undo(args: &[&str], result: Option<&str>) {
+        assert_eq!(run(args).undo(), result.map(Path::new));
+    }
+
+    #[test_case(&[],            false ; "off")]
+    #[test_case(&["--dry-run"], true  ; "on")]
+    fn dry_run(args: &[&str], result: bool) {
+        assert_eq!(run(args).dry_run(), result);
+    }
+
+    #[test_case(&[],               false ; "off")]
+    #[test_case(&["--no-clobber"], true  ; "on")]
+    fn no_clobber(args: &[&str], result: bool) {
+        assert_eq!(run(args).no_clobber(), result);
+    }
+
+    #[test_case(&[],                   false ; "off")]
+    #[test_case(&["--fail-if-exists"], true  ; "on")]
+    fn fail_if_exists(args: &[&str], result: bool) {
+        assert_eq!(run(args).fail_if_exists(), result);
+    }
+
+    #[test_case(&[],                     None                       ; "none")]
+    #[test_case(&["--backup"],           Some(BackupMode::Suffix)   ; "default")]
+    #[test_case(&["--backup=suffix"],    Some(BackupMode::Suffix)   ; "suffix")]
+    #[test_case(&["--backup=numbered"],  Some(BackupMode::Numbered) ; "numbered")]
+    fn backup(args: &[&str], result: Option<BackupMode>) {
+        assert_eq!(run(args).backup(), result);
+    }
+
+    #[test_case(&[],               None                            ; "none")]
+    #[test_case(&["--trash"],      Some(Trash::Home)               ; "home")]
+    #[test_case(&["--trash=dir"],  Some(Trash::Dir("dir".into()))  ; "dir")]
+    fn trash(args: &[&str], result: Option<Trash>) {
+        assert_eq!(run(args).trash(), result);
+    }
+
+    #[test_case(&[],           false ; "off")]
+    #[test_case(&["--update"], true  ; "on")]
+    fn update(args: &[&str], result: bool) {
+        assert_eq!(run(args).update(), result);
+    }
+
+    #[test_case(&[],             false ; "off")]
+    #[test_case(&["--no-merge"], true  ; "on")]
+    fn no_merge(args: &[&str], result: bool) {
+        assert_eq!(run(args).no_merge(), result);
+    }
+
+    #[test_case(&[],                false ; "off")]
+    #[test_case(&["--interactive"], true  ; "on")]
+    fn interactive(args: &[&str], result: bool) {
+        assert_eq!(run(args).interactive(), result);
+    }
+
+    #[test_case(&[],                false ; "off")]
+    #[test_case(&["--dereference"], true  ; "on")]
+    fn dereference(args: &[&str], result: bool) {
+        assert_eq!(run(args).dereference(), result);
+    }
+
+    #[test]
+    fn preserve() {
+        assert_eq!(run(&[]).preserve(), PreserveAttrs::all());
+    }
+
+    #[test_case(&[],           false ; "off")]
+    #[test_case(&["--verify"], true  ; "on")]
+    fn verify(args: &[&str], result: bool) {
+        assert_eq!(run(args).verify(), result);
+    }
+
+    #[test_case(&[],                      None         ; "none")]
+    #[test_case(&["--remaining", "file"], Some("file") ; "some")]
+    fn remaining(args: &[&str], result: Option<&str>) {
+        assert_eq!(run(args).remaining(), result.map(Path::new));
+    }
+
+    #[test_case(&[],                   None         ; "none")]
+    #[test_case(&["--resume", "file"], Some("file") ; "some")]
+    fn resume(args: &[&str], result: Option<&str>) {
+        assert_eq!(run(args).resume(), result.map(Path::new));
+    }
+
+    #[test_case(&[],             false ; "off")]
+    #[test_case(&["--progress"], true  ; "on")]
+    fn progress(args: &[&str], result: bool) {
+        assert_eq!(run(args).progress(), result);
+    }
+
+    #[test_case(&[],                 None                          ; "none")]
+    #[test_case(&["--report=jsonl"], Some(ReportFormat::JsonLines) ; "json lines")]
+    fn report(args: &[&str], result: Option<ReportFormat>) {
+        assert_eq!(run(args).report(), result);
+    }
+
+    #[test_case(&[],              1 ; "default")]
+    #[test_case(&["--jobs", "4"], 4 ; "some")]
+    fn jobs(args: &[&str], result: usize) {
+        assert_eq!(run(args).jobs(), result);
+    }
+
+    #[test]
+    fn jobs_available_cpus() {
+        assert!(run(&["--jobs", "0"]).jobs() >= 1);
+    }
+
     fn run(args: &[&str]) -> Cli {
         Cli::try_parse_from(&[&["mvb"], args].concat()).unwrap()
     }
@@ -2,16 +2,22 @@ use std::path::PathBuf;
 
 use clap::{crate_name, crate_version, AppSettings, ArgSettings, Clap};
 use common::color::{parse_color, COLOR_CHOICES};
+use common::encoding::{parse_encoding_policy, EncodingPolicy, ENCODING_POLICIES};
 use common::help::highlight_static;
+use common::input::parse_single_byte_char;
 use common::run::Options;
+use globset::Glob;
 use indoc::indoc;
 use regex::Regex;
 use termcolor::ColorChoice;
 
 use crate::counter;
 use crate::pattern::eval;
+use crate::sort::{parse_sort_key, Config as SortConfig, Key as SortKey, SORT_KEYS};
+use crate::walk::{build_glob_set, parse_glob, Config as WalkConfig, EntryType, Order};
 
 const INPUT_HEADING: Option<&str> = Some("INPUT OPTIONS");
+const WALK_HEADING: Option<&str> = Some("WALK OPTIONS");
 const OUTPUT_HEADING: Option<&str> = Some("OUTPUT OPTIONS");
 const PROCESSING_HEADING: Option<&str> = Some("PROCESSING OPTIONS");
 const PATTERN_HEADING: Option<&str> = Some("PATTERN OPTIONS");
@@ -81,6 +87,40 @@ pub struct Cli {
     #[clap(short = 'l', long, help_heading = INPUT_HEADING)]
     pub read_end: bool,
 
+    /// Read values as CSV records
+    ///
+    /// Each record is split into columns using standard CSV quoting rules.
+    /// The first column is the input value, all columns can be referenced as `{1}`, `{2}`, ...
+    #[clap(
+        long,
+        conflicts_with_all = &["read-tsv", "read-jsonl", "read-nul", "read-raw", "read", "read-end", "no-stdin", "walk", "values"],
+        help_heading = INPUT_HEADING
+    )]
+    pub read_csv: bool,
+
+    /// Read values as TSV records
+    ///
+    /// The same as `--read-csv` but columns are separated by a tab character.
+    #[clap(
+        long,
+        conflicts_with_all = &["read-csv", "read-jsonl", "read-nul", "read-raw", "read", "read-end", "no-stdin", "walk", "values"],
+        help_heading = INPUT_HEADING
+    )]
+    pub read_tsv: bool,
+
+    /// Read values as JSON lines, using string field `key` as input value
+    ///
+    /// Each line must contain a JSON object with string field `key`.
+    /// Other fields can be referenced as variables `{.name}`.
+    /// Strings are used as they are, `null` as an empty string and other values as JSON.
+    #[clap(
+        long,
+        value_name = "key",
+        conflicts_with_all = &["read-csv", "read-tsv", "read-nul", "read-raw", "read", "no-stdin", "walk", "values"],
+        help_heading = INPUT_HEADING
+    )]
+    pub read_jsonl: Option<String>,
+
     /// Do not read values from standard input
     ///
     /// By default, when no values are passed as arguments, they are read from standard input instead.
@@ -93,6 +133,141 @@ pub struct Cli {
     )]
     pub no_stdin: bool,
 
+    /// How to handle input which is not valid UTF-8
+    ///
+    /// - `fail` - Fail with an error (default).
+    /// - `lossy` - Replace invalid sequences with `U+FFFD` character.
+    /// - `escape` - Escape invalid bytes and `%` as `%xx` (e.g., `%FF`, `%25`).
+    ///
+    /// Escaped values are decoded back to raw bytes by path filters which access file system
+    /// and by output without `-p, --pretty` or `-j, --json-lines` flag.
+    /// Other filters treat each escaped byte as a single character, so they keep escapes intact.
+    /// This allows lossless processing of any path.
+    #[clap(
+        long,
+        value_name = "policy",
+        possible_values = ENCODING_POLICIES,
+        parse(try_from_str = parse_encoding_policy),
+        help_heading = INPUT_HEADING
+    )]
+    pub invalid_utf8: Option<EncodingPolicy>,
+
+    /// Sort input values before processing them
+    ///
+    /// - `natural` - Compare numbers inside values by their numeric value (`img2` before `img10`).
+    /// - `lexical` - Compare values character by character (`img10` before `img2`).
+    /// - `mtime` - Compare modification time of FS paths (oldest first).
+    /// - `size` - Compare size of FS paths (smallest first).
+    ///
+    /// All input values are read into memory before processing.
+    /// This makes counters reproducible regardless of the input order.
+    #[clap(
+        long,
+        value_name = "key",
+        possible_values = SORT_KEYS,
+        parse(try_from_str = parse_sort_key),
+        help_heading = INPUT_HEADING
+    )]
+    pub sort: Option<SortKey>,
+
+    /// Reverse order of input values (after sorting)
+    #[clap(long, help_heading = INPUT_HEADING)]
+    pub reverse: bool,
+
+    /// Skip duplicate input values (only the first occurrence is kept)
+    #[clap(long, help_heading = INPUT_HEADING)]
+    pub unique: bool,
+
+    /// Read values as paths found by walking a directory
+    ///
+    /// Entries of the directory are recursively visited in alphabetical order.
+    /// The directory itself is not included and symbolic links are not followed.
+    /// Hidden entries and entries ignored by `.gitignore` files inside the directory are skipped.
+    #[clap(
+        long,
+        value_name = "dir",
+        conflicts_with_all = &["values", "read", "read-nul", "read-raw", "read-end", "no-stdin"],
+        help_heading = WALK_HEADING
+    )]
+    pub walk: Option<PathBuf>,
+
+    /// Minimal depth of walked entries (direct children have depth 1)
+    #[clap(
+        long,
+        value_name = "number",
+        requires = "walk",
+        help_heading = WALK_HEADING
+    )]
+    pub min_depth: Option<usize>,
+
+    /// Maximal depth of walked entries (direct children have depth 1)
+    #[clap(
+        long,
+        value_name = "number",
+        requires = "walk",
+        help_heading = WALK_HEADING
+    )]
+    pub max_depth: Option<usize>,
+
+    /// Include hidden entries (with name starting with `.`)
+    #[clap(long, requires = "walk", help_heading = WALK_HEADING)]
+    pub hidden: bool,
+
+    /// Include entries ignored by `.gitignore` files
+    #[clap(long, requires = "walk", help_heading = WALK_HEADING)]
+    pub no_ignore: bool,
+
+    /// Include only files (and symbolic links), not directories
+    #[clap(
+        long,
+        requires = "walk",
+        conflicts_with = "dirs-only",
+        help_heading = WALK_HEADING
+    )]
+    pub files_only: bool,
+
+    /// Include only directories
+    #[clap(long, requires = "walk", help_heading = WALK_HEADING)]
+    pub dirs_only: bool,
+
+    /// Include only entries matching a glob pattern
+    ///
+    /// Patterns are matched against paths relative to the walked directory (e.g., `*.txt` or `src/**`).
+    /// Directories are still walked even if they do not match.
+    /// This option can be used multiple times.
+    #[clap(
+        long,
+        value_name = "glob",
+        requires = "walk",
+        multiple_occurrences = true,
+        number_of_values = 1,
+        parse(try_from_str = parse_glob),
+        help_heading = WALK_HEADING
+    )]
+    pub include: Vec<Glob>,
+
+    /// Exclude entries matching a glob pattern
+    ///
+    /// Patterns are matched against paths relative to the walked directory (e.g., `*.txt` or `target`).
+    /// Excluded directories are not walked.
+    /// This option can be used multiple times.
+    #[clap(
+        long,
+        value_name = "glob",
+        requires = "walk",
+        multiple_occurrences = true,
+        number_of_values = 1,
+        parse(try_from_str = parse_glob),
+        help_heading = WALK_HEADING
+    )]
+    pub exclude: Vec<Glob>,
+
+    /// Walk contents of a directory before the directory itself
+    ///
+    /// This allows renaming of directories after their contents.
+    #[clap(long, requires = "walk", help_heading = WALK_HEADING)]
+    pub contents_first: bool,
+
     /// Print results terminated by a specific string, not newline
     #[clap(
         short = 'T',
@@ -324,19 +499,51 @@ pub struct Cli {
     pub version: bool,
 }
 
-impl Options for Cli {
-    fn color(&self) -> Option<ColorChoice> {
-        self.color
+impl Cli {
+    pub fn encoding(&self) -> EncodingPolicy {
+        self.invalid_utf8.unwrap_or_default()
+    }
+
+    pub fn sort_config(&self) -> Option<SortConfig> {
+        if self.sort.is_some() || self.reverse || self.unique {
+            Some(SortConfig {
+                key: self.sort,
+                reverse: self.reverse,
+                unique: self.unique,
+                encoding: self.encoding(),
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn walk_config(&self) -> WalkConfig {
+        WalkConfig {
+            min_depth: self.min_depth.unwrap_or(1),
+            max_depth: self.max_depth,
+            hidden: self.hidden,
+            gitignore: !self.no_ignore,
+            entry_type: if self.files_only {
+                EntryType::File
+            } else if self.dirs_only {
+                EntryType::Dir
+            } else {
+                EntryType::Any
+            },
+            include: build_glob_set(&self.include),
+            exclude: build_glob_set(&self.exclude),
+            order: if self.contents_first {
+                Order::ContentsFirst
+            } else {
+                Order::DepthFirst
+            },
+        }
     }
 }
 
-pub fn parse_single_byte_char(string: &str) -> Result<u8, &'static str> {
-    if string.chars().count() != 1 {
-        Err("value must be a single character")
-    } else if string.len() != 1 {
-        Err("multi-byte characters are not supported")
-    } else {
-        Ok(string.as_bytes()[0])
+impl Options for Cli {
+    fn color(&self) -> Option<ColorChoice> {
+        self.color
     }
 }
 
@@ -352,24 +559,31 @@ mod tests {
         assert_eq!(run(args).color(), result);
     }
 
-    fn run(args: &[&str]) -> Cli {
-        Cli::try_parse_from(&[&["rew"], args].concat()).unwrap()
+    #[test_case(&[],                        EncodingPolicy::Fail   ; "default")]
+    #[test_case(&["--invalid-utf8=lossy"],  EncodingPolicy::Lossy  ; "lossy")]
+    #[test_case(&["--invalid-utf8=escape"], EncodingPolicy::Escape ; "escape")]
+    fn encoding(args: &[&str], result: EncodingPolicy) {
+        assert_eq!(run(args).encoding(), result);
     }
 
-    mod parse_single_byte_char {
-        use test_case::test_case;
-
-        use super::*;
-
-        #[test]
-        fn ok() {
-            assert_eq!(parse_single_byte_char("a"), Ok(b'a'));
-        }
+    #[test_case(&[],                                       None                                         ; "default")]
+    #[test_case(&["--sort=natural"],                       Some((Some(SortKey::Natural), false, false)) ; "sort")]
+    #[test_case(&["--reverse"],                            Some((None, true, false))                    ; "reverse")]
+    #[test_case(&["--unique"],                             Some((None, false, true))                    ; "unique")]
+    #[test_case(&["--sort=size", "--reverse", "--unique"], Some((Some(SortKey::Size), true, true))      ; "all")]
+    fn sort_config(args: &[&str], result: Option<(Option<SortKey>, bool, bool)>) {
+        assert_eq!(
+            run(args).sort_config(),
+            result.map(|(key, reverse, unique)| SortConfig {
+                key,
+                reverse,
+                unique,
+                encoding: EncodingPolicy::Fail,
+            })
+        );
+    }
 
-        #[test_case("á",  "multi-byte characters are not supported" ; "multi byte")]
-        #[test_case("aa", "value must be a single character"        ; "multi char")]
-        fn err(value: &str, error: &str) {
-            assert_eq!(parse_single_byte_char(value), Err(error));
-        }
+    fn run(args: &[&str]) -> Cli {
+        Cli::try_parse_from(&[&["rew"], args].concat()).unwrap()
     }
 }
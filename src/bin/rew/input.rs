@@ -1,40 +1,295 @@
-use std::io::{BufRead, Result};
+use std::io::{BufRead, Error, ErrorKind, Result};
 use std::slice::Iter;
+use std::vec::IntoIter;
 
+use common::encoding::{decode_bytes, decode_os_str, EncodingPolicy};
 use common::input::{Splitter, Terminator};
+use csv::{ByteRecord, Reader, ReaderBuilder};
+use serde_json::Value;
+
+use crate::sort;
+use crate::walk::Walker;
+
+pub struct Record<'a> {
+    pub value: &'a str,
+    pub columns: &'a [String],
+    pub variables: &'a [(String, String)],
+}
+
+pub struct OwnedRecord {
+    value: String,
+    columns: Vec<String>,
+    variables: Vec<(String, String)>,
+}
 
 pub enum Values<'a, A: AsRef<str>, I: BufRead> {
-    Args { iter: Iter<'a, A> },
-    Stdin { splitter: Splitter<I> },
+    Args {
+        iter: Iter<'a, A>,
+        encoding: EncodingPolicy,
+        value: String,
+    },
+    Stdin {
+        splitter: Splitter<I>,
+    },
+    Walk {
+        walker: Walker,
+        encoding: EncodingPolicy,
+        value: String,
+    },
+    Columns {
+        reader: Reader<I>,
+        encoding: EncodingPolicy,
+        record: ByteRecord,
+        columns: Vec<String>,
+    },
+    JsonLines {
+        splitter: Splitter<I>,
+        key: String,
+        item: usize,
+        value: String,
+        variables: Vec<(String, String)>,
+    },
+    Sorted {
+        records: IntoIter<OwnedRecord>,
+        record: Option<OwnedRecord>,
+    },
 }
 
 impl<'a, A: AsRef<str>, I: BufRead> Values<'a, A, I> {
-    pub fn from_args(values: &'a [A]) -> Self {
+    pub fn from_args(values: &'a [A], encoding: EncodingPolicy) -> Self {
         Values::Args {
             iter: values.iter(),
+            encoding,
+            value: String::new(),
         }
     }
 
-    pub fn from_stdin(stdin: I, terminator: Terminator) -> Self {
+    pub fn from_stdin(stdin: I, terminator: Terminator, encoding: EncodingPolicy) -> Self {
         Values::Stdin {
-            splitter: Splitter::new(stdin, terminator),
+            splitter: Splitter::with_policy(stdin, terminator, encoding),
         }
     }
 
+    pub fn from_walker(walker: Walker, encoding: EncodingPolicy) -> Self {
+        Values::Walk {
+            walker,
+            encoding,
+            value: String::new(),
+        }
+    }
+
+    pub fn from_columns(stdin: I, delimiter: u8, encoding: EncodingPolicy) -> Self {
+        let reader = ReaderBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(stdin);
+
+        Values::Columns {
+            reader,
+            encoding,
+            record: ByteRecord::new(),
+            columns: Vec::new(),
+        }
+    }
+
+    pub fn from_json_lines(
+        stdin: I,
+        terminator: Terminator,
+        encoding: EncodingPolicy,
+        key: &str,
+    ) -> Self {
+        Values::JsonLines {
+            splitter: Splitter::with_policy(stdin, terminator, encoding),
+            key: key.into(),
+            item: 0,
+            value: String::new(),
+            variables: Vec::new(),
+        }
+    }
+
+    pub fn sorted(mut self, config: &sort::Config) -> Result<Self> {
+        let mut records = Vec::new();
+
+        while let Some(record) = self.next_record()? {
+            records.push(OwnedRecord {
+                value: record.value.into(),
+                columns: record.columns.to_vec(),
+                variables: record.variables.to_vec(),
+            });
+        }
+
+        sort::sort(&mut records, config, |record| &record.value)?;
+
+        Ok(Values::Sorted {
+            records: records.into_iter(),
+            record: None,
+        })
+    }
+
     pub fn next(&mut self) -> Result<Option<&str>> {
-        match self {
-            Self::Args { iter } => Ok(iter.next().map(A::as_ref)),
-            Self::Stdin { splitter: reader } => Ok(reader.read()?.map(|(value, _)| value)),
+        Ok(self.next_record()?.map(|record| record.value))
+    }
+
+    pub fn next_record(&mut self) -> Result<Option<Record<'_>>> {
+        let value = match self {
+            Self::Args {
+                iter,
+                encoding,
+                value,
+            } => match iter.next() {
+                Some(arg) => {
+                    // Arguments are always valid UTF-8 but they still have to be escaped.
+                    *value = decode_bytes(arg.as_ref().as_bytes(), *encoding)?.into_owned();
+                    Some(value.as_str())
+                }
+                None => None,
+            },
+            Self::Stdin { splitter: reader } => reader.read()?.map(|(value, _)| value),
+            Self::Walk {
+                walker,
+                encoding,
+                value,
+            } => match walker.next()? {
+                Some(path) => {
+                    *value = decode_os_str(path.as_os_str(), *encoding)
+                        .map_err(|_| {
+                            Error::new(
+                                ErrorKind::InvalidData,
+                                format!("Path '{}' is not valid UTF-8", path.to_string_lossy()),
+                            )
+                        })?
+                        .into_owned();
+                    Some(value.as_str())
+                }
+                None => None,
+            },
+            Self::Columns {
+                reader,
+                encoding,
+                record,
+                columns,
+            } => {
+                if !reader.read_byte_record(record)? {
+                    return Ok(None);
+                }
+
+                columns.clear();
+                for field in record.iter() {
+                    columns.push(decode_bytes(field, *encoding)?.into_owned());
+                }
+
+                // The first column is the input value.
+                return Ok(Some(Record {
+                    value: columns.first().map_or("", String::as_str),
+                    columns,
+                    variables: &[],
+                }));
+            }
+            Self::JsonLines {
+                splitter,
+                key,
+                item,
+                value,
+                variables,
+            } => {
+                let line = match splitter.read()? {
+                    Some((line, _)) => line,
+                    None => return Ok(None),
+                };
+
+                *item += 1;
+                parse_json_record(line, key, *item, value, variables)?;
+
+                return Ok(Some(Record {
+                    value,
+                    columns: &[],
+                    variables,
+                }));
+            }
+            Self::Sorted { records, record } => {
+                *record = records.next();
+
+                return Ok(record.as_ref().map(|record| Record {
+                    value: &record.value,
+                    columns: &record.columns,
+                    variables: &record.variables,
+                }));
+            }
+        };
+
+        Ok(value.map(|value| Record {
+            value,
+            columns: &[],
+            variables: &[],
+        }))
+    }
+}
+
+fn parse_json_record(
+    line: &str,
+    key: &str,
+    item: usize,
+    value: &mut String,
+    variables: &mut Vec<(String, String)>,
+) -> Result<()> {
+    let record = serde_json::from_str::<Value>(line).map_err(|error| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid JSON: {} (item #{})", error, item),
+        )
+    })?;
+
+    let object = match record {
+        Value::Object(object) => object,
+        _ => return Err(make_json_field_error(key, item)),
+    };
+
+    let mut found = false;
+    variables.clear();
+
+    for (name, field) in object {
+        if name == key {
+            match field {
+                Value::String(string) => *value = string,
+                _ => return Err(make_json_field_error(key, item)),
+            }
+            found = true;
+        } else {
+            let string = match field {
+                Value::String(string) => string,
+                Value::Null => String::new(),
+                // Numbers, booleans, arrays and objects keep their JSON representation.
+                other => other.to_string(),
+            };
+            variables.push((name, string));
         }
     }
+
+    if found {
+        Ok(())
+    } else {
+        Err(make_json_field_error(key, item))
+    }
+}
+
+fn make_json_field_error(key: &str, item: usize) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Expected string field '{}' (item #{})", key, item),
+    )
 }
 
 #[cfg(test)]
 mod tests {
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
     use common::testing::unpack_io_error;
+    use ntest::*;
     use test_case::test_case;
 
     use super::*;
+    use crate::walk::Config;
 
     #[test_case(args(),  0, Some("a") ; "args 0")]
     #[test_case(args(),  1, Some("b") ; "args 1")]
@@ -49,11 +304,136 @@ mod tests {
         assert_eq!(values.next().map_err(unpack_io_error), Ok(result));
     }
 
+    #[test]
+    fn next_args_escape() {
+        let mut values = Values::<&str, &[u8]>::from_args(&["a%41"][..], EncodingPolicy::Escape);
+        assert_eq!(values.next().map_err(unpack_io_error), Ok(Some("a%2541")));
+    }
+
     fn args<'a>() -> Values<'a, &'a str, &'a [u8]> {
-        Values::from_args(&["a", "b"][..])
+        Values::from_args(&["a", "b"][..], EncodingPolicy::Fail)
     }
 
     fn stdin<'a>() -> Values<'a, &'a str, &'a [u8]> {
-        Values::from_stdin(&b"a\nb"[..], Terminator::Newline { required: false })
+        Values::from_stdin(
+            &b"a\nb"[..],
+            Terminator::Newline { required: false },
+            EncodingPolicy::Fail,
+        )
+    }
+
+    #[test_case(b',', "a,b\n\"c,\"\"d\"\"\",\"e\nf\"\n\ng", &[&["a", "b"], &["c,\"d\"", "e\nf"], &["g"]] ; "csv")]
+    #[test_case(b'\t', "a\tb c\r\n\"d\te\"\t",            &[&["a", "b c"], &["d\te", ""]]                ; "tsv")]
+    fn next_record_columns(delimiter: u8, input: &str, result: &[&[&str]]) {
+        let mut values =
+            Values::<&str, &[u8]>::from_columns(input.as_bytes(), delimiter, EncodingPolicy::Fail);
+
+        for columns in result {
+            let record = values.next_record().unwrap().unwrap();
+            assert_eq!(record.value, columns[0]);
+            assert_eq!(record.columns, *columns);
+        }
+
+        assert_true!(values.next_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn next_record_columns_escape() {
+        let mut values =
+            Values::<&str, &[u8]>::from_columns(&b"a\xFF,b"[..], b',', EncodingPolicy::Escape);
+        let record = values.next_record().unwrap().unwrap();
+        assert_eq!(record.value, "a%FF");
+        assert_eq!(record.columns, &["a%FF", "b"]);
+    }
+
+    #[test]
+    fn next_record_json_lines() {
+        let mut values = Values::<&str, &[u8]>::from_json_lines(
+            &b"{\"a\":\"b\",\"c\":\"d\",\"e\":1,\"f\":null}\n{\"a\":\"g\"}"[..],
+            Terminator::Newline { required: false },
+            EncodingPolicy::Fail,
+            "a",
+        );
+
+        let record = values.next_record().unwrap().unwrap();
+        assert_eq!(record.value, "b");
+        assert_eq!(
+            record.variables,
+            &[
+                ("c".into(), "d".into()),
+                ("e".into(), "1".into()),
+                ("f".into(), "".into())
+            ]
+        );
+
+        let record = values.next_record().unwrap().unwrap();
+        assert_eq!(record.value, "g");
+        assert_true!(record.variables.is_empty());
+
+        assert_true!(values.next_record().unwrap().is_none());
+    }
+
+    #[test_case("{\"a\":\"b\"}\n{", "Invalid JSON: EOF while parsing an object at line 1 column 1 (item #2)" ; "invalid")]
+    #[test_case("[\"a\"]",          "Expected string field 'a' (item #1)"                                    ; "not object")]
+    #[test_case("{\"b\":\"c\"}",    "Expected string field 'a' (item #1)"                                    ; "missing key")]
+    #[test_case("{\"a\":1}",        "Expected string field 'a' (item #1)"                                    ; "not string")]
+    fn next_record_json_lines_err(input: &str, error: &str) {
+        let mut values = Values::<&str, &[u8]>::from_json_lines(
+            input.as_bytes(),
+            Terminator::Newline { required: false },
+            EncodingPolicy::Fail,
+            "a",
+        );
+
+        let result = loop {
+            match values.next_record() {
+                Ok(Some(_)) => continue,
+                Ok(None) => break Ok(()),
+                Err(error) => break Err(unpack_io_error(error)),
+            }
+        };
+
+        assert_eq!(result, Err((ErrorKind::InvalidData, error.into())));
+    }
+
+    #[test]
+    fn next_walk() {
+        let dir = TempDir::new().unwrap();
+        dir.child("a").touch().unwrap();
+
+        let walker = Walker::new(dir.path(), Config::default());
+        let mut values = Values::<&str, &[u8]>::from_walker(walker, EncodingPolicy::Fail);
+        let path = dir.child("a").path().to_string_lossy().to_string();
+
+        assert_eq!(
+            values.next().map_err(unpack_io_error),
+            Ok(Some(path.as_str()))
+        );
+        assert_eq!(values.next().map_err(unpack_io_error), Ok(None));
+    }
+
+    #[test_case(EncodingPolicy::Fail,   Err("Path '{dir}/fo\u{FFFD}o' is not valid UTF-8") ; "fail")]
+    #[test_case(EncodingPolicy::Escape, Ok("{dir}/fo%80o")                                 ; "escape")]
+    #[cfg(unix)]
+    fn next_walk_non_utf8(encoding: EncodingPolicy, result: std::result::Result<&str, &str>) {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = TempDir::new().unwrap();
+        let name = std::ffi::OsStr::from_bytes(b"fo\x80o");
+        std::fs::write(dir.path().join(name), "").unwrap();
+
+        let walker = Walker::new(dir.path(), Config::default());
+        let mut values = Values::<&str, &[u8]>::from_walker(walker, encoding);
+        let dir_path = dir.path().to_str().unwrap();
+
+        assert_eq!(
+            values
+                .next()
+                .map(|value| value.map(String::from))
+                .map_err(unpack_io_error),
+            result
+                .map(|value| Some(value.replace("{dir}", dir_path)))
+                .map_err(|message| (ErrorKind::InvalidData, message.replace("{dir}", dir_path)))
+        );
     }
 }
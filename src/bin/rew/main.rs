@@ -11,6 +11,7 @@ use crate::output::write_pattern_error;
 use crate::pattern::parse::Separator;
 use crate::pattern::regex::RegexHolder;
 use crate::pattern::{eval, help, parse, Pattern};
+use crate::walk::Walker;
 
 mod cli;
 mod counter;
@@ -18,6 +19,8 @@ mod input;
 mod output;
 mod pattern;
 mod regex;
+mod sort;
+mod walk;
 
 const EXIT_CODE_PARSE_ERROR: i32 = 3;
 const EXIT_CODE_EVAL_ERROR: i32 = 4;
@@ -37,7 +40,19 @@ fn run(cli: &Cli, io: &Io) -> Result {
         return Ok(EXIT_CODE_OK);
     }
 
-    let mut input_values = if cli.values.is_empty() && !cli.no_stdin {
+    let encoding = cli.encoding();
+
+    let mut input_values = if let Some(walk_dir) = &cli.walk {
+        input::Values::from_walker(Walker::new(walk_dir, cli.walk_config()), encoding)
+    } else if cli.values.is_empty() && (cli.read_csv || cli.read_tsv) {
+        let delimiter = if cli.read_tsv { b'\t' } else { b',' };
+        input::Values::from_columns(io.stdin(), delimiter, encoding)
+    } else if let (true, Some(key)) = (cli.values.is_empty(), &cli.read_jsonl) {
+        let terminator = Terminator::Newline {
+            required: cli.read_end,
+        };
+        input::Values::from_json_lines(io.stdin(), terminator, encoding, key)
+    } else if cli.values.is_empty() && !cli.no_stdin {
         let required = cli.read_end;
         let terminator = if let Some(value) = cli.read {
             Terminator::Byte { value, required }
@@ -48,11 +63,15 @@ fn run(cli: &Cli, io: &Io) -> Result {
         } else {
             Terminator::Newline { required }
         };
-        input::Values::from_stdin(io.stdin(), terminator)
+        input::Values::from_stdin(io.stdin(), terminator, encoding)
     } else {
-        input::Values::from_args(cli.values.as_slice())
+        input::Values::from_args(cli.values.as_slice(), encoding)
     };
 
+    if let Some(sort_config) = cli.sort_config() {
+        input_values = input_values.sorted(&sort_config)?;
+    }
+
     let output_mode = if cli.pretty {
         output::Mode::Pretty
     } else if cli.diff {
@@ -75,7 +94,8 @@ fn run(cli: &Cli, io: &Io) -> Result {
         "\n"
     };
 
-    let mut output_values = output::Values::new(io.stdout(), output_mode, output_terminator);
+    let mut output_values =
+        output::Values::new(io.stdout(), output_mode, output_terminator, encoding);
     let mut exit_code = EXIT_CODE_OK;
 
     if let Some(raw_pattern) = cli.pattern.as_ref() {
@@ -92,6 +112,8 @@ fn run(cli: &Cli, io: &Io) -> Result {
         let parse_config = parse::Config {
             escape: cli.escape.unwrap_or('%'),
             separator,
+            columns: cli.read_csv || cli.read_tsv,
+            variables: cli.read_jsonl.is_some(),
         };
 
         let pattern = match Pattern::parse(raw_pattern, &parse_config) {
@@ -152,7 +174,9 @@ fn run(cli: &Cli, io: &Io) -> Result {
             _ => Some('"'),
         };
 
-        while let Some(input_value) = input_values.next()? {
+        while let Some(input_record) = input_values.next_record()? {
+            let input_value = input_record.value;
+
             let global_counter = if global_counter_used {
                 global_counter_generator.next()
             } else {
@@ -176,7 +200,10 @@ fn run(cli: &Cli, io: &Io) -> Result {
                 global_counter,
                 local_counter,
                 regex_captures,
+                columns: input_record.columns,
+                variables: input_record.variables,
                 expression_quotes,
+                encoding,
             };
 
             let output_value = match pattern.eval(input_value, &context) {
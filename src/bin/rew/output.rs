@@ -3,8 +3,10 @@ use std::io::{Result, Write};
 use std::ops::Range;
 
 use common::color::{spec_bold_color, spec_color};
+use common::encoding::{encode_str, EncodingPolicy};
 use common::output::write_error;
 use common::symbols::{DIFF_IN, DIFF_OUT};
+use serde_json::json;
 use termcolor::{Color, WriteColor};
 
 use crate::pattern::error::GetErrorRange;
@@ -21,16 +23,18 @@ pub struct Values<O: Write + WriteColor> {
     output: O,
     mode: Mode,
     terminator: String,
+    encoding: EncodingPolicy,
     first_result: bool,
     flush_needed: bool,
 }
 
 impl<O: Write + WriteColor> Values<O> {
-    pub fn new(output: O, mode: Mode, terminator: &str) -> Self {
+    pub fn new(output: O, mode: Mode, terminator: &str, encoding: EncodingPolicy) -> Self {
         Self {
             output,
             mode,
             terminator: terminator.into(),
+            encoding,
             first_result: true,
             flush_needed: !terminator.ends_with('\n'),
         }
@@ -39,7 +43,8 @@ impl<O: Write + WriteColor> Values<O> {
     pub fn write(&mut self, input_value: &str, output_value: &str) -> Result<()> {
         match self.mode {
             Mode::Standard => {
-                write!(self.output, "{}{}", output_value, self.terminator)?;
+                self.write_raw(output_value)?;
+                write!(self.output, "{}", self.terminator)?;
                 self.flush_if_needed()
             }
             Mode::StandardNoEnd => {
@@ -49,14 +54,14 @@ impl<O: Write + WriteColor> Values<O> {
                     write!(self.output, "{}", self.terminator)?;
                     self.flush_if_needed()?;
                 }
-                write!(self.output, "{}", output_value)
+                self.write_raw(output_value)
             }
             Mode::Diff => {
-                write!(
-                    self.output,
-                    "{}{}{}{}{}{}",
-                    DIFF_IN, input_value, self.terminator, DIFF_OUT, output_value, self.terminator
-                )?;
+                write!(self.output, "{}", DIFF_IN)?;
+                self.write_raw(input_value)?;
+                write!(self.output, "{}{}", self.terminator, DIFF_OUT)?;
+                self.write_raw(output_value)?;
+                write!(self.output, "{}", self.terminator)?;
                 self.flush_if_needed()
             }
             Mode::Pretty => {
@@ -68,15 +73,17 @@ impl<O: Write + WriteColor> Values<O> {
                 writeln!(self.output, "{}", output_value)
             }
             Mode::JsonLines => {
-                writeln!(
-                    self.output,
-                    r#"{{"in":"{}","out":"{}"}}"#,
-                    input_value, output_value
-                )
+                let record = json!({ "in": input_value, "out": output_value });
+                writeln!(self.output, "{}", record)
             }
         }
     }
 
+    fn write_raw(&mut self, value: &str) -> Result<()> {
+        // Escaped values are written as their original bytes.
+        self.output.write_all(&encode_str(value, self.encoding))
+    }
+
     fn flush_if_needed(&mut self) -> Result<()> {
         if self.flush_needed {
             self.output.flush()
@@ -124,6 +131,7 @@ pub fn highlight_range<O: Write + WriteColor>(
 mod tests {
     use common::testing::{ColoredOuput, OutputChunk};
     use indoc::indoc;
+    use termcolor::NoColor;
     use test_case::test_case;
 
     use super::*;
@@ -145,12 +153,33 @@ mod tests {
                                            "#})                      ; "json lines")]
     fn values_write(mode: Mode, terminator: &str, chunks: Vec<OutputChunk>) {
         let mut output = ColoredOuput::new();
-        let mut values = Values::new(&mut output, mode, terminator);
+        let mut values = Values::new(&mut output, mode, terminator, EncodingPolicy::Fail);
         values.write("a", "b").unwrap();
         values.write("c", "d").unwrap();
         assert_eq!(output.chunks(), &chunks);
     }
 
+    #[test]
+    fn values_write_json_escapes() {
+        let mut output = ColoredOuput::new();
+        let mut values = Values::new(&mut output, Mode::JsonLines, "\n", EncodingPolicy::Fail);
+        values.write("a\n\"b\"", "c\\d\t").unwrap();
+        assert_eq!(
+            output.chunks(),
+            &plain("{\"in\":\"a\\n\\\"b\\\"\",\"out\":\"c\\\\d\\t\"}\n")
+        );
+    }
+
+    #[test_case(Mode::Standard,  b"b\xE9%\n"              ; "standard")]
+    #[test_case(Mode::Diff,      b"<a\xE9\n>b\xE9%\n"      ; "diff")]
+    #[test_case(Mode::JsonLines, b"{\"in\":\"a%E9\",\"out\":\"b%E9%25\"}\n" ; "json lines")]
+    fn values_write_escaped(mode: Mode, result: &[u8]) {
+        let mut output = NoColor::new(Vec::new());
+        let mut values = Values::new(&mut output, mode, "\n", EncodingPolicy::Escape);
+        values.write("a%E9", "b%E9%25").unwrap();
+        assert_eq!(output.into_inner(), result);
+    }
+
     pub fn plain(value: &str) -> Vec<OutputChunk> {
         vec![OutputChunk::plain(value)]
     }
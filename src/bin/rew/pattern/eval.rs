@@ -1,6 +1,8 @@
 use std::path::Path;
 use std::{error, fmt, result};
 
+use common::encoding::EncodingPolicy;
+
 use crate::pattern::error::{ErrorRange, GetErrorRange};
 use crate::pattern::filter::Filter;
 use crate::pattern::utils::AnyString;
@@ -12,7 +14,10 @@ pub struct Context<'a> {
     pub global_counter: Counter,
     pub local_counter: Counter,
     pub regex_captures: Option<regex::Captures<'a>>,
+    pub columns: &'a [String],
+    pub variables: &'a [(String, String)],
     pub expression_quotes: Option<char>,
+    pub encoding: EncodingPolicy,
 }
 
 impl<'a> Context<'a> {
@@ -24,6 +29,17 @@ impl<'a> Context<'a> {
             .map_or("", |capture| capture.as_str())
     }
 
+    pub fn column(&self, index: usize) -> &str {
+        self.columns.get(index).map_or("", String::as_str)
+    }
+
+    pub fn variable(&self, name: &str) -> &str {
+        self.variables
+            .iter()
+            .find(|(key, _)| key == name)
+            .map_or("", |(_, value)| value.as_str())
+    }
+
     #[cfg(test)]
     pub fn fixture() -> Self {
         Context {
@@ -34,7 +50,10 @@ impl<'a> Context<'a> {
             local_counter: 1,
             global_counter: 2,
             regex_captures: regex::Regex::new("(.).(.)").unwrap().captures("abc"),
+            columns: &[],
+            variables: &[],
             expression_quotes: None,
+            encoding: EncodingPolicy::Fail,
         }
     }
 }
@@ -113,6 +132,26 @@ mod tests {
         }
     }
 
+    #[test_case(0, "a" ; "index 0")]
+    #[test_case(1, "b" ; "index 1")]
+    #[test_case(2, ""  ; "index 2")]
+    fn eval_context_column(index: usize, result: &str) {
+        let columns = ["a".to_string(), "b".to_string()];
+        let mut context = Context::fixture();
+        context.columns = &columns;
+        assert_eq!(context.column(index), result);
+    }
+
+    #[test_case("a", "1" ; "first")]
+    #[test_case("b", "2" ; "second")]
+    #[test_case("c", ""  ; "missing")]
+    fn eval_context_variable(name: &str, result: &str) {
+        let variables = [("a".into(), "1".into()), ("b".into(), "2".into())];
+        let mut context = Context::fixture();
+        context.variables = &variables;
+        assert_eq!(context.variable(name), result);
+    }
+
     mod error {
         use super::*;
 
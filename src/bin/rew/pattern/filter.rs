@@ -1,9 +1,11 @@
 use std::fmt;
 
+use common::encoding::{escaped_to_text, text_to_escaped, EncodingPolicy};
 use unidecode::unidecode;
 
 use crate::pattern::char::{AsChar, Char};
 use crate::pattern::field::Field;
+use crate::pattern::index::parse_index;
 use crate::pattern::integer::parse_integer;
 use crate::pattern::number::NumberRange;
 use crate::pattern::padding::Padding;
@@ -46,6 +48,8 @@ pub enum Filter {
     RegexReplaceAll(RegexSubstitution),
     RegexSwitch(RegexSwitch),
     RegexCapture(usize),
+    Column(usize),
+    Variable(String),
     Trim,
     ToLowercase,
     ToUppercase,
@@ -64,6 +68,18 @@ impl Filter {
     pub fn parse(reader: &mut Reader<Char>, config: &parse::Config) -> parse::Result<Self> {
         let position = reader.position();
 
+        if let Some('0'..='9') = reader.peek_char() {
+            let index = parse_index(reader)?;
+            return if config.columns {
+                Ok(Self::Column(index))
+            } else {
+                Err(parse::Error {
+                    kind: parse::ErrorKind::ColumnWithoutCsvInput,
+                    range: position..reader.position(),
+                })
+            };
+        }
+
         if let Some(char) = reader.read() {
             match char.as_char() {
                 'w' => Ok(Self::WorkingDir),
@@ -104,6 +120,23 @@ impl Filter {
                 'S' => Ok(Self::RegexReplaceAll(RegexSubstitution::parse(reader)?)),
                 '@' => Ok(Self::RegexSwitch(RegexSwitch::parse(reader)?)),
                 '$' => Ok(Self::RegexCapture(parse_integer(reader)?)),
+                '.' => {
+                    let name_start = reader.position();
+                    let name = reader.read_to_end().to_string();
+                    if name.is_empty() {
+                        Err(parse::Error {
+                            kind: parse::ErrorKind::ExpectedVariableName,
+                            range: name_start..reader.position(),
+                        })
+                    } else if !config.variables {
+                        Err(parse::Error {
+                            kind: parse::ErrorKind::VariableWithoutJsonInput,
+                            range: position..reader.position(),
+                        })
+                    } else {
+                        Ok(Self::Variable(name))
+                    }
+                }
                 't' => Ok(Self::Trim),
                 'v' => Ok(Self::ToLowercase),
                 '^' => Ok(Self::ToUppercase),
@@ -129,13 +162,43 @@ impl Filter {
         }
     }
 
-    pub fn eval(&self, mut value: String, context: &eval::Context) -> eval::BaseResult<String> {
+    pub fn eval(&self, value: String, context: &eval::Context) -> eval::BaseResult<String> {
+        if context.encoding == EncodingPolicy::Escape && self.is_text_filter() {
+            // Escape sequences have to stay intact, so they are processed as single characters.
+            let text = escaped_to_text(&value).into_owned();
+            let result = self.eval_value(text, context)?;
+            return Ok(text_to_escaped(&result).into_owned());
+        }
+
+        self.eval_value(value, context)
+    }
+
+    fn is_text_filter(&self) -> bool {
+        // Filters accessing file system work with escaped values directly
+        // and the rest does not depend on the input value at all.
+        !matches!(
+            self,
+            Self::WorkingDir
+                | Self::AbsolutePath
+                | Self::RelativePath
+                | Self::CanonicalPath
+                | Self::RegexCapture(_)
+                | Self::Column(_)
+                | Self::Variable(_)
+                | Self::LocalCounter
+                | Self::GlobalCounter
+                | Self::RandomNumber(_)
+                | Self::RandomUuid
+        )
+    }
+
+    fn eval_value(&self, mut value: String, context: &eval::Context) -> eval::BaseResult<String> {
         match self {
-            Self::WorkingDir => path::to_string(context.working_dir),
-            Self::AbsolutePath => path::to_absolute(value, context.working_dir),
-            Self::RelativePath => path::to_relative(value, context.working_dir),
+            Self::WorkingDir => path::decode(context.working_dir, context.encoding),
+            Self::AbsolutePath => path::to_absolute(value, context.working_dir, context.encoding),
+            Self::RelativePath => path::to_relative(value, context.working_dir, context.encoding),
             Self::NormalizedPath => path::normalize(&value),
-            Self::CanonicalPath => path::canonicalize(value, context.working_dir),
+            Self::CanonicalPath => path::canonicalize(value, context.working_dir, context.encoding),
             Self::ParentDirectory => path::get_parent_directory(value),
             Self::RemoveLastName => path::remove_last_name(value),
             Self::FileName => path::get_file_name(&value),
@@ -158,6 +221,8 @@ impl Filter {
             Self::RegexReplaceAll(substitution) => Ok(substitution.replace_all(&value)),
             Self::RegexSwitch(switch) => Ok(switch.eval(&value).to_string()),
             Self::RegexCapture(number) => Ok(context.regex_capture(*number).to_string()),
+            Self::Column(index) => Ok(context.column(*index).to_string()),
+            Self::Variable(name) => Ok(context.variable(name).to_string()),
             Self::Trim => Ok(value.trim().to_string()),
             Self::ToLowercase => Ok(value.to_lowercase()),
             Self::ToUppercase => Ok(value.to_uppercase()),
@@ -233,6 +298,10 @@ impl fmt::Display for Filter {
                     number
                 )
             }
+            Self::Column(index) => {
+                write!(formatter, "Column #{} of a CSV/TSV input record", index + 1)
+            }
+            Self::Variable(name) => write!(formatter, "Variable '{}' of a JSON input record", name),
             Self::Trim => write!(formatter, "Trim"),
             Self::ToLowercase => write!(formatter, "To lowercase"),
             Self::ToUppercase => write!(formatter, "To uppercase"),
@@ -300,6 +369,8 @@ mod tests {
         #[test_case("S/[0/",    2..4, E::RegexInvalid(AnyString::any())              ; "regex replace all invalid regex")]
         #[test_case("@:[0:X:Y", 2..4, E::RegexInvalid(AnyString::any())              ; "regex switch invalid regex")]
         #[test_case("$",        1..1, E::ExpectedNumber                              ; "regex capture expected number")]
+        #[test_case("0",        0..1, E::IndexZero                                   ; "column index zero")]
+        #[test_case(".",        1..1, E::ExpectedVariableName                        ; "variable expected name")]
         #[test_case("<x",       1..2, E::PaddingPrefixInvalid('<', Some('x'.into())) ; "padding left prefix invalid")]
         #[test_case(">y",       1..2, E::PaddingPrefixInvalid('>', Some('y'.into())) ; "padding right prefix invalid")]
         fn err(input: &str, range: ErrorRange, kind: ErrorKind) {
@@ -309,6 +380,19 @@ mod tests {
             )
         }
 
+        #[test_case("12",    0..2, E::ColumnWithoutCsvInput    ; "column")]
+        #[test_case(".date", 0..5, E::VariableWithoutJsonInput ; "variable")]
+        fn err_without_records(input: &str, range: ErrorRange, kind: ErrorKind) {
+            let mut config = Config::fixture();
+            config.columns = false;
+            config.variables = false;
+
+            assert_eq!(
+                Filter::parse(&mut Reader::from(input), &config),
+                Err(Error { kind, range }),
+            )
+        }
+
         #[test_case("w",            F::WorkingDir                           ; "working dir")]
         #[test_case("a",            F::AbsolutePath                         ; "absolute path")]
         #[test_case("A",            F::RelativePath                         ; "relative path")]
@@ -351,6 +435,9 @@ mod tests {
         #[test_case("@:[0-9]+:X:Y", F::RegexSwitch(regex_switch())          ; "regex switch ")]
         #[test_case("$0",           F::RegexCapture(0)                      ; "regex capture 0")]
         #[test_case("$10",          F::RegexCapture(10)                     ; "regex capture 10")]
+        #[test_case("1",            F::Column(0)                            ; "column 1")]
+        #[test_case("10",           F::Column(9)                            ; "column 10")]
+        #[test_case(".date",        F::Variable("date".into())              ; "variable")]
         #[test_case("t",            F::Trim                                 ; "trim")]
         #[test_case("v",            F::ToLowercase                          ; "to lowercase")]
         #[test_case("^",            F::ToUppercase                          ; "to uppercase")]
@@ -390,8 +477,26 @@ mod tests {
 
         use super::*;
         use crate::pattern::eval::{Context, ErrorKind};
+        use crate::pattern::parse::Config;
+        use crate::pattern::reader::Reader;
         use crate::pattern::uuid::assert_uuid;
 
+        #[test_case("caf%E9", "#1-4",  "caf%E9" ; "substring")]
+        #[test_case("caf%E9", "#-1",   "%E9"    ; "substring rev")]
+        #[test_case("caf%E9", "r:E:Z", "caf%E9" ; "replace")]
+        #[test_case("caf%E9", "s:E:Z", "caf%E9" ; "regex replace")]
+        #[test_case("caf%E9", "s:.$",  "caf"    ; "regex replace byte")]
+        #[test_case("caf%e9", "^",     "CAF%E9" ; "to uppercase")]
+        #[test_case("a%25b",  "r:%:-", "a-b"    ; "escape char")]
+        #[test_case("%E9",    "<<aaa", "aa%E9"  ; "left pad")]
+        #[test_case("%E9",    "$0",    "abc"    ; "regex capture")]
+        fn escape(input: &str, filter: &str, output: &str) {
+            let filter = Filter::parse(&mut Reader::from(filter), &Config::fixture()).unwrap();
+            let mut context = Context::fixture();
+            context.encoding = common::encoding::EncodingPolicy::Escape;
+            assert_eq!(filter.eval(input.into(), &context), Ok(output.into()))
+        }
+
         #[test_case("non-existent", F::CanonicalPath, ErrorKind::CanonicalizationFailed(AnyString::any()) ; "canonicalization failed")]
         fn err(input: &str, filter: Filter, kind: ErrorKind) {
             assert_eq!(filter.eval(input.into(), &Context::fixture()), Err(kind))
@@ -443,6 +548,8 @@ mod tests {
         #[test_case("1",             F::RegexSwitch(regex_switch()),         "X"        ; "regex switch case")]
         #[test_case("a",             F::RegexSwitch(regex_switch()),         "Y"        ; "regex switch default")]
         #[test_case("",              F::RegexCapture(1),                     "a"        ; "regex capture")]
+        #[test_case("a",             F::Column(0),                           ""         ; "column")]
+        #[test_case("a",             F::Variable("x".into()),                ""         ; "variable")]
         #[test_case(" abcd ",        F::Trim,                                "abcd"     ; "trim")]
         #[test_case("ábčdÁBČD",      F::ToLowercase,                         "ábčdábčd" ; "to lowercase")]
         #[test_case("ábčdÁBČD",      F::ToUppercase,                         "ÁBČDÁBČD" ; "to uppercase")]
@@ -526,6 +633,8 @@ mod tests {
         "regex switch "
     )]
     #[test_case(F::RegexCapture(1),                      "Capture group #1 of a global regular expression" ; "regex capture")]
+    #[test_case(F::Column(1),                            "Column #2 of a CSV/TSV input record"             ; "column")]
+    #[test_case(F::Variable("x".into()),                 "Variable 'x' of a JSON input record"             ; "variable")]
     #[test_case(F::Trim,                                 "Trim"                                            ; "trim")]
     #[test_case(F::ToLowercase,                          "To lowercase"                                    ; "to lowercase")]
     #[test_case(F::ToUppercase,                          "To uppercase"                                    ; "to uppercase")]
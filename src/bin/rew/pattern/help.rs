@@ -57,6 +57,8 @@ pub const FILTERS: &str = indoc! {r#"
   `&N:S`  Field `N`, string separator `S`   (`:` = any delimiter char except `/`)
   `&N/S`  Field `N`, regex separator `S`    (`N` = 1-based index)
   `&N`    Field `N`, default separator   (`-N` = backward indexing)
+  `N`     Column `N` of a CSV/TSV record (see `--read-csv`)
+  `.K`    Variable `K` of a JSON record  (see `--read-jsonl`)
 
 # REPLACEMENT
 
@@ -36,14 +36,14 @@ impl Padding {
     }
 
     pub fn apply_left(&self, mut value: String) -> String {
-        for char in self.expand().chars().rev().skip(value.len()) {
+        for char in self.expand().chars().rev().skip(value.chars().count()) {
             value.insert(0, char);
         }
         value
     }
 
     pub fn apply_right(&self, mut value: String) -> String {
-        for char in self.expand().chars().skip(value.len()) {
+        for char in self.expand().chars().skip(value.chars().count()) {
             value.push(char);
         }
         value
@@ -101,6 +101,7 @@ mod tests {
         #[test_case("abcd", "",     "abcd" ; "nonempty with empty")]
         #[test_case("abcd", "0123", "abcd" ; "nonempty same length")]
         #[test_case("ab",   "0123", "01ab" ; "shorter with longer")]
+        #[test_case("áb",   "0123", "01áb" ; "shorter with longer non-ascii")]
         fn apply_left(input: &str, padding: &str, output: &str) {
             assert_eq!(
                 Padding::Fixed(padding.into()).apply_left(input.into()),
@@ -113,6 +114,7 @@ mod tests {
         #[test_case("abcd", "",     "abcd" ; "nonempty with empty")]
         #[test_case("abcd", "0123", "abcd" ; "nonempty same length")]
         #[test_case("ab",   "0123", "ab23" ; "shorter with longer")]
+        #[test_case("áb",   "0123", "áb23" ; "shorter with longer non-ascii")]
         fn apply_right(input: &str, padding: &str, output: &str) {
             assert_eq!(
                 Padding::Fixed(padding.into()).apply_right(input.into()),
@@ -12,6 +12,10 @@ use crate::pattern::utils::AnyString;
 pub struct Config {
     pub escape: char,
     pub separator: Separator,
+    // Whether input values are CSV/TSV records with columns.
+    pub columns: bool,
+    // Whether input values are JSON records with variables.
+    pub variables: bool,
 }
 
 #[cfg(test)]
@@ -20,6 +24,8 @@ impl Config {
         Self {
             escape: '%',
             separator: Separator::Regex("\\s+".into()),
+            columns: true,
+            variables: true,
         }
     }
 }
@@ -77,6 +83,7 @@ impl fmt::Display for Error {
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum ErrorKind {
+    ColumnWithoutCsvInput,
     ExpectedDelimiterChar,
     ExpectedFieldSeparator,
     ExpectedFilter,
@@ -90,6 +97,7 @@ pub enum ErrorKind {
     ExpectedRepetition,
     ExpectedSubstitution,
     ExpectedRegexSwitch,
+    ExpectedVariableName,
     ExprStartInsideExpr,
     IndexZero,
     IntegerOverflow(String),
@@ -105,6 +113,7 @@ pub enum ErrorKind {
     UnmatchedExprEnd,
     UnmatchedExprStart,
     UnterminatedEscapeSequence(char),
+    VariableWithoutJsonInput,
 }
 
 #[derive(PartialEq, Debug)]
@@ -116,6 +125,7 @@ pub enum ErrorHint {
 impl ErrorKind {
     pub fn hint(&self) -> Option<ErrorHint> {
         match self {
+            Self::ColumnWithoutCsvInput => None,
             Self::ExpectedDelimiterChar => Some(ErrorHint::FilterUsage),
             Self::ExpectedFieldSeparator => Some(ErrorHint::FilterUsage),
             Self::ExpectedFilter => Some(ErrorHint::PatternSyntax),
@@ -129,6 +139,7 @@ impl ErrorKind {
             Self::ExpectedRepetition => Some(ErrorHint::FilterUsage),
             Self::ExpectedSubstitution => Some(ErrorHint::FilterUsage),
             Self::ExpectedRegexSwitch => Some(ErrorHint::FilterUsage),
+            Self::ExpectedVariableName => Some(ErrorHint::FilterUsage),
             Self::ExprStartInsideExpr => Some(ErrorHint::PatternSyntax),
             Self::IndexZero => Some(ErrorHint::FilterUsage),
             Self::IntegerOverflow(_) => None,
@@ -144,6 +155,7 @@ impl ErrorKind {
             Self::UnmatchedExprEnd => Some(ErrorHint::PatternSyntax),
             Self::UnmatchedExprStart => Some(ErrorHint::PatternSyntax),
             Self::UnterminatedEscapeSequence(_) => Some(ErrorHint::PatternSyntax),
+            Self::VariableWithoutJsonInput => None,
         }
     }
 }
@@ -157,6 +169,10 @@ impl From<Infallible> for ErrorKind {
 impl fmt::Display for ErrorKind {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            Self::ColumnWithoutCsvInput => write!(
+                formatter,
+                "Column filter requires '--read-csv' or '--read-tsv' input"
+            ),
             Self::ExpectedDelimiterChar => write!(formatter, "Expected delimiter character"),
             Self::ExpectedFieldSeparator => write!(formatter, "Expected field separator"),
             Self::ExpectedFilter => write!(formatter, "Expected filter after '{}'", PIPE),
@@ -196,6 +212,7 @@ impl fmt::Display for ErrorKind {
                 write!(formatter, "Expected repetition 'N:V' or 'N'")
             }
             Self::ExpectedSubstitution => write!(formatter, "Expected substitution ':A:B' or ':A'"),
+            Self::ExpectedVariableName => write!(formatter, "Expected variable name"),
             Self::ExprStartInsideExpr => {
                 write!(formatter, "Unescaped '{}' inside expression", EXPR_START)
             }
@@ -260,6 +277,9 @@ impl fmt::Display for ErrorKind {
                     escape_char(*escape)
                 )
             }
+            Self::VariableWithoutJsonInput => {
+                write!(formatter, "Variable filter requires '--read-jsonl' input")
+            }
         }
     }
 }
@@ -312,6 +332,7 @@ mod tests {
         type E = ErrorKind;
         type H = ErrorHint;
 
+        #[test_case(E::ColumnWithoutCsvInput,                     None                   ; "column without csv input")]
         #[test_case(E::ExpectedDelimiterChar,                     Some(H::FilterUsage)   ; "expected delimiter char")]
         #[test_case(E::ExpectedFieldSeparator,                    Some(H::FilterUsage)   ; "expected field separator")]
         #[test_case(E::ExpectedFilter,                            Some(H::PatternSyntax) ; "expected filter")]
@@ -323,6 +344,7 @@ mod tests {
         #[test_case(E::ExpectedRangeLength,                       Some(H::FilterUsage)   ; "expected range length")]
         #[test_case(E::ExpectedRegex,                             Some(H::FilterUsage)   ; "expected regex")]
         #[test_case(E::ExpectedRegexSwitch,                       Some(H::FilterUsage)   ; "expected regex switch")]
+        #[test_case(E::ExpectedVariableName,                      Some(H::FilterUsage)   ; "expected variable name")]
         #[test_case(E::ExpectedRepetition,                        Some(H::FilterUsage)   ; "expected repetition")]
         #[test_case(E::ExpectedSubstitution,                      Some(H::FilterUsage)   ; "expected substitution")]
         #[test_case(E::ExprStartInsideExpr,                       Some(H::PatternSyntax) ; "expr start inside expr")]
@@ -340,10 +362,12 @@ mod tests {
         #[test_case(E::UnmatchedExprEnd,                          Some(H::PatternSyntax) ; "unmatched expr end")]
         #[test_case(E::UnmatchedExprStart,                        Some(H::PatternSyntax) ; "unmatched expr start")]
         #[test_case(E::UnterminatedEscapeSequence('%'),           Some(H::PatternSyntax) ; "unterminated escape sequence")]
+        #[test_case(E::VariableWithoutJsonInput,                  None                   ; "variable without json input")]
         fn hint(kind: ErrorKind, hint: Option<ErrorHint>) {
             assert_eq!(kind.hint(), hint);
         }
 
+        #[test_case(E::ColumnWithoutCsvInput,                       "Column filter requires '--read-csv' or '--read-tsv' input"         ; "column without csv input")]
         #[test_case(E::ExpectedDelimiterChar,                       "Expected delimiter character"                                      ; "expected delimiter character")]
         #[test_case(E::ExpectedFieldSeparator,                      "Expected field separator"                                          ; "expected field separator")]
         #[test_case(E::ExpectedFilter,                              "Expected filter after '|'"                                         ; "expected filter")]
@@ -356,6 +380,7 @@ mod tests {
         #[test_case(E::ExpectedRangeLength,                         "Expected range length after '+'"                                   ; "expected range length")]
         #[test_case(E::ExpectedRegex,                               "Expected regular expression"                                       ; "expected regex")]
         #[test_case(E::ExpectedRegexSwitch,                         "Expected regular expression switch ':X1:Y1:...:Xn:Yn:D'"           ; "expected switch")]
+        #[test_case(E::ExpectedVariableName,                        "Expected variable name"                                            ; "expected variable name")]
         #[test_case(E::ExpectedRepetition,                          "Expected repetition 'N:V' or 'N'"                                  ; "expected repetition")]
         #[test_case(E::ExpectedSubstitution,                        "Expected substitution ':A:B' or ':A'"                              ; "expected substitution")]
         #[test_case(E::ExprStartInsideExpr,                         "Unescaped '{' inside expression"                                   ; "expr start inside expr")]
@@ -374,6 +399,7 @@ mod tests {
         #[test_case(E::UnmatchedExprEnd,                            "No matching '{' before expression end"                             ; "unmatched expr end")]
         #[test_case(E::UnmatchedExprStart,                          "No matching '}' after expression start"                            ; "unmatched expr start")]
         #[test_case(E::UnterminatedEscapeSequence('%'),             "Unterminated escape sequence '%'"                                  ; "unterminated escape sequence")]
+        #[test_case(E::VariableWithoutJsonInput,                    "Variable filter requires '--read-jsonl' input"                     ; "variable without json input")]
         fn display(kind: ErrorKind, result: &str) {
             assert_eq!(kind.to_string(), result);
         }
@@ -1,40 +1,53 @@
 use std::ffi::OsStr;
 use std::path::{is_separator, Component, Path, PathBuf, MAIN_SEPARATOR};
 
+use common::encoding::{decode_os_str, encode_os_string, EncodingPolicy};
 use normpath::PathExt;
 use pathdiff::diff_paths;
 
 use crate::pattern::eval::{BaseResult, ErrorKind};
 use crate::pattern::utils::AnyString;
 
-pub fn to_absolute(value: String, working_dir: &Path) -> BaseResult<String> {
+pub fn to_absolute(
+    value: String,
+    working_dir: &Path,
+    encoding: EncodingPolicy,
+) -> BaseResult<String> {
     if value.is_empty() {
-        to_string(working_dir)
+        decode(working_dir, encoding)
     } else {
-        let path = Path::new(&value);
+        let path = encode(&value, encoding);
         if path.is_absolute() {
             Ok(value)
         } else {
-            into_string(working_dir.join(path))
+            decode(&working_dir.join(path), encoding)
         }
     }
 }
 
-pub fn to_relative(value: String, working_dir: &Path) -> BaseResult<String> {
-    let path = Path::new(&value);
+pub fn to_relative(
+    value: String,
+    working_dir: &Path,
+    encoding: EncodingPolicy,
+) -> BaseResult<String> {
+    let path = encode(&value, encoding);
     if path.is_relative() {
         Ok(value)
     } else {
-        into_string(diff_paths(path, working_dir).unwrap_or_default())
+        decode(&diff_paths(path, working_dir).unwrap_or_default(), encoding)
     }
 }
 
-pub fn canonicalize(value: String, working_dir: &Path) -> BaseResult<String> {
-    let absolute_value = to_absolute(value, working_dir)?;
-    let absolute_path = Path::new(&absolute_value);
+pub fn canonicalize(
+    value: String,
+    working_dir: &Path,
+    encoding: EncodingPolicy,
+) -> BaseResult<String> {
+    let absolute_value = to_absolute(value, working_dir, encoding)?;
+    let absolute_path = encode(&absolute_value, encoding);
 
     match absolute_path.normalize() {
-        Ok(result) => into_string(result.into_path_buf()).map(|mut result| {
+        Ok(result) => decode(result.as_path(), encoding).map(|mut result| {
             // Normalize unix vs windows behaviour
             if cfg!(windows)
                 && result.ends_with(MAIN_SEPARATOR)
@@ -195,6 +208,17 @@ pub fn remove_trailing_dir_separator(mut value: String) -> String {
     value
 }
 
+// Paths coming from the file system are converted to values according to the encoding policy.
+pub fn decode<S: AsRef<OsStr> + ?Sized>(value: &S, encoding: EncodingPolicy) -> BaseResult<String> {
+    decode_os_str(value.as_ref(), encoding)
+        .map(|value| value.into_owned())
+        .map_err(|_| ErrorKind::InputNotUtf8)
+}
+
+fn encode(value: &str, encoding: EncodingPolicy) -> PathBuf {
+    PathBuf::from(encode_os_string(value, encoding))
+}
+
 pub fn into_string(value: PathBuf) -> BaseResult<String> {
     match value.into_os_string().into_string() {
         Ok(result) => Ok(result),
@@ -230,7 +254,7 @@ mod tests {
     fn to_absolute(input: &str, output: &str) {
         let working_dir = std::env::current_dir().unwrap();
         assert_eq!(
-            super::to_absolute(input.into(), &working_dir),
+            super::to_absolute(input.into(), &working_dir, EncodingPolicy::Fail),
             Ok(fmt_working_dir(output, &working_dir))
         );
     }
@@ -243,11 +267,43 @@ mod tests {
     fn to_relative(input: &str, output: &str) {
         let working_dir = std::env::current_dir().unwrap();
         assert_eq!(
-            super::to_relative(fmt_working_dir(input, &working_dir), &working_dir),
+            super::to_relative(
+                fmt_working_dir(input, &working_dir),
+                &working_dir,
+                EncodingPolicy::Fail
+            ),
             Ok(fmt_working_dir(output, &working_dir))
         );
     }
 
+    #[test_case(EncodingPolicy::Fail,   Err(ErrorKind::InputNotUtf8) ; "fail")]
+    #[test_case(EncodingPolicy::Lossy,  Ok("/fo\u{FFFD}o/a")       ; "lossy")]
+    #[test_case(EncodingPolicy::Escape, Ok("/fo%80o/a")             ; "escape")]
+    #[cfg(unix)]
+    fn to_absolute_non_utf8(encoding: EncodingPolicy, result: BaseResult<&str>) {
+        let working_dir = Path::new("/").join(make_non_utf8_os_string());
+        assert_eq!(
+            super::to_absolute("a".into(), &working_dir, encoding),
+            result.map(String::from)
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn canonicalize_escaped() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let file = dir.path().join(make_non_utf8_os_string());
+        std::fs::write(&file, "").unwrap();
+
+        let working_dir = dir.path().canonicalize().unwrap();
+        let expected = format!("{}/fo%80o", working_dir.to_str().unwrap());
+
+        assert_eq!(
+            super::canonicalize("fo%80o".into(), &working_dir, EncodingPolicy::Escape),
+            Ok(expected)
+        );
+    }
+
     mod canonicalize {
         use test_case::test_case;
 
@@ -257,7 +313,7 @@ mod tests {
         fn err() {
             let working_dir = std::env::current_dir().unwrap();
             assert_eq!(
-                super::canonicalize("non-existent".into(), &working_dir),
+                super::canonicalize("non-existent".into(), &working_dir, EncodingPolicy::Fail),
                 Err(ErrorKind::CanonicalizationFailed(AnyString::any()))
             );
         }
@@ -271,7 +327,7 @@ mod tests {
         fn ok(input: &str, output: &str) {
             let working_dir = std::env::current_dir().unwrap();
             assert_eq!(
-                super::canonicalize(input.into(), &working_dir),
+                super::canonicalize(input.into(), &working_dir, EncodingPolicy::Fail),
                 Ok(fmt_working_dir(output, &working_dir))
             );
         }
@@ -0,0 +1,253 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fs::{self, Metadata};
+use std::io::{Error, Result};
+use std::path::PathBuf;
+
+use common::encoding::{encode_os_string, EncodingPolicy};
+
+pub const SORT_KEYS: &[&str] = &[NATURAL, LEXICAL, MTIME, SIZE];
+
+const NATURAL: &str = "natural";
+const LEXICAL: &str = "lexical";
+const MTIME: &str = "mtime";
+const SIZE: &str = "size";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    // Numbers inside values are compared by their numeric value.
+    Natural,
+    // Values are compared character by character.
+    Lexical,
+    // Values are FS paths compared by their modification time.
+    Mtime,
+    // Values are FS paths compared by their size.
+    Size,
+}
+
+pub fn parse_sort_key(string: &str) -> std::result::Result<Key, &'static str> {
+    match string {
+        NATURAL => Ok(Key::Natural),
+        LEXICAL => Ok(Key::Lexical),
+        MTIME => Ok(Key::Mtime),
+        SIZE => Ok(Key::Size),
+        _ => Err("invalid value"),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Config {
+    pub key: Option<Key>,
+    pub reverse: bool,
+    pub unique: bool,
+    pub encoding: EncodingPolicy,
+}
+
+pub fn sort<T>(items: &mut Vec<T>, config: &Config, value: fn(&T) -> &str) -> Result<()> {
+    if config.unique {
+        let mut seen = HashSet::new();
+        items.retain(|item| seen.insert(value(item).to_string()));
+    }
+
+    match config.key {
+        Some(Key::Natural) => items.sort_by(|a, b| natural_cmp(value(a), value(b))),
+        Some(Key::Lexical) => items.sort_by(|a, b| value(a).cmp(value(b))),
+        Some(Key::Mtime) => sort_by_metadata(items, config.encoding, value, Metadata::modified)?,
+        Some(Key::Size) => {
+            sort_by_metadata(items, config.encoding, value, |metadata| Ok(metadata.len()))?
+        }
+        None => {}
+    }
+
+    if config.reverse {
+        items.reverse();
+    }
+
+    Ok(())
+}
+
+fn sort_by_metadata<T, K: Ord>(
+    items: &mut Vec<T>,
+    encoding: EncodingPolicy,
+    value: fn(&T) -> &str,
+    get_key: fn(&Metadata) -> Result<K>,
+) -> Result<()> {
+    let mut keyed_items = Vec::with_capacity(items.len());
+
+    for item in items.drain(..) {
+        let path = PathBuf::from(encode_os_string(value(&item), encoding));
+        let key = fs::metadata(&path).and_then(|metadata| get_key(&metadata));
+        let key = key.map_err(|error| {
+            Error::new(
+                error.kind(),
+                format!("Cannot read metadata of '{}': {}", value(&item), error),
+            )
+        })?;
+        keyed_items.push((key, item));
+    }
+
+    // Values with equal keys are ordered naturally to keep the result deterministic.
+    keyed_items.sort_by(|(a_key, a), (b_key, b)| {
+        a_key
+            .cmp(b_key)
+            .then_with(|| natural_cmp(value(a), value(b)))
+    });
+
+    items.extend(keyed_items.into_iter().map(|(_, item)| item));
+    Ok(())
+}
+
+pub fn natural_cmp(left: &str, right: &str) -> Ordering {
+    let mut left_rest = left;
+    let mut right_rest = right;
+    let mut zeros_ordering = Ordering::Equal;
+
+    while !left_rest.is_empty() && !right_rest.is_empty() {
+        let (left_chunk, left_next) = split_chunk(left_rest);
+        let (right_chunk, right_next) = split_chunk(right_rest);
+
+        let ordering = if is_number(left_chunk) && is_number(right_chunk) {
+            let left_number = left_chunk.trim_start_matches('0');
+            let right_number = right_chunk.trim_start_matches('0');
+
+            // Numbers with more leading zeros go first (e.g. `01` before `1`),
+            // unless the values differ somewhere else.
+            if zeros_ordering == Ordering::Equal {
+                zeros_ordering = right_chunk.len().cmp(&left_chunk.len());
+            }
+
+            left_number
+                .len()
+                .cmp(&right_number.len())
+                .then_with(|| left_number.cmp(right_number))
+        } else {
+            left_chunk.cmp(right_chunk)
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+
+        left_rest = left_next;
+        right_rest = right_next;
+    }
+
+    left_rest
+        .len()
+        .cmp(&right_rest.len())
+        .then(zeros_ordering)
+        .then_with(|| left.cmp(right))
+}
+
+fn split_chunk(value: &str) -> (&str, &str) {
+    let digits = value.starts_with(|char: char| char.is_ascii_digit());
+    let end = value
+        .find(|char: char| char.is_ascii_digit() != digits)
+        .unwrap_or(value.len());
+    value.split_at(end)
+}
+
+fn is_number(chunk: &str) -> bool {
+    chunk.starts_with(|char: char| char.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+    use common::testing::unpack_io_error;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("natural", Ok(Key::Natural)     ; "natural")]
+    #[test_case("lexical", Ok(Key::Lexical)     ; "lexical")]
+    #[test_case("mtime",   Ok(Key::Mtime)       ; "mtime")]
+    #[test_case("size",    Ok(Key::Size)        ; "size")]
+    #[test_case("x",       Err("invalid value") ; "invalid")]
+    fn parse_sort_key(string: &str, result: std::result::Result<Key, &str>) {
+        assert_eq!(super::parse_sort_key(string), result);
+    }
+
+    #[test_case("",       "",        Ordering::Equal   ; "empty")]
+    #[test_case("a",      "",        Ordering::Greater ; "non empty")]
+    #[test_case("img2",   "img10",   Ordering::Less    ; "number")]
+    #[test_case("img10",  "img2",    Ordering::Greater ; "number reversed")]
+    #[test_case("img2",   "img2",    Ordering::Equal   ; "equal")]
+    #[test_case("img02",  "img2",    Ordering::Less    ; "leading zeros")]
+    #[test_case("img02b", "img2a",   Ordering::Greater ; "leading zeros ignored")]
+    #[test_case("a1b2",   "a1b10",   Ordering::Less    ; "multiple numbers")]
+    #[test_case("v1.9.0", "v1.10.0", Ordering::Less    ; "version")]
+    #[test_case("img",    "img1",    Ordering::Less    ; "prefix")]
+    #[test_case("1a",     "a",       Ordering::Less    ; "number before text")]
+    #[test_case("B",      "a",       Ordering::Less    ; "case sensitive")]
+    fn natural_cmp(left: &str, right: &str, result: Ordering) {
+        assert_eq!(super::natural_cmp(left, right), result);
+    }
+
+    #[test_case(None,               false, false, "b10 a b2 a" ; "none")]
+    #[test_case(None,               true,  false, "a b2 a b10" ; "reverse")]
+    #[test_case(None,               false, true,  "b10 a b2"   ; "unique")]
+    #[test_case(Some(Key::Natural), false, false, "a a b2 b10" ; "natural")]
+    #[test_case(Some(Key::Natural), true,  true,  "b10 b2 a"   ; "natural reverse unique")]
+    #[test_case(Some(Key::Lexical), false, false, "a a b10 b2" ; "lexical")]
+    fn sort(key: Option<Key>, reverse: bool, unique: bool, result: &str) {
+        let mut items = vec!["b10", "a", "b2", "a"];
+        let config = Config {
+            key,
+            reverse,
+            unique,
+            encoding: EncodingPolicy::Fail,
+        };
+
+        super::sort(&mut items, &config, |item| *item).unwrap();
+        assert_eq!(items.join(" "), result);
+    }
+
+    #[test]
+    fn sort_size() {
+        let dir = TempDir::new().unwrap();
+        dir.child("a").write_str("abc").unwrap();
+        dir.child("b10").write_str("a").unwrap();
+        dir.child("b2").write_str("a").unwrap();
+
+        let paths = ["a", "b10", "b2"]
+            .iter()
+            .map(|name| dir.child(name).path().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+
+        let mut items = paths.iter().map(String::as_str).collect::<Vec<_>>();
+        let config = Config {
+            key: Some(Key::Size),
+            reverse: false,
+            unique: false,
+            encoding: EncodingPolicy::Fail,
+        };
+
+        super::sort(&mut items, &config, |item| *item).unwrap();
+        assert_eq!(items, &[&paths[2][..], &paths[1], &paths[0]]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn sort_metadata_err() {
+        let mut items = vec!["_missing_"];
+        let config = Config {
+            key: Some(Key::Mtime),
+            reverse: false,
+            unique: false,
+            encoding: EncodingPolicy::Fail,
+        };
+
+        assert_eq!(
+            super::sort(&mut items, &config, |item| *item).map_err(unpack_io_error),
+            Err((
+                ErrorKind::NotFound,
+                "Cannot read metadata of '_missing_': No such file or directory (os error 2)"
+                    .into()
+            ))
+        );
+    }
+}
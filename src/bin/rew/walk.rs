@@ -0,0 +1,345 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::vec::IntoIter;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+
+const GITIGNORE_NAME: &str = ".gitignore";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryType {
+    Any,
+    File,
+    Dir,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Order {
+    DepthFirst,
+    ContentsFirst,
+}
+
+pub struct Config {
+    pub min_depth: usize,
+    pub max_depth: Option<usize>,
+    pub hidden: bool,
+    pub gitignore: bool,
+    pub entry_type: EntryType,
+    pub include: GlobSet,
+    pub exclude: GlobSet,
+    pub order: Order,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            min_depth: 1,
+            max_depth: None,
+            hidden: false,
+            gitignore: true,
+            entry_type: EntryType::Any,
+            include: GlobSet::empty(),
+            exclude: GlobSet::empty(),
+            order: Order::DepthFirst,
+        }
+    }
+}
+
+pub fn parse_glob(string: &str) -> std::result::Result<Glob, String> {
+    Glob::new(string).map_err(|error| error.to_string())
+}
+
+pub fn build_glob_set(globs: &[Glob]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+
+    for glob in globs {
+        builder.add(glob.clone());
+    }
+
+    builder
+        .build()
+        .expect("Expected glob set built from valid globs")
+}
+
+struct Entry {
+    path: PathBuf,
+    is_dir: bool,
+}
+
+struct Frame {
+    // Directory to be yielded once all its entries were yielded (contents-first order).
+    dir: Option<Entry>,
+    entries: IntoIter<Entry>,
+    gitignore: Option<Gitignore>,
+}
+
+pub struct Walker {
+    root: PathBuf,
+    config: Config,
+    stack: Vec<Frame>,
+    started: bool,
+}
+
+impl Walker {
+    pub fn new(root: &Path, config: Config) -> Self {
+        Self {
+            root: root.into(),
+            config,
+            stack: Vec::new(),
+            started: false,
+        }
+    }
+
+    pub fn next(&mut self) -> Result<Option<PathBuf>> {
+        if !self.started {
+            self.started = true;
+            self.push_dir(self.root.clone(), None)?;
+        }
+
+        while let Some(frame) = self.stack.last_mut() {
+            let entry = match frame.entries.next() {
+                Some(entry) => entry,
+                None => {
+                    let dir = self.stack.pop().and_then(|frame| frame.dir);
+                    match dir {
+                        Some(dir) if self.is_yielded(&dir) => return Ok(Some(dir.path)),
+                        _ => continue,
+                    }
+                }
+            };
+
+            if self.is_ignored(&entry) {
+                continue;
+            }
+
+            let depth = self.stack.len();
+
+            if entry.is_dir && self.config.max_depth.is_none_or(|max| depth < max) {
+                if self.config.order == Order::ContentsFirst {
+                    self.push_dir(entry.path.clone(), Some(entry))?;
+                    continue;
+                }
+                self.push_dir(entry.path.clone(), None)?;
+            }
+
+            if self.is_yielded_at(&entry, depth) {
+                return Ok(Some(entry.path));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn push_dir(&mut self, path: PathBuf, dir: Option<Entry>) -> Result<()> {
+        let mut entries = Vec::new();
+
+        for dir_entry in fs::read_dir(&path)? {
+            let dir_entry = dir_entry?;
+            entries.push(Entry {
+                path: dir_entry.path(),
+                // Symbolic links are not followed.
+                is_dir: dir_entry.file_type()?.is_dir(),
+            });
+        }
+
+        // Sorted entries produce the same output on every platform.
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let gitignore = if self.config.gitignore {
+            build_gitignore(&path)?
+        } else {
+            None
+        };
+
+        self.stack.push(Frame {
+            dir,
+            entries: entries.into_iter(),
+            gitignore,
+        });
+
+        Ok(())
+    }
+
+    fn is_ignored(&self, entry: &Entry) -> bool {
+        if !self.config.hidden && is_hidden(&entry.path) {
+            return true;
+        }
+
+        if self
+            .config
+            .exclude
+            .is_match(self.relative_path(&entry.path))
+        {
+            return true;
+        }
+
+        // The nearest `.gitignore` file has the highest priority.
+        for frame in self.stack.iter().rev() {
+            if let Some(gitignore) = &frame.gitignore {
+                match gitignore.matched(&entry.path, entry.is_dir) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => {}
+                }
+            }
+        }
+
+        false
+    }
+
+    fn is_yielded(&self, entry: &Entry) -> bool {
+        // Stack does not contain frame of the entry directory anymore.
+        self.is_yielded_at(entry, self.stack.len())
+    }
+
+    fn is_yielded_at(&self, entry: &Entry, depth: usize) -> bool {
+        let type_matches = match self.config.entry_type {
+            EntryType::Any => true,
+            EntryType::File => !entry.is_dir,
+            EntryType::Dir => entry.is_dir,
+        };
+
+        type_matches
+            && depth >= self.config.min_depth
+            && self.config.max_depth.is_none_or(|max| depth <= max)
+            && (self.config.include.is_empty()
+                || self
+                    .config
+                    .include
+                    .is_match(self.relative_path(&entry.path)))
+    }
+
+    fn relative_path<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+}
+
+fn build_gitignore(dir: &Path) -> Result<Option<Gitignore>> {
+    let path = dir.join(GITIGNORE_NAME);
+
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let mut builder = GitignoreBuilder::new(dir);
+
+    if let Some(error) = builder.add(&path) {
+        return Err(Error::new(ErrorKind::InvalidData, error.to_string()));
+    }
+
+    builder
+        .build()
+        .map(Some)
+        .map_err(|error| Error::new(ErrorKind::InvalidData, error.to_string()))
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(Config::default(),                                                                "a a/b a/b/c a/d e"         ; "default")]
+    #[test_case(Config { order: Order::ContentsFirst, ..Config::default() },                      "a/b/c a/b a/d a e"         ; "contents first")]
+    #[test_case(Config { max_depth: Some(0), ..Config::default() },                               ""                          ; "max depth 0")]
+    #[test_case(Config { max_depth: Some(1), ..Config::default() },                               "a e"                       ; "max depth 1")]
+    #[test_case(Config { max_depth: Some(2), ..Config::default() },                               "a a/b a/d e"               ; "max depth 2")]
+    #[test_case(Config { min_depth: 2, ..Config::default() },                                     "a/b a/b/c a/d"             ; "min depth 2")]
+    #[test_case(Config { entry_type: EntryType::File, ..Config::default() },                      "a/b/c a/d e"               ; "files only")]
+    #[test_case(Config { entry_type: EntryType::Dir, ..Config::default() },                       "a a/b"                     ; "dirs only")]
+    #[test_case(Config { hidden: true, ..Config::default() },                                     ".f .f/g a a/b a/b/c a/d e" ; "hidden")]
+    #[test_case(Config { gitignore: false, ..Config::default() },                                 "a a/b a/b/c a/d e h"       ; "no gitignore")]
+    #[test_case(Config { include: globs(&["*c", "e"]), ..Config::default() },                     "a/b/c e"                   ; "include")]
+    #[test_case(Config { exclude: globs(&["a/b"]), ..Config::default() },                         "a a/d e"                   ; "exclude")]
+    #[test_case(Config { include: globs(&["a*"]), exclude: globs(&["*d"]), ..Config::default() }, "a a/b a/b/c"               ; "include exclude")]
+    fn walk(config: Config, result: &str) {
+        let dir = TempDir::new().unwrap();
+        dir.child("a/b/c").touch().unwrap();
+        dir.child("a/d").touch().unwrap();
+        dir.child("e").touch().unwrap();
+        dir.child(".f/g").touch().unwrap();
+        dir.child("h").touch().unwrap();
+        dir.child(".gitignore").write_str("h\n").unwrap();
+
+        let mut walker = Walker::new(dir.path(), config);
+        let mut paths = Vec::new();
+
+        while let Some(path) = walker.next().unwrap() {
+            paths.push(
+                path.strip_prefix(dir.path())
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string(),
+            );
+        }
+
+        let ignore_file = GITIGNORE_NAME.to_string();
+        paths.retain(|path| path != &ignore_file);
+
+        assert_eq!(paths.join(" "), result);
+    }
+
+    #[test]
+    fn nested_gitignore() {
+        let dir = TempDir::new().unwrap();
+        dir.child("a/b").touch().unwrap();
+        dir.child("a/c").touch().unwrap();
+        dir.child("d").touch().unwrap();
+        dir.child(".gitignore").write_str("b\nc\n").unwrap();
+        dir.child("a/.gitignore").write_str("!c\n").unwrap();
+
+        let mut walker = Walker::new(dir.path(), Config::default());
+        let mut paths = Vec::new();
+
+        while let Some(path) = walker.next().unwrap() {
+            paths.push(path);
+        }
+
+        assert_eq!(
+            paths,
+            vec![
+                dir.child("a").path().to_path_buf(),
+                dir.child("a/c").path().to_path_buf(),
+                dir.child("d").path().to_path_buf(),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_root() {
+        let dir = TempDir::new().unwrap();
+        let mut walker = Walker::new(&dir.path().join("a"), Config::default());
+        assert_eq!(
+            walker.next().map_err(|error| error.kind()),
+            Err(ErrorKind::NotFound)
+        );
+    }
+
+    #[test_case("*.txt", Ok("*.txt")                                                       ; "valid")]
+    #[test_case("a[b",   Err("error parsing glob 'a[b': unclosed character class; missing ']'") ; "invalid")]
+    fn parse_glob(string: &str, result: std::result::Result<&str, &str>) {
+        assert_eq!(
+            super::parse_glob(string).map(|glob| glob.glob().to_string()),
+            result.map(String::from).map_err(String::from)
+        );
+    }
+
+    fn globs(globs: &[&str]) -> GlobSet {
+        let globs = globs
+            .iter()
+            .map(|glob| super::parse_glob(glob).unwrap())
+            .collect::<Vec<Glob>>();
+        build_glob_set(&globs)
+    }
+}
@@ -0,0 +1,354 @@
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::io::Result;
+
+use crate::utils::str_from_utf8;
+
+pub const ENCODING_POLICIES: &[&str] = &[FAIL, LOSSY, ESCAPE];
+
+const FAIL: &str = "fail";
+const LOSSY: &str = "lossy";
+const ESCAPE: &str = "escape";
+
+const ESCAPE_CHAR: char = '%';
+
+// Escaped bytes are represented by characters U+10FF00..U+10FFFF while processed as text.
+const TEXT_BYTE_OFFSET: u32 = 0x10FF00;
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum EncodingPolicy {
+    // Values which are not valid UTF-8 are rejected.
+    #[default]
+    Fail,
+    // Invalid sequences are replaced by U+FFFD character.
+    Lossy,
+    // Invalid bytes (and the escape character itself) are written as `%xx`.
+    Escape,
+}
+
+pub fn parse_encoding_policy(string: &str) -> std::result::Result<EncodingPolicy, &'static str> {
+    match string {
+        FAIL => Ok(EncodingPolicy::Fail),
+        LOSSY => Ok(EncodingPolicy::Lossy),
+        ESCAPE => Ok(EncodingPolicy::Escape),
+        _ => Err("invalid value"),
+    }
+}
+
+pub fn decode_bytes(bytes: &[u8], policy: EncodingPolicy) -> Result<Cow<'_, str>> {
+    match policy {
+        EncodingPolicy::Fail => str_from_utf8(bytes).map(Cow::Borrowed),
+        EncodingPolicy::Lossy => Ok(String::from_utf8_lossy(bytes)),
+        EncodingPolicy::Escape => Ok(escape_bytes(bytes)),
+    }
+}
+
+pub fn encode_str(value: &str, policy: EncodingPolicy) -> Cow<'_, [u8]> {
+    match policy {
+        EncodingPolicy::Fail | EncodingPolicy::Lossy => Cow::Borrowed(value.as_bytes()),
+        EncodingPolicy::Escape => unescape_str(value),
+    }
+}
+
+pub fn decode_os_str(value: &OsStr, policy: EncodingPolicy) -> Result<Cow<'_, str>> {
+    decode_bytes_from_os_str(value, policy)
+}
+
+pub fn encode_os_string(value: &str, policy: EncodingPolicy) -> OsString {
+    os_string_from_bytes(encode_str(value, policy))
+}
+
+pub fn os_str_bytes(value: &OsStr) -> Cow<'_, [u8]> {
+    os_str_to_bytes(value)
+}
+
+// Escaped value is converted to a text where each escaped byte is a single character,
+// so the text can be modified (e.g., by its substring) without breaking escape sequences.
+pub fn escaped_to_text(value: &str) -> Cow<'_, str> {
+    if !value.contains(|char| char == ESCAPE_CHAR || is_text_byte(char)) {
+        return Cow::Borrowed(value);
+    }
+
+    let bytes = unescape_str(value);
+    let mut result = String::with_capacity(bytes.len());
+
+    for_each_chunk(
+        &bytes,
+        |result: &mut String, char| {
+            if is_text_byte(char) {
+                // Such character would be confused with a byte, so its bytes are used instead.
+                for byte in char.to_string().bytes() {
+                    push_text_byte(result, byte);
+                }
+            } else {
+                result.push(char);
+            }
+        },
+        push_text_byte,
+        &mut result,
+    );
+
+    Cow::Owned(result)
+}
+
+pub fn text_to_escaped(value: &str) -> Cow<'_, str> {
+    if !value.contains(|char| char == ESCAPE_CHAR || is_text_byte(char)) {
+        return Cow::Borrowed(value);
+    }
+
+    let mut result = String::with_capacity(value.len());
+
+    for char in value.chars() {
+        if char == ESCAPE_CHAR {
+            push_escaped_byte(&mut result, ESCAPE_CHAR as u8);
+        } else if is_text_byte(char) {
+            push_escaped_byte(&mut result, (char as u32 - TEXT_BYTE_OFFSET) as u8);
+        } else {
+            result.push(char);
+        }
+    }
+
+    Cow::Owned(result)
+}
+
+fn is_text_byte(char: char) -> bool {
+    char as u32 >= TEXT_BYTE_OFFSET
+}
+
+fn push_text_byte(result: &mut String, byte: u8) {
+    result.push(char::from_u32(TEXT_BYTE_OFFSET + byte as u32).expect("Expected valid char"));
+}
+
+fn escape_bytes(bytes: &[u8]) -> Cow<'_, str> {
+    if let Ok(str) = std::str::from_utf8(bytes) {
+        if !str.contains(ESCAPE_CHAR) {
+            return Cow::Borrowed(str);
+        }
+    }
+
+    let mut result = String::with_capacity(bytes.len());
+
+    for_each_chunk(
+        bytes,
+        |result: &mut String, char| {
+            if char == ESCAPE_CHAR {
+                push_escaped_byte(result, ESCAPE_CHAR as u8);
+            } else {
+                result.push(char);
+            }
+        },
+        push_escaped_byte,
+        &mut result,
+    );
+
+    Cow::Owned(result)
+}
+
+fn for_each_chunk(
+    bytes: &[u8],
+    push_char: fn(&mut String, char),
+    push_byte: fn(&mut String, u8),
+    result: &mut String,
+) {
+    let mut remaining = bytes;
+
+    while !remaining.is_empty() {
+        let (valid, invalid) = match std::str::from_utf8(remaining) {
+            Ok(valid) => (valid, &[][..]),
+            Err(error) => {
+                let (valid, rest) = remaining.split_at(error.valid_up_to());
+                let invalid_len = error.error_len().unwrap_or(rest.len());
+                let valid = std::str::from_utf8(valid).expect("Expected valid UTF-8 prefix");
+                remaining = &rest[invalid_len..];
+                (valid, &rest[..invalid_len])
+            }
+        };
+
+        for char in valid.chars() {
+            push_char(result, char);
+        }
+
+        for byte in invalid {
+            push_byte(result, *byte);
+        }
+
+        if invalid.is_empty() {
+            break;
+        }
+    }
+}
+
+fn push_escaped_byte(result: &mut String, byte: u8) {
+    result.push(ESCAPE_CHAR);
+    result.push_str(&format!("{:02X}", byte));
+}
+
+fn unescape_str(value: &str) -> Cow<'_, [u8]> {
+    if !value.contains(ESCAPE_CHAR) {
+        return Cow::Borrowed(value.as_bytes());
+    }
+
+    let bytes = value.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        if bytes[index] == ESCAPE_CHAR as u8 {
+            let byte = bytes
+                .get(index + 1..index + 3)
+                .and_then(|digits| std::str::from_utf8(digits).ok())
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+
+            if let Some(byte) = byte {
+                result.push(byte);
+                index += 3;
+                continue;
+            }
+        }
+
+        // Invalid escape sequences are kept as they are.
+        result.push(bytes[index]);
+        index += 1;
+    }
+
+    Cow::Owned(result)
+}
+
+#[cfg(unix)]
+fn decode_bytes_from_os_str(value: &OsStr, policy: EncodingPolicy) -> Result<Cow<'_, str>> {
+    use std::os::unix::ffi::OsStrExt;
+    decode_bytes(value.as_bytes(), policy)
+}
+
+#[cfg(not(unix))]
+fn decode_bytes_from_os_str(value: &OsStr, policy: EncodingPolicy) -> Result<Cow<'_, str>> {
+    // Other platforms do not expose raw bytes, only a lossy conversion is possible.
+    match value.to_str() {
+        Some(str) => decode_bytes(str.as_bytes(), policy),
+        None if policy == EncodingPolicy::Fail => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Value does not have UTF-8 encoding",
+        )),
+        None => Ok(value.to_string_lossy()),
+    }
+}
+
+#[cfg(unix)]
+fn os_string_from_bytes(bytes: Cow<[u8]>) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(bytes.into_owned())
+}
+
+#[cfg(not(unix))]
+fn os_string_from_bytes(bytes: Cow<[u8]>) -> OsString {
+    String::from_utf8_lossy(&bytes).into_owned().into()
+}
+
+#[cfg(unix)]
+fn os_str_to_bytes(value: &OsStr) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(value.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn os_str_to_bytes(value: &OsStr) -> Cow<'_, [u8]> {
+    match value.to_string_lossy() {
+        Cow::Borrowed(str) => Cow::Borrowed(str.as_bytes()),
+        Cow::Owned(string) => Cow::Owned(string.into_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::testing::unpack_io_error;
+
+    const FAIL: EncodingPolicy = EncodingPolicy::Fail;
+    const LOSSY: EncodingPolicy = EncodingPolicy::Lossy;
+    const ESCAPE: EncodingPolicy = EncodingPolicy::Escape;
+
+    #[test_case("fail",   Ok(FAIL)             ; "fail")]
+    #[test_case("lossy",  Ok(LOSSY)            ; "lossy")]
+    #[test_case("escape", Ok(ESCAPE)           ; "escape")]
+    #[test_case("x",      Err("invalid value") ; "invalid")]
+    fn parse_encoding_policy(string: &str, result: std::result::Result<EncodingPolicy, &str>) {
+        assert_eq!(super::parse_encoding_policy(string), result);
+    }
+
+    #[test_case(FAIL,   b"a%b",                 Ok("a%b")                ; "fail valid")]
+    #[test_case(LOSSY,  b"a%b",                 Ok("a%b")                ; "lossy valid")]
+    #[test_case(ESCAPE, b"a%b",                 Ok("a%25b")              ; "escape valid")]
+    #[test_case(LOSSY,  b"a\xE9b\xFF",          Ok("a\u{FFFD}b\u{FFFD}") ; "lossy invalid")]
+    #[test_case(ESCAPE, b"a\xE9b\xFF",          Ok("a%E9b%FF")           ; "escape invalid")]
+    #[test_case(ESCAPE, b"\xC4\x8D\xF0\x9F",    Ok("\u{10D}%F0%9F")      ; "escape incomplete")]
+    #[test_case(FAIL,   b"a\xE9b",              Err("Value does not have UTF-8 encoding (offset 1)") ; "fail invalid")]
+    fn decode_bytes(policy: EncodingPolicy, bytes: &[u8], result: std::result::Result<&str, &str>) {
+        assert_eq!(
+            super::decode_bytes(bytes, policy).map_err(unpack_io_error),
+            result
+                .map(Cow::Borrowed)
+                .map_err(|message| (ErrorKind::InvalidData, message.into()))
+        );
+    }
+
+    #[test_case(FAIL,   "a%E9",      b"a%E9"    ; "fail")]
+    #[test_case(LOSSY,  "a%E9",      b"a%E9"    ; "lossy")]
+    #[test_case(ESCAPE, "a%E9b%25",  b"a\xE9b%" ; "escape")]
+    #[test_case(ESCAPE, "a%e9",      b"a\xE9"   ; "escape lowercase")]
+    #[test_case(ESCAPE, "a%G0%1%",   b"a%G0%1%" ; "escape invalid")]
+    fn encode_str(policy: EncodingPolicy, value: &str, result: &[u8]) {
+        assert_eq!(super::encode_str(value, policy).as_ref(), result);
+    }
+
+    #[test]
+    fn escape_round_trip() {
+        let bytes = b"%a\xE9\x80%25\xFF\xC3\xA1";
+        let escaped = super::decode_bytes(bytes, ESCAPE).unwrap();
+        assert_eq!(super::encode_str(&escaped, ESCAPE).as_ref(), &bytes[..]);
+    }
+
+    #[test_case("a",          "a"                                         ; "plain")]
+    #[test_case("a%25b",      "a%b"                                       ; "escape char")]
+    #[test_case("caf%E9",     "caf\u{10FFE9}"                             ; "invalid byte")]
+    #[test_case("a%G0",       "a%G0"                                      ; "invalid escape")]
+    #[test_case("\u{10FF41}", "\u{10FFF4}\u{10FF8F}\u{10FFBD}\u{10FF81}" ; "private char")]
+    fn escaped_to_text(value: &str, result: &str) {
+        assert_eq!(super::escaped_to_text(value), result);
+    }
+
+    #[test_case("a",             "a"      ; "plain")]
+    #[test_case("a%b",           "a%25b"  ; "escape char")]
+    #[test_case("caf\u{10FFE9}", "caf%E9" ; "invalid byte")]
+    fn text_to_escaped(value: &str, result: &str) {
+        assert_eq!(super::text_to_escaped(value), result);
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let bytes = "%a\u{10FF41}"
+            .bytes()
+            .chain(b"\xE9\x80".iter().copied())
+            .collect::<Vec<u8>>();
+        let escaped = super::decode_bytes(&bytes, ESCAPE).unwrap();
+        let text = super::escaped_to_text(&escaped);
+        let escaped = super::text_to_escaped(&text);
+        assert_eq!(super::encode_str(&escaped, ESCAPE).as_ref(), &bytes[..]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn os_str() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let value = OsStr::from_bytes(b"a\xE9");
+        let escaped = decode_os_str(value, ESCAPE).unwrap();
+
+        assert_eq!(escaped, "a%E9");
+        assert_eq!(encode_os_string(&escaped, ESCAPE), value);
+        assert_eq!(os_str_bytes(value).as_ref(), b"a\xE9");
+    }
+}
@@ -1,6 +1,7 @@
+use std::borrow::Cow;
 use std::io::{BufRead, Result};
 
-use crate::utils::str_from_utf8;
+use crate::encoding::{decode_bytes, EncodingPolicy};
 
 pub enum Terminator {
     Newline { required: bool },
@@ -11,15 +12,23 @@ pub enum Terminator {
 pub struct Splitter<I: BufRead> {
     input: I,
     terminator: Terminator,
+    policy: EncodingPolicy,
     buffer: Vec<u8>,
+    decoded: String,
 }
 
 impl<I: BufRead> Splitter<I> {
     pub fn new(input: I, terminator: Terminator) -> Self {
+        Self::with_policy(input, terminator, EncodingPolicy::Fail)
+    }
+
+    pub fn with_policy(input: I, terminator: Terminator, policy: EncodingPolicy) -> Self {
         Self {
             input,
             terminator,
+            policy,
             buffer: Vec::new(),
+            decoded: String::new(),
         }
     }
 
@@ -59,7 +68,13 @@ impl<I: BufRead> Splitter<I> {
             };
 
             if valid {
-                return str_from_utf8(&self.buffer[..size]).map(|str| Some((str, orig_size)));
+                return match decode_bytes(&self.buffer[..size], self.policy)? {
+                    Cow::Borrowed(str) => Ok(Some((str, orig_size))),
+                    Cow::Owned(string) => {
+                        self.decoded = string;
+                        Ok(Some((&self.decoded, orig_size)))
+                    }
+                };
             }
         }
 
@@ -67,6 +82,16 @@ impl<I: BufRead> Splitter<I> {
     }
 }
 
+pub fn parse_single_byte_char(string: &str) -> std::result::Result<u8, &'static str> {
+    if string.chars().count() != 1 {
+        Err("value must be a single character")
+    } else if string.len() != 1 {
+        Err("multi-byte characters are not supported")
+    } else {
+        Ok(string.as_bytes()[0])
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use test_case::test_case;
@@ -153,4 +178,34 @@ mod tests {
         }
         assert_eq!(splitter.read().map_err(unpack_io_error), Ok(result));
     }
+
+    #[test_case(EncodingPolicy::Fail,   Err("Value does not have UTF-8 encoding (offset 1)") ; "fail")]
+    #[test_case(EncodingPolicy::Lossy,  Ok("a\u{FFFD}%")                                      ; "lossy")]
+    #[test_case(EncodingPolicy::Escape, Ok("a%FF%25")                                        ; "escape")]
+    fn read_policy(policy: EncodingPolicy, result: std::result::Result<&str, &str>) {
+        let mut splitter = Splitter::with_policy(&b"a\xFF%\nb"[..], NL_REQ, policy);
+        assert_eq!(
+            splitter.read().map_err(unpack_io_error),
+            result
+                .map(|value| Some((value, 4)))
+                .map_err(|message| (std::io::ErrorKind::InvalidData, message.into()))
+        );
+    }
+
+    mod parse_single_byte_char {
+        use test_case::test_case;
+
+        use super::*;
+
+        #[test]
+        fn ok() {
+            assert_eq!(parse_single_byte_char("a"), Ok(b'a'));
+        }
+
+        #[test_case("á",  "multi-byte characters are not supported" ; "multi byte")]
+        #[test_case("aa", "value must be a single character"        ; "multi char")]
+        fn err(value: &str, error: &str) {
+            assert_eq!(parse_single_byte_char(value), Err(error));
+        }
+    }
 }
@@ -1,4 +1,5 @@
-use std::io::{Stdin, StdinLock};
+use std::fs::File;
+use std::io::{BufReader, Stdin, StdinLock};
 use std::{io, process};
 
 use clap::Clap;
@@ -13,6 +14,11 @@ pub const EXIT_CODE_CLI_ERROR: i32 = 2;
 
 pub type Result = io::Result<i32>;
 
+#[cfg(unix)]
+const TTY_PATH: &str = "/dev/tty";
+#[cfg(windows)]
+const TTY_PATH: &str = "CONIN$";
+
 pub trait Options: Clap {
     fn color(&self) -> Option<ColorChoice>;
 }
@@ -43,6 +49,16 @@ impl Io {
     pub fn stderr(&self) -> StandardStreamLock {
         self.stderr.lock()
     }
+
+    pub fn tty(&self) -> io::Result<BufReader<File>> {
+        match File::open(TTY_PATH) {
+            Ok(file) => Ok(BufReader::new(file)),
+            Err(error) => Err(io::Error::new(
+                error.kind(),
+                format!("Cannot open terminal '{}': {}", TTY_PATH, error),
+            )),
+        }
+    }
 }
 
 pub fn exec_run<O, R>(run: R)
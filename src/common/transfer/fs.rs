@@ -1,50 +1,699 @@
-use std::fs;
-use std::path::Path;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::{env, fs, io};
 
+use filetime::FileTime;
 use fs_extra::error::{Error, ErrorKind, Result};
 use fs_extra::{dir, file};
 use lazy_static::lazy_static;
+use pathdiff::diff_paths;
+#[cfg(not(unix))]
 use same_file::is_same_file;
 
-#[derive(PartialEq, Debug)]
+use crate::transfer::policy::{make_backup_path, OverwritePolicy, PreserveAttrs, TransferPolicy};
+use crate::transfer::trash::move_to_trash;
+
+const COPY_BLOCK_SIZE: usize = 64 * 1024;
+const VERIFY_BLOCK_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum FileType {
     File,
     Dir,
+    Symlink,
     Unknown,
 }
 
-impl From<&Path> for FileType {
-    fn from(path: &Path) -> Self {
-        match path.metadata() {
-            Ok(metadata) => {
-                if metadata.is_dir() {
-                    FileType::Dir
-                } else {
-                    FileType::File
-                }
-            }
+impl FileType {
+    pub fn resolve(path: &Path, follow_links: bool) -> Self {
+        let metadata = if follow_links {
+            path.metadata()
+        } else {
+            path.symlink_metadata()
+        };
+
+        match metadata {
+            Ok(metadata) if metadata.file_type().is_symlink() => FileType::Symlink,
+            Ok(metadata) if metadata.is_dir() => FileType::Dir,
+            Ok(_) => FileType::File,
             Err(_) => FileType::Unknown,
         }
     }
 }
 
-#[derive(Clone, Copy)]
+impl From<&Path> for FileType {
+    fn from(path: &Path) -> Self {
+        Self::resolve(path, false)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum TransferMode {
     Move,
     Copy,
+    HardLink,
+    Symlink,
+    RelativeSymlink,
+    Reflink,
 }
 
-pub fn transfer_path(src_path: &Path, dst_path: &Path, mode: TransferMode) -> Result<()> {
-    match (FileType::from(src_path), FileType::from(dst_path)) {
-        (FileType::Unknown, _) => Err(Error::new(
-            ErrorKind::NotFound,
-            &format!(
-                "Path '{}' not found or user lacks permission",
-                src_path.to_string_lossy()
-            ),
+#[derive(PartialEq, Debug)]
+pub enum TransferAction {
+    Create,
+    Overwrite,
+    MergeDir,
+    Skip,
+}
+
+pub trait TransferProgress {
+    fn begin_file(&mut self, src_path: &Path, dst_path: &Path, size: u64) -> io::Result<()>;
+    fn update_file(&mut self, copied_size: u64) -> io::Result<()>;
+    fn end_file(&mut self) -> io::Result<()>;
+}
+
+impl<T: TransferProgress + ?Sized> TransferProgress for Option<&mut T> {
+    fn begin_file(&mut self, src_path: &Path, dst_path: &Path, size: u64) -> io::Result<()> {
+        match self {
+            Some(progress) => progress.begin_file(src_path, dst_path, size),
+            None => Ok(()),
+        }
+    }
+
+    fn update_file(&mut self, copied_size: u64) -> io::Result<()> {
+        match self {
+            Some(progress) => progress.update_file(copied_size),
+            None => Ok(()),
+        }
+    }
+
+    fn end_file(&mut self) -> io::Result<()> {
+        match self {
+            Some(progress) => progress.end_file(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<A: TransferProgress, B: TransferProgress> TransferProgress for (A, B) {
+    fn begin_file(&mut self, src_path: &Path, dst_path: &Path, size: u64) -> io::Result<()> {
+        self.0.begin_file(src_path, dst_path, size)?;
+        self.1.begin_file(src_path, dst_path, size)
+    }
+
+    fn update_file(&mut self, copied_size: u64) -> io::Result<()> {
+        self.0.update_file(copied_size)?;
+        self.1.update_file(copied_size)
+    }
+
+    fn end_file(&mut self) -> io::Result<()> {
+        self.0.end_file()?;
+        self.1.end_file()
+    }
+}
+
+pub fn transfer_path(
+    src_path: &Path,
+    dst_path: &Path,
+    mode: TransferMode,
+    policy: &TransferPolicy,
+) -> Result<TransferAction> {
+    transfer_path_with_progress(src_path, dst_path, mode, policy, None)
+}
+
+pub fn transfer_path_with_progress(
+    src_path: &Path,
+    dst_path: &Path,
+    mode: TransferMode,
+    policy: &TransferPolicy,
+    progress: Option<&mut dyn TransferProgress>,
+) -> Result<TransferAction> {
+    let src_type = FileType::resolve(src_path, policy.dereference);
+    let dst_type = FileType::from(dst_path);
+
+    check_file_types(src_path, src_type, dst_path, dst_type)?;
+
+    if dst_type == FileType::Unknown {
+        create_path(src_path, src_type, dst_path, mode, policy, progress)?;
+        return Ok(TransferAction::Create);
+    }
+
+    if is_same_entry(src_path, dst_path, policy.dereference)? {
+        // Dereferenced source might be a link to the destination which must not replace it.
+        if mode == TransferMode::Move && !policy.dereference {
+            // Paths might still differ in case on a case-insensitive file system.
+            fs::rename(src_path, dst_path)?;
+            return Ok(TransferAction::Create);
+        }
+        return Ok(TransferAction::Skip);
+    }
+
+    let action = select_action(src_path, dst_path, dst_type, policy, || {
+        is_newer(src_path, dst_path, policy.dereference)
+    })?;
+
+    match action {
+        TransferAction::Overwrite => {
+            let in_place = src_type == FileType::File
+                && dst_type == FileType::File
+                && (mode == TransferMode::Move || mode == TransferMode::Copy);
+
+            if let OverwritePolicy::Backup(backup_mode) = policy.overwrite {
+                fs::rename(dst_path, make_backup_path(dst_path, backup_mode))?;
+            } else if let OverwritePolicy::Trash(trash) = &policy.overwrite {
+                move_to_trash(dst_path, dst_path, trash)?;
+            } else if !in_place {
+                // Links cannot replace an existing file and copying to a symlink would overwrite its target.
+                fs::remove_file(dst_path)?;
+            }
+
+            create_path(src_path, src_type, dst_path, mode, policy, progress)?
+        }
+        TransferAction::MergeDir => merge_dir(src_path, dst_path, mode, policy, progress)?,
+        TransferAction::Create | TransferAction::Skip => {}
+    }
+
+    Ok(action)
+}
+
+fn create_path(
+    src_path: &Path,
+    src_type: FileType,
+    dst_path: &Path,
+    mode: TransferMode,
+    policy: &TransferPolicy,
+    mut progress: Option<&mut dyn TransferProgress>,
+) -> Result<()> {
+    if let Some(dst_parent) = dst_path.parent() {
+        dir::create_all(dst_parent, false)?;
+    }
+
+    match (src_type, mode) {
+        (_, TransferMode::Move) => {
+            // Rename would move links themselves, so their targets are copied instead.
+            let has_links = policy.dereference
+                && (src_type == FileType::Dir || FileType::from(src_path) == FileType::Symlink);
+
+            if has_links || fs::rename(src_path, dst_path).is_err() {
+                // Paths are probably on different file systems.
+                create_path(
+                    src_path,
+                    src_type,
+                    dst_path,
+                    TransferMode::Copy,
+                    policy,
+                    progress,
+                )?;
+                remove_path(src_path)?;
+            }
+        }
+        (FileType::File, TransferMode::Copy) => {
+            // Attributes are already applied to the temporary copy.
+            return copy_file(src_path, dst_path, policy, progress);
+        }
+        (FileType::File, TransferMode::HardLink) | (FileType::Symlink, TransferMode::HardLink) => {
+            fs::hard_link(src_path, dst_path)?;
+        }
+        (FileType::File, TransferMode::Reflink) => {
+            if let Some(progress) = progress {
+                let size = src_path.metadata()?.len();
+                progress.begin_file(src_path, dst_path, size)?;
+                reflink_file(src_path, dst_path)?;
+                progress.update_file(size)?;
+                progress.end_file()?;
+            } else {
+                reflink_file(src_path, dst_path)?;
+            }
+        }
+        (FileType::Symlink, TransferMode::Copy) | (FileType::Symlink, TransferMode::Reflink) => {
+            let target_path = fs::read_link(src_path)?;
+            create_symlink(&target_path, dst_path, src_path.is_dir())?;
+        }
+        (_, TransferMode::Copy) | (_, TransferMode::HardLink) | (_, TransferMode::Reflink) => {
+            // Directories cannot be copied or linked at once, only their entries.
+            fs::create_dir(dst_path)?;
+            for entry in fs::read_dir(src_path)? {
+                let entry = entry?;
+                let entry_path = entry.path();
+                let entry_type = FileType::resolve(&entry_path, policy.dereference);
+                create_path(
+                    &entry_path,
+                    entry_type,
+                    &dst_path.join(entry.file_name()),
+                    mode,
+                    policy,
+                    progress.as_mut().map(|progress| &mut **progress as _),
+                )?;
+            }
+        }
+        (_, TransferMode::Symlink) => {
+            let target_path = normalize_path(src_path, &env::current_dir()?);
+            create_symlink(&target_path, dst_path, src_path.is_dir())?;
+        }
+        (_, TransferMode::RelativeSymlink) => {
+            let working_dir = env::current_dir()?;
+            let target_path = normalize_path(src_path, &working_dir);
+            let link_path = normalize_path(dst_path, &working_dir);
+            let link_dir = link_path.parent().unwrap_or(&link_path);
+            let relative_path = diff_paths(&target_path, link_dir).unwrap_or(target_path);
+            create_symlink(&relative_path, dst_path, src_path.is_dir())?;
+        }
+    }
+
+    if mode == TransferMode::Copy || mode == TransferMode::Reflink {
+        if policy.verify {
+            verify_copy(src_path, src_type, dst_path)?;
+        }
+        // Directory attributes are applied after its entries were created.
+        preserve_attrs(src_path, src_type, dst_path, &policy.preserve)?;
+    }
+
+    Ok(())
+}
+
+fn copy_file(
+    src_path: &Path,
+    dst_path: &Path,
+    policy: &TransferPolicy,
+    progress: Option<&mut dyn TransferProgress>,
+) -> Result<()> {
+    // Interrupted copy must not leave a truncated file in place of the destination.
+    let temp_path = make_temp_path(dst_path);
+    let dst_metadata = dst_path.symlink_metadata().ok();
+
+    let result = write_file_copy(src_path, &temp_path, dst_path, policy, progress)
+        .and_then(|()| match dst_metadata {
+            Some(metadata) => keep_attrs(&metadata, &temp_path, &policy.preserve),
+            None => Ok(()),
+        })
+        .and_then(|()| Ok(fs::rename(&temp_path, dst_path)?));
+
+    if result.is_err() {
+        // The original error is more important than a failed cleanup.
+        fs::remove_file(&temp_path).ok();
+    }
+
+    result
+}
+
+fn write_file_copy(
+    src_path: &Path,
+    temp_path: &Path,
+    dst_path: &Path,
+    policy: &TransferPolicy,
+    progress: Option<&mut dyn TransferProgress>,
+) -> Result<()> {
+    if let Some(progress) = progress {
+        copy_file_content(src_path, temp_path, dst_path, progress)?;
+    } else {
+        file::copy(src_path, temp_path, &FILE_COPY_OPTIONS)?;
+    }
+    // Copy might have already made the file read-only, which does not matter for sync.
+    fs::File::open(temp_path)?.sync_all()?;
+
+    if policy.verify && !has_same_content(src_path, temp_path)? {
+        return Err(make_verify_error(src_path, dst_path));
+    }
+
+    preserve_attrs(src_path, FileType::File, temp_path, &policy.preserve)
+}
+
+fn keep_attrs(metadata: &fs::Metadata, temp_path: &Path, preserve: &PreserveAttrs) -> Result<()> {
+    // Overwritten file is replaced by a new one which should look the same unless preserved
+    // attributes say otherwise. Ownership change clears setuid/setgid bits, so mode goes last.
+    #[cfg(unix)]
+    {
+        if !preserve.ownership {
+            use std::os::unix::fs::MetadataExt;
+            // Like GNU cp, silently ignore ownership which only a privileged user can set.
+            match std::os::unix::fs::lchown(temp_path, Some(metadata.uid()), Some(metadata.gid())) {
+                Err(error) if error.kind() != io::ErrorKind::PermissionDenied => {
+                    return Err(error.into())
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if !preserve.mode {
+        fs::set_permissions(temp_path, metadata.permissions())?;
+    }
+
+    Ok(())
+}
+
+fn copy_file_content(
+    src_path: &Path,
+    temp_path: &Path,
+    dst_path: &Path,
+    progress: &mut dyn TransferProgress,
+) -> Result<()> {
+    let mut src_file = fs::File::open(src_path)?;
+    let metadata = src_file.metadata()?;
+    let mut temp_file = fs::File::create(temp_path)?;
+    let mut block = vec![0; COPY_BLOCK_SIZE];
+    let mut copied_size = 0;
+
+    progress.begin_file(src_path, dst_path, metadata.len())?;
+
+    loop {
+        let len = read_block(&mut src_file, &mut block)?;
+        if len == 0 {
+            break;
+        }
+        temp_file.write_all(&block[..len])?;
+        copied_size += len as u64;
+        progress.update_file(copied_size)?;
+    }
+
+    // Same as file::copy which uses std::fs::copy.
+    temp_file.set_permissions(metadata.permissions())?;
+    progress.end_file()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn create_symlink(target_path: &Path, link_path: &Path, _target_is_dir: bool) -> Result<()> {
+    std::os::unix::fs::symlink(target_path, link_path)?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn create_symlink(target_path: &Path, link_path: &Path, target_is_dir: bool) -> Result<()> {
+    if target_is_dir {
+        std::os::windows::fs::symlink_dir(target_path, link_path)?;
+    } else {
+        std::os::windows::fs::symlink_file(target_path, link_path)?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn reflink_file(src_path: &Path, dst_path: &Path) -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // _IOW(0x94, 9, int) from linux/fs.h
+    const FICLONE: libc::c_ulong = 0x4004_9409;
+
+    let src_file = fs::File::open(src_path)?;
+    let dst_file = fs::File::create(dst_path)?;
+
+    // Both file descriptors are valid until the end of this function.
+    let result = unsafe { libc::ioctl(dst_file.as_raw_fd(), FICLONE as _, src_file.as_raw_fd()) };
+
+    if result == 0 {
+        dst_file.set_permissions(src_file.metadata()?.permissions())?;
+        Ok(())
+    } else {
+        let error = io::Error::last_os_error();
+        drop(dst_file);
+        fs::remove_file(dst_path)?;
+        Err(make_reflink_error(src_path, dst_path, &error.to_string()))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink_file(src_path: &Path, dst_path: &Path) -> Result<()> {
+    Err(make_reflink_error(
+        src_path,
+        dst_path,
+        "Not supported on this platform",
+    ))
+}
+
+fn make_reflink_error(src_path: &Path, dst_path: &Path, reason: &str) -> Error {
+    Error::new(
+        ErrorKind::Other,
+        &format!(
+            "Cannot clone '{}' to '{}': {}",
+            src_path.to_string_lossy(),
+            dst_path.to_string_lossy(),
+            reason
+        ),
+    )
+}
+
+fn verify_copy(src_path: &Path, src_type: FileType, dst_path: &Path) -> Result<()> {
+    let same = match src_type {
+        FileType::File => has_same_content(src_path, dst_path)?,
+        FileType::Symlink => fs::read_link(src_path)? == fs::read_link(dst_path)?,
+        // Directory entries are verified individually.
+        FileType::Dir | FileType::Unknown => true,
+    };
+
+    if same {
+        Ok(())
+    } else {
+        Err(make_verify_error(src_path, dst_path))
+    }
+}
+
+fn make_verify_error(src_path: &Path, dst_path: &Path) -> Error {
+    Error::new(
+        ErrorKind::Other,
+        &format!(
+            "Copy '{}' differs from its source '{}'",
+            dst_path.to_string_lossy(),
+            src_path.to_string_lossy()
+        ),
+    )
+}
+
+fn has_same_content(src_path: &Path, dst_path: &Path) -> io::Result<bool> {
+    let mut src_file = fs::File::open(src_path)?;
+    let mut dst_file = fs::File::open(dst_path)?;
+
+    if src_file.metadata()?.len() != dst_file.metadata()?.len() {
+        return Ok(false);
+    }
+
+    let mut src_block = [0; VERIFY_BLOCK_SIZE];
+    let mut dst_block = [0; VERIFY_BLOCK_SIZE];
+
+    loop {
+        let src_len = read_block(&mut src_file, &mut src_block)?;
+        let dst_len = read_block(&mut dst_file, &mut dst_block)?;
+
+        if src_block[..src_len] != dst_block[..dst_len] {
+            return Ok(false);
+        }
+        if src_len == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+fn read_block<R: Read>(reader: &mut R, block: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+
+    while len < block.len() {
+        match reader.read(&mut block[len..]) {
+            Ok(0) => break,
+            Ok(read_len) => len += read_len,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(len)
+}
+
+fn preserve_attrs(
+    src_path: &Path,
+    src_type: FileType,
+    dst_path: &Path,
+    attrs: &PreserveAttrs,
+) -> Result<()> {
+    if attrs.is_empty() {
+        return Ok(());
+    }
+
+    let is_symlink = src_type == FileType::Symlink;
+    let metadata = if is_symlink {
+        src_path.symlink_metadata()?
+    } else {
+        src_path.metadata()?
+    };
+
+    // Ownership change clears setuid/setgid bits, mode has to be set afterwards.
+    // Timestamps are set the last, because setting other attributes could modify them.
+    #[cfg(unix)]
+    {
+        if attrs.xattr && !is_symlink {
+            preserve_xattrs(src_path, dst_path)?;
+        }
+        if attrs.ownership {
+            use std::os::unix::fs::MetadataExt;
+            // Like GNU cp, silently ignore ownership which only a privileged user can set.
+            match std::os::unix::fs::lchown(dst_path, Some(metadata.uid()), Some(metadata.gid())) {
+                Err(error) if error.kind() != io::ErrorKind::PermissionDenied => {
+                    return Err(error.into())
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if attrs.mode && !is_symlink {
+        fs::set_permissions(dst_path, metadata.permissions())?;
+    }
+
+    if attrs.timestamps {
+        filetime::set_symlink_file_times(
+            dst_path,
+            FileTime::from_last_access_time(&metadata),
+            FileTime::from_last_modification_time(&metadata),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn preserve_xattrs(src_path: &Path, dst_path: &Path) -> Result<()> {
+    let names = match xattr::list_deref(src_path) {
+        Ok(names) => names,
+        Err(error) if error.kind() == io::ErrorKind::Unsupported => return Ok(()),
+        Err(error) => return Err(error.into()),
+    };
+
+    for name in names {
+        if let Some(value) = xattr::get_deref(src_path, &name)? {
+            // Attributes outside of the user namespace may require a privileged user.
+            match xattr::set(dst_path, &name, &value) {
+                Err(error) if error.kind() != io::ErrorKind::PermissionDenied => {
+                    return Err(error.into())
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn merge_dir(
+    src_path: &Path,
+    dst_path: &Path,
+    mode: TransferMode,
+    policy: &TransferPolicy,
+    mut progress: Option<&mut dyn TransferProgress>,
+) -> Result<()> {
+    for entry in fs::read_dir(src_path)? {
+        let entry = entry?;
+        transfer_path_with_progress(
+            &entry.path(),
+            &dst_path.join(entry.file_name()),
+            mode,
+            policy,
+            progress.as_mut().map(|progress| &mut **progress as _),
+        )?;
+    }
+
+    // Entries skipped due to overwrite policy are kept in the source directory.
+    if mode == TransferMode::Move && fs::read_dir(src_path)?.next().is_none() {
+        fs::remove_dir(src_path)?;
+    }
+
+    Ok(())
+}
+
+pub fn measure_size(path: &Path, follow_links: bool) -> u64 {
+    // Unreadable entries do not prevent the transfer, they are reported once accessed.
+    match FileType::resolve(path, follow_links) {
+        FileType::File => path.metadata().map_or(0, |metadata| metadata.len()),
+        FileType::Dir => fs::read_dir(path).map_or(0, |entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| measure_size(&entry.path(), follow_links))
+                .sum()
+        }),
+        FileType::Symlink | FileType::Unknown => 0,
+    }
+}
+
+pub fn select_action<F>(
+    src_path: &Path,
+    dst_path: &Path,
+    dst_type: FileType,
+    policy: &TransferPolicy,
+    is_newer: F,
+) -> Result<TransferAction>
+where
+    F: FnOnce() -> Result<bool>,
+{
+    match policy.overwrite {
+        OverwritePolicy::Fail => Err(Error::new(
+            ErrorKind::AlreadyExists,
+            &format!("Path '{}' already exists", dst_path.to_string_lossy()),
         )),
+        _ if dst_type == FileType::Dir => {
+            if policy.merge_dirs {
+                Ok(TransferAction::MergeDir)
+            } else {
+                Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    &format!(
+                        "Cannot merge directory '{}' into existing directory '{}'",
+                        src_path.to_string_lossy(),
+                        dst_path.to_string_lossy()
+                    ),
+                ))
+            }
+        }
+        OverwritePolicy::Skip => Ok(TransferAction::Skip),
+        OverwritePolicy::Update if !is_newer()? => Ok(TransferAction::Skip),
+        _ => Ok(TransferAction::Overwrite),
+    }
+}
+
+pub fn is_newer(src_path: &Path, dst_path: &Path, follow_src_links: bool) -> Result<bool> {
+    let src_metadata = if follow_src_links {
+        src_path.metadata()?
+    } else {
+        src_path.symlink_metadata()?
+    };
+    Ok(src_metadata.modified()? > dst_path.symlink_metadata()?.modified()?)
+}
+
+pub fn is_same_entry(src_path: &Path, dst_path: &Path, follow_src_links: bool) -> Result<bool> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let src_metadata = if follow_src_links {
+            src_path.metadata()?
+        } else {
+            src_path.symlink_metadata()?
+        };
+        let dst_metadata = dst_path.symlink_metadata()?;
+
+        Ok(src_metadata.dev() == dst_metadata.dev() && src_metadata.ino() == dst_metadata.ino())
+    }
+
+    #[cfg(not(unix))]
+    {
+        let src_type = FileType::resolve(src_path, follow_src_links);
+        let dst_type = FileType::from(dst_path);
 
-        (FileType::File, FileType::Dir) => Err(Error::new(
+        if src_type == FileType::Symlink || dst_type == FileType::Symlink {
+            Ok(src_path == dst_path)
+        } else {
+            Ok(is_same_file(src_path, dst_path)?)
+        }
+    }
+}
+
+pub fn check_file_types(
+    src_path: &Path,
+    src_type: FileType,
+    dst_path: &Path,
+    dst_type: FileType,
+) -> Result<()> {
+    match (src_type, dst_type) {
+        (FileType::Unknown, _) => Err(make_not_found_error(src_path)),
+
+        (FileType::File, FileType::Dir) | (FileType::Symlink, FileType::Dir) => Err(Error::new(
             ErrorKind::Other,
             &format!(
                 "Cannot to overwrite directory '{}' with file '{}'",
@@ -53,7 +702,7 @@ pub fn transfer_path(src_path: &Path, dst_path: &Path, mode: TransferMode) -> Re
             ),
         )),
 
-        (FileType::Dir, FileType::File) => Err(Error::new(
+        (FileType::Dir, FileType::File) | (FileType::Dir, FileType::Symlink) => Err(Error::new(
             ErrorKind::Other,
             &format!(
                 "Cannot to overwrite file '{}' with directory '{}'",
@@ -62,47 +711,195 @@ pub fn transfer_path(src_path: &Path, dst_path: &Path, mode: TransferMode) -> Re
             ),
         )),
 
-        (FileType::File, dst_type) => {
-            if let Some(dst_parent) = dst_path.parent() {
-                dir::create_all(dst_parent, false)?;
-            }
-            match mode {
-                TransferMode::Move => {
-                    if fs::rename(src_path, dst_path).is_err() {
-                        file::move_file(src_path, dst_path, &FILE_COPY_OPTIONS)?;
-                    }
-                }
-                TransferMode::Copy => {
-                    if dst_type == FileType::Unknown || !is_same_file(src_path, dst_path)? {
-                        file::copy(src_path, dst_path, &FILE_COPY_OPTIONS)?;
-                    }
-                }
+        _ => Ok(()),
+    }
+}
+
+pub struct DryRun {
+    working_dir: PathBuf,
+    policy: TransferPolicy,
+    overrides: HashMap<PathBuf, FileType>,
+}
+
+impl DryRun {
+    pub fn new(working_dir: &Path, policy: &TransferPolicy) -> Self {
+        Self {
+            working_dir: working_dir.into(),
+            policy: policy.clone(),
+            overrides: HashMap::new(),
+        }
+    }
+
+    pub fn transfer(
+        &mut self,
+        src_path: &Path,
+        dst_path: &Path,
+        mode: TransferMode,
+    ) -> Result<(TransferAction, Vec<PathBuf>)> {
+        let src_type = self.file_type(src_path, self.policy.dereference);
+        let dst_type = self.file_type(dst_path, false);
+
+        check_file_types(src_path, src_type, dst_path, dst_type)?;
+
+        let src_key = normalize_path(src_path, &self.working_dir);
+        let dst_key = normalize_path(dst_path, &self.working_dir);
+
+        let simulated = self.is_overridden(&src_key) || self.is_overridden(&dst_key);
+        let real_src_path = self.working_dir.join(src_path);
+        let real_dst_path = self.working_dir.join(dst_path);
+
+        let action = if dst_type == FileType::Unknown {
+            TransferAction::Create
+        } else if src_key == dst_key
+            || (!simulated
+                && is_same_entry(&real_src_path, &real_dst_path, self.policy.dereference)?)
+        {
+            TransferAction::Skip
+        } else {
+            // Modification time of simulated paths is unknown, assume the source is newer.
+            select_action(src_path, dst_path, dst_type, &self.policy, || {
+                Ok(simulated || is_newer(&real_src_path, &real_dst_path, self.policy.dereference)?)
+            })?
+        };
+
+        let mut created_dirs = dst_path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| {
+                !dir.as_os_str().is_empty() && self.file_type(dir, true) == FileType::Unknown
+            })
+            .map(Path::to_path_buf)
+            .collect::<Vec<PathBuf>>();
+        created_dirs.reverse();
+
+        for dir in &created_dirs {
+            self.overrides
+                .insert(normalize_path(dir, &self.working_dir), FileType::Dir);
+        }
+
+        if action != TransferAction::Skip {
+            if mode == TransferMode::Move {
+                self.overrides.insert(src_key, FileType::Unknown);
             }
+            self.overrides.insert(dst_key, src_type);
+        }
+
+        Ok((action, created_dirs))
+    }
+
+    pub fn remove(&mut self, path: &Path) -> Result<()> {
+        if self.file_type(path, false) == FileType::Unknown {
+            Err(make_not_found_error(path))
+        } else {
+            self.overrides
+                .insert(normalize_path(path, &self.working_dir), FileType::Unknown);
             Ok(())
         }
+    }
 
-        (FileType::Dir, dst_type) => {
-            dir::create_all(dst_path, false)?;
-            match mode {
-                TransferMode::Move => {
-                    if fs::rename(src_path, dst_path).is_err() {
-                        dir::move_dir(src_path, dst_path, &DIR_COPY_OPTIONS)?;
-                    }
-                }
-                TransferMode::Copy => {
-                    if dst_type == FileType::Unknown || !is_same_file(src_path, dst_path)? {
-                        dir::copy(src_path, dst_path, &DIR_COPY_OPTIONS)?;
-                    }
-                }
+    fn file_type(&self, path: &Path, follow_links: bool) -> FileType {
+        let key = normalize_path(path, &self.working_dir);
+
+        if let Some(file_type) = self.overrides.get(&key) {
+            *file_type
+        } else if key
+            .ancestors()
+            .skip(1)
+            .any(|dir| self.overrides.contains_key(dir))
+        {
+            // Content of moved or created directories is not simulated.
+            FileType::Unknown
+        } else {
+            FileType::resolve(&self.working_dir.join(path), follow_links)
+        }
+    }
+
+    fn is_overridden(&self, key: &Path) -> bool {
+        key.ancestors()
+            .any(|path| self.overrides.contains_key(path))
+    }
+}
+
+pub fn move_path(src_path: &Path, dst_path: &Path) -> Result<()> {
+    let policy = TransferPolicy {
+        preserve: PreserveAttrs::all(),
+        ..TransferPolicy::default()
+    };
+    let src_type = FileType::from(src_path);
+
+    if src_type == FileType::Unknown {
+        return Err(make_not_found_error(src_path));
+    }
+
+    create_path(
+        src_path,
+        src_type,
+        dst_path,
+        TransferMode::Move,
+        &policy,
+        None,
+    )
+}
+
+pub fn remove_path(path: &Path) -> Result<()> {
+    match FileType::from(path) {
+        FileType::Unknown => Err(make_not_found_error(path)),
+        FileType::File => file::remove(path),
+        FileType::Dir => dir::remove(path),
+        FileType::Symlink => Ok(fs::remove_file(path)?),
+    }
+}
+
+fn make_not_found_error(path: &Path) -> Error {
+    Error::new(
+        ErrorKind::NotFound,
+        &format!(
+            "Path '{}' not found or user lacks permission",
+            path.to_string_lossy()
+        ),
+    )
+}
+
+pub fn normalize_path(path: &Path, working_dir: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+
+    for component in working_dir.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
             }
-            Ok(())
+            _ => result.push(component),
+        }
+    }
+
+    result
+}
+
+pub fn find_missing_dirs(path: &Path) -> Vec<PathBuf> {
+    let mut dirs = path
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| !dir.as_os_str().is_empty() && dir.symlink_metadata().is_err())
+        .map(Path::to_path_buf)
+        .collect::<Vec<PathBuf>>();
+    dirs.reverse();
+    dirs
+}
+
+pub fn make_temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+    loop {
+        let temp_path = path.with_file_name(format!(".{}.{:08x}.tmp", name, rand::random::<u32>()));
+        if temp_path.symlink_metadata().is_err() {
+            return temp_path;
         }
     }
 }
 
 lazy_static! {
     pub static ref FILE_COPY_OPTIONS: file::CopyOptions = get_file_copy_options();
-    pub static ref DIR_COPY_OPTIONS: dir::CopyOptions = get_dir_copy_options();
 }
 
 fn get_file_copy_options() -> file::CopyOptions {
@@ -112,25 +909,19 @@ fn get_file_copy_options() -> file::CopyOptions {
     options
 }
 
-fn get_dir_copy_options() -> dir::CopyOptions {
-    let mut options = dir::CopyOptions::new();
-    options.overwrite = true;
-    options.skip_exist = false;
-    options.copy_inside = true;
-    options.content_only = true;
-    options
-}
-
 #[cfg(test)]
 mod tests {
+    use assert_fs::fixture::ChildPath;
     use assert_fs::prelude::*;
     use assert_fs::{NamedTempFile, TempDir};
     use fs_extra::error::ErrorKind;
     use ntest::*;
+    use same_file::is_same_file;
     use test_case::test_case;
 
     use super::*;
     use crate::transfer::testing::{debug_fse_error_kind, unpack_fse_error};
+    use std::result;
 
     #[test_case(temp_dir().path(),            FileType::Dir     ; "dir")]
     #[test_case(touch(temp_file("a")).path(), FileType::File    ; "file")]
@@ -139,7 +930,17 @@ mod tests {
         assert_eq!(FileType::from(path), file_type);
     }
 
+    #[test_case(false, FileType::Symlink ; "no follow")]
+    #[test_case(true,  FileType::Unknown ; "follow")]
+    fn file_type_dangling_symlink(follow_links: bool, file_type: FileType) {
+        let dir = temp_dir();
+        let link = symlink("x", dir.child("a"));
+        assert_eq!(FileType::resolve(link.path(), follow_links), file_type);
+    }
+
     mod transfer_path {
+        use test_case::test_case;
+
         use super::*;
 
         #[test]
@@ -147,8 +948,13 @@ mod tests {
             let src_file = temp_file("a");
 
             assert_eq!(
-                transfer_path(src_file.path(), &Path::new("b"), TransferMode::Move) // Mode is irrelevant
-                    .map_err(unpack_fse_error),
+                transfer_path(
+                    src_file.path(),
+                    &Path::new("b"),
+                    TransferMode::Move,
+                    &TransferPolicy::default()
+                ) // Mode is irrelevant
+                .map_err(unpack_fse_error),
                 Err((
                     debug_fse_error_kind(ErrorKind::NotFound),
                     format!(
@@ -167,8 +973,13 @@ mod tests {
             let dst_dir = temp_dir();
 
             assert_eq!(
-                transfer_path(src_file.path(), dst_dir.path(), TransferMode::Move) // Mode is irrelevant
-                    .map_err(unpack_fse_error),
+                transfer_path(
+                    src_file.path(),
+                    dst_dir.path(),
+                    TransferMode::Move,
+                    &TransferPolicy::default()
+                ) // Mode is irrelevant
+                .map_err(unpack_fse_error),
                 Err((
                     debug_fse_error_kind(ErrorKind::Other),
                     format!(
@@ -189,8 +1000,13 @@ mod tests {
             let dst_file = touch(temp_file("a"));
 
             assert_eq!(
-                transfer_path(src_dir.path(), dst_file.path(), TransferMode::Move) // Mode is irrelevant
-                    .map_err(unpack_fse_error),
+                transfer_path(
+                    src_dir.path(),
+                    dst_file.path(),
+                    TransferMode::Move,
+                    &TransferPolicy::default()
+                ) // Mode is irrelevant
+                .map_err(unpack_fse_error),
                 Err((
                     debug_fse_error_kind(ErrorKind::Other),
                     format!(
@@ -211,9 +1027,14 @@ mod tests {
             let dst_file = temp_file("b");
 
             assert_eq!(
-                transfer_path(src_file.path(), dst_file.path(), TransferMode::Move)
-                    .map_err(unpack_fse_error),
-                Ok(())
+                transfer_path(
+                    src_file.path(),
+                    dst_file.path(),
+                    TransferMode::Move,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Create)
             );
 
             src_file.assert(predicates::path::missing());
@@ -225,9 +1046,14 @@ mod tests {
             let src_file = write(temp_file("a"), "1");
 
             assert_eq!(
-                transfer_path(src_file.path(), src_file.path(), TransferMode::Move)
-                    .map_err(unpack_fse_error),
-                Ok(())
+                transfer_path(
+                    src_file.path(),
+                    src_file.path(),
+                    TransferMode::Move,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Create)
             );
 
             src_file.assert("1");
@@ -239,9 +1065,14 @@ mod tests {
             let dst_file = write(temp_file("b"), "2");
 
             assert_eq!(
-                transfer_path(src_file.path(), dst_file.path(), TransferMode::Move)
-                    .map_err(unpack_fse_error),
-                Ok(())
+                transfer_path(
+                    src_file.path(),
+                    dst_file.path(),
+                    TransferMode::Move,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Overwrite)
             );
 
             src_file.assert(predicates::path::missing());
@@ -254,9 +1085,14 @@ mod tests {
             let dst_file = temp_file("b");
 
             assert_eq!(
-                transfer_path(src_file.path(), dst_file.path(), TransferMode::Copy)
-                    .map_err(unpack_fse_error),
-                Ok(())
+                transfer_path(
+                    src_file.path(),
+                    dst_file.path(),
+                    TransferMode::Copy,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Create)
             );
 
             src_file.assert("1");
@@ -269,9 +1105,14 @@ mod tests {
             let src_file = write(temp_file("a"), "1");
 
             assert_eq!(
-                transfer_path(src_file.path(), src_file.path(), TransferMode::Copy)
-                    .map_err(unpack_fse_error),
-                Ok(())
+                transfer_path(
+                    src_file.path(),
+                    src_file.path(),
+                    TransferMode::Copy,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Skip)
             );
 
             src_file.assert("1");
@@ -283,9 +1124,14 @@ mod tests {
             let dst_file = write(temp_file("b"), "2");
 
             assert_eq!(
-                transfer_path(src_file.path(), dst_file.path(), TransferMode::Copy)
-                    .map_err(unpack_fse_error),
-                Ok(())
+                transfer_path(
+                    src_file.path(),
+                    dst_file.path(),
+                    TransferMode::Copy,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Overwrite)
             );
 
             src_file.assert("1");
@@ -293,8 +1139,49 @@ mod tests {
         }
 
         #[test]
-        fn rename_dir() {
-            let root_dir = temp_dir();
+        fn copy_file_through_temp_file() {
+            let dir = temp_dir();
+            write(dir.child("a"), "1");
+            let dst_file = write(dir.child("b"), "2");
+
+            assert_eq!(
+                transfer_path(
+                    dir.child("a").path(),
+                    dst_file.path(),
+                    TransferMode::Copy,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Overwrite)
+            );
+
+            dst_file.assert("1");
+            assert_eq!(list_dir(dir.path()), vec!["a", "b"]);
+        }
+
+        #[test]
+        fn copy_file_cleanup() {
+            let dir = temp_dir();
+            let src_file = write(dir.child("a"), "1");
+            let dst_dir = mkdir(dir.child("b"));
+            touch(dst_dir.child("c"));
+
+            // Temporary copy cannot be renamed to a non-empty directory.
+            assert_true!(super::super::copy_file(
+                src_file.path(),
+                dst_dir.path(),
+                &TransferPolicy::default(),
+                None
+            )
+            .is_err());
+
+            assert_eq!(list_dir(dir.path()), vec!["a", "b"]);
+            assert_eq!(list_dir(dst_dir.path()), vec!["c"]);
+        }
+
+        #[test]
+        fn rename_dir() {
+            let root_dir = temp_dir();
 
             let src_dir = mkdir(root_dir.child("a"));
             let src_file = write(src_dir.child("c"), "1");
@@ -303,9 +1190,14 @@ mod tests {
             let dst_file = dst_dir.child("c");
 
             assert_eq!(
-                transfer_path(src_dir.path(), dst_dir.path(), TransferMode::Move)
-                    .map_err(unpack_fse_error),
-                Ok(())
+                transfer_path(
+                    src_dir.path(),
+                    dst_dir.path(),
+                    TransferMode::Move,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Create)
             );
 
             src_dir.assert(predicates::path::missing());
@@ -321,9 +1213,14 @@ mod tests {
             let src_file = write(src_dir.child("a"), "1");
 
             assert_eq!(
-                transfer_path(src_dir.path(), src_dir.path(), TransferMode::Move)
-                    .map_err(unpack_fse_error),
-                Ok(())
+                transfer_path(
+                    src_dir.path(),
+                    src_dir.path(),
+                    TransferMode::Move,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Create)
             );
 
             src_dir.assert(predicates::path::is_dir());
@@ -341,9 +1238,14 @@ mod tests {
             let dst_file = write(dst_dir.child("c"), "2");
 
             assert_eq!(
-                transfer_path(src_dir.path(), dst_dir.path(), TransferMode::Move)
-                    .map_err(unpack_fse_error),
-                Ok(())
+                transfer_path(
+                    src_dir.path(),
+                    dst_dir.path(),
+                    TransferMode::Move,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::MergeDir)
             );
 
             src_dir.assert(predicates::path::missing());
@@ -364,9 +1266,14 @@ mod tests {
             let dst_file = dst_dir.child("c");
 
             assert_eq!(
-                transfer_path(src_dir.path(), dst_dir.path(), TransferMode::Copy)
-                    .map_err(unpack_fse_error),
-                Ok(())
+                transfer_path(
+                    src_dir.path(),
+                    dst_dir.path(),
+                    TransferMode::Copy,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Create)
             );
 
             src_dir.assert(predicates::path::is_dir());
@@ -383,9 +1290,14 @@ mod tests {
             let src_file = write(src_dir.child("a"), "1");
 
             assert_eq!(
-                transfer_path(src_dir.path(), src_dir.path(), TransferMode::Copy)
-                    .map_err(unpack_fse_error),
-                Ok(())
+                transfer_path(
+                    src_dir.path(),
+                    src_dir.path(),
+                    TransferMode::Copy,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Skip)
             );
 
             src_dir.assert(predicates::path::is_dir());
@@ -403,9 +1315,14 @@ mod tests {
             let dst_file = write(dst_dir.child("c"), "2");
 
             assert_eq!(
-                transfer_path(src_dir.path(), dst_dir.path(), TransferMode::Copy)
-                    .map_err(unpack_fse_error),
-                Ok(())
+                transfer_path(
+                    src_dir.path(),
+                    dst_dir.path(),
+                    TransferMode::Copy,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::MergeDir)
             );
 
             src_dir.assert(predicates::path::is_dir());
@@ -414,13 +1331,978 @@ mod tests {
             dst_dir.assert(predicates::path::is_dir());
             dst_file.assert("1");
         }
+
+        #[test]
+        fn hard_link_file() {
+            let root_dir = temp_dir();
+            let src_file = write(root_dir.child("a"), "1");
+            let dst_file = write(root_dir.child("b/c"), "2");
+
+            assert_eq!(
+                transfer_path(
+                    src_file.path(),
+                    dst_file.path(),
+                    TransferMode::HardLink,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Overwrite)
+            );
+
+            src_file.assert("1");
+            dst_file.assert("1");
+            assert_true!(is_same_file(src_file.path(), dst_file.path()).unwrap());
+        }
+
+        #[test]
+        fn hard_link_dir() {
+            let root_dir = temp_dir();
+            let src_dir = mkdir(root_dir.child("a"));
+            let src_file = write(src_dir.child("b/c"), "1");
+            let dst_dir = root_dir.child("d");
+
+            assert_eq!(
+                transfer_path(
+                    src_dir.path(),
+                    dst_dir.path(),
+                    TransferMode::HardLink,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Create)
+            );
+
+            dst_dir.assert(predicates::path::is_dir());
+            assert_false!(is_same_file(src_dir.path(), dst_dir.path()).unwrap());
+            assert_true!(is_same_file(src_file.path(), dst_dir.child("b/c").path()).unwrap());
+        }
+
+        #[test_case(TransferMode::Symlink,         false ; "absolute")]
+        #[test_case(TransferMode::RelativeSymlink, true  ; "relative")]
+        fn symlink(mode: TransferMode, relative: bool) {
+            let root_dir = temp_dir();
+            let src_dir = mkdir(root_dir.child("a"));
+            write(src_dir.child("b"), "1");
+            let dst_dir = root_dir.child("c/d");
+
+            assert_eq!(
+                transfer_path(
+                    src_dir.path(),
+                    dst_dir.path(),
+                    mode,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Create)
+            );
+
+            let target_path = if relative {
+                PathBuf::from("../a")
+            } else {
+                src_dir.path().into()
+            };
+
+            assert_eq!(fs::read_link(dst_dir.path()).unwrap(), target_path);
+            dst_dir.child("b").assert("1");
+        }
+
+        #[test_case(TransferMode::Move, false ; "move ")]
+        #[test_case(TransferMode::Move, true  ; "move dereference")]
+        #[test_case(TransferMode::Copy, false ; "copy")]
+        #[test_case(TransferMode::Copy, true  ; "copy dereference")]
+        fn symlink_to_file(mode: TransferMode, dereference: bool) {
+            let root_dir = temp_dir();
+            let src_file = write(root_dir.child("a"), "1");
+            let src_link = symlink("a", root_dir.child("b"));
+            let dst_path = root_dir.child("c");
+
+            let policy = TransferPolicy {
+                dereference,
+                ..TransferPolicy::default()
+            };
+
+            assert_eq!(
+                transfer_path(src_link.path(), dst_path.path(), mode, &policy)
+                    .map_err(unpack_fse_error),
+                Ok(TransferAction::Create)
+            );
+
+            if dereference {
+                assert_false!(dst_path
+                    .path()
+                    .symlink_metadata()
+                    .unwrap()
+                    .file_type()
+                    .is_symlink());
+            } else {
+                assert_eq!(fs::read_link(dst_path.path()).unwrap(), PathBuf::from("a"));
+            }
+
+            if mode == TransferMode::Move {
+                assert_false!(src_link.path().symlink_metadata().is_ok());
+            }
+
+            src_file.assert("1");
+            dst_path.assert("1");
+        }
+
+        #[test]
+        fn move_dir_with_symlink_dereference() {
+            let root_dir = temp_dir();
+            let src_dir = mkdir(root_dir.child("a"));
+            let target_file = write(root_dir.child("b"), "1");
+            symlink("../b", src_dir.child("c"));
+            let dst_dir = root_dir.child("d");
+
+            let policy = TransferPolicy {
+                dereference: true,
+                ..TransferPolicy::default()
+            };
+
+            assert_eq!(
+                transfer_path(src_dir.path(), dst_dir.path(), TransferMode::Move, &policy)
+                    .map_err(unpack_fse_error),
+                Ok(TransferAction::Create)
+            );
+
+            assert_false!(dst_dir
+                .child("c")
+                .path()
+                .symlink_metadata()
+                .unwrap()
+                .file_type()
+                .is_symlink());
+
+            src_dir.assert(predicates::path::missing());
+            target_file.assert("1");
+            dst_dir.child("c").assert("1");
+        }
+
+        #[test]
+        fn move_symlink_to_its_target_dereference() {
+            let root_dir = temp_dir();
+            let target_file = write(root_dir.child("a"), "1");
+            let src_link = symlink("a", root_dir.child("b"));
+
+            let policy = TransferPolicy {
+                dereference: true,
+                ..TransferPolicy::default()
+            };
+
+            assert_eq!(
+                transfer_path(
+                    src_link.path(),
+                    target_file.path(),
+                    TransferMode::Move,
+                    &policy
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Skip)
+            );
+
+            target_file.assert("1");
+        }
+
+        #[test_case(false ; "keep links")]
+        #[test_case(true  ; "dereference")]
+        fn copy_dir_with_symlink(dereference: bool) {
+            let root_dir = temp_dir();
+            let src_dir = mkdir(root_dir.child("a"));
+            write(src_dir.child("b"), "1");
+            symlink("b", src_dir.child("c"));
+            let dst_dir = root_dir.child("d");
+
+            let policy = TransferPolicy {
+                dereference,
+                ..TransferPolicy::default()
+            };
+
+            assert_eq!(
+                transfer_path(src_dir.path(), dst_dir.path(), TransferMode::Copy, &policy)
+                    .map_err(unpack_fse_error),
+                Ok(TransferAction::Create)
+            );
+
+            let dst_link = dst_dir.child("c");
+            let is_symlink = dst_link
+                .path()
+                .symlink_metadata()
+                .unwrap()
+                .file_type()
+                .is_symlink();
+
+            assert_eq!(is_symlink, !dereference);
+            dst_link.assert("1");
+        }
+
+        #[test]
+        fn copy_dangling_symlink() {
+            let root_dir = temp_dir();
+            let src_link = symlink("x", root_dir.child("a"));
+            let dst_link = root_dir.child("b");
+
+            assert_eq!(
+                transfer_path(
+                    src_link.path(),
+                    dst_link.path(),
+                    TransferMode::Copy,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Create)
+            );
+
+            assert_eq!(fs::read_link(dst_link.path()).unwrap(), PathBuf::from("x"));
+        }
+
+        #[test]
+        fn reflink_file() {
+            let root_dir = temp_dir();
+            let src_file = write(root_dir.child("a"), "1");
+            let dst_file = root_dir.child("b");
+
+            // Support depends on file system of the temporary directory.
+            match transfer_path(
+                src_file.path(),
+                dst_file.path(),
+                TransferMode::Reflink,
+                &TransferPolicy::default(),
+            ) {
+                Ok(action) => {
+                    assert_eq!(action, TransferAction::Create);
+                    dst_file.assert("1");
+                }
+                Err(error) => {
+                    assert_true!(error.to_string().starts_with("Cannot clone"));
+                    dst_file.assert(predicates::path::missing());
+                }
+            }
+
+            src_file.assert("1");
+        }
+    }
+
+    mod transfer_policy {
+        use std::time::{Duration, SystemTime};
+
+        use test_case::test_case;
+
+        use super::*;
+        use crate::transfer::policy::BackupMode;
+
+        #[test]
+        fn skip() {
+            let src_file = write(temp_file("a"), "1");
+            let dst_file = write(temp_file("b"), "2");
+
+            assert_eq!(
+                transfer(src_file.path(), dst_file.path(), OverwritePolicy::Skip),
+                Ok(TransferAction::Skip)
+            );
+
+            src_file.assert("1");
+            dst_file.assert("2");
+        }
+
+        #[test]
+        fn fail() {
+            let src_file = write(temp_file("a"), "1");
+            let dst_file = write(temp_file("b"), "2");
+
+            assert_eq!(
+                transfer(src_file.path(), dst_file.path(), OverwritePolicy::Fail),
+                Err((
+                    debug_fse_error_kind(ErrorKind::AlreadyExists),
+                    format!(
+                        "Path '{}' already exists",
+                        dst_file.path().to_string_lossy()
+                    )
+                ))
+            );
+
+            src_file.assert("1");
+            dst_file.assert("2");
+        }
+
+        #[test_case(BackupMode::Suffix,   "b~"    ; "suffix")]
+        #[test_case(BackupMode::Numbered, "b.~1~" ; "numbered")]
+        fn backup(backup_mode: BackupMode, backup_name: &str) {
+            let dir = temp_dir();
+            let src_file = write(dir.child("a"), "1");
+            let dst_file = write(dir.child("b"), "2");
+
+            assert_eq!(
+                transfer(
+                    src_file.path(),
+                    dst_file.path(),
+                    OverwritePolicy::Backup(backup_mode)
+                ),
+                Ok(TransferAction::Overwrite)
+            );
+
+            src_file.assert(predicates::path::missing());
+            dst_file.assert("1");
+            dir.child(backup_name).assert("2");
+        }
+
+        #[test_case(-10, TransferAction::Skip,      "2" ; "older")]
+        #[test_case(10,  TransferAction::Overwrite, "1" ; "newer")]
+        fn update(src_age_offset: i64, action: TransferAction, dst_content: &str) {
+            let src_file = write(temp_file("a"), "1");
+            let dst_file = write(temp_file("b"), "2");
+
+            let now = SystemTime::now();
+            let src_time = if src_age_offset < 0 {
+                now - Duration::from_secs(src_age_offset.unsigned_abs())
+            } else {
+                now + Duration::from_secs(src_age_offset.unsigned_abs())
+            };
+
+            fs::File::options()
+                .write(true)
+                .open(src_file.path())
+                .unwrap()
+                .set_modified(src_time)
+                .unwrap();
+
+            assert_eq!(
+                transfer(src_file.path(), dst_file.path(), OverwritePolicy::Update),
+                Ok(action)
+            );
+
+            dst_file.assert(dst_content);
+        }
+
+        #[test]
+        fn merge_dir() {
+            let src_dir = temp_dir();
+            write(src_dir.child("a"), "1");
+            write(src_dir.child("b/c"), "2");
+
+            let dst_dir = temp_dir();
+            write(dst_dir.child("a"), "3");
+            write(dst_dir.child("b/d"), "4");
+
+            assert_eq!(
+                transfer(src_dir.path(), dst_dir.path(), OverwritePolicy::Skip),
+                Ok(TransferAction::MergeDir)
+            );
+
+            src_dir.child("a").assert("1");
+            src_dir.child("b").assert(predicates::path::missing());
+
+            dst_dir.child("a").assert("3");
+            dst_dir.child("b/c").assert("2");
+            dst_dir.child("b/d").assert("4");
+        }
+
+        #[test]
+        fn no_merge() {
+            let src_dir = temp_dir();
+            let dst_dir = temp_dir();
+            touch(dst_dir.child("a"));
+
+            assert_eq!(
+                transfer_path(
+                    src_dir.path(),
+                    dst_dir.path(),
+                    TransferMode::Move,
+                    &TransferPolicy {
+                        overwrite: OverwritePolicy::Overwrite,
+                        merge_dirs: false,
+                        ..TransferPolicy::default()
+                    }
+                )
+                .map_err(unpack_fse_error),
+                Err((
+                    debug_fse_error_kind(ErrorKind::AlreadyExists),
+                    format!(
+                        "Cannot merge directory '{}' into existing directory '{}'",
+                        src_dir.path().to_string_lossy(),
+                        dst_dir.path().to_string_lossy()
+                    )
+                ))
+            );
+
+            src_dir.assert(predicates::path::exists());
+            dst_dir.child("a").assert(predicates::path::exists());
+        }
+
+        #[test_case(false ; "off")]
+        #[test_case(true  ; "on")]
+        fn preserve(enabled: bool) {
+            use std::os::unix::fs::PermissionsExt;
+
+            let root_dir = temp_dir();
+            let src_dir = mkdir(root_dir.child("a"));
+            let src_file = write(src_dir.child("b"), "1");
+            let dst_dir = root_dir.child("c");
+            let dst_file = dst_dir.child("b");
+
+            let time = SystemTime::now() - Duration::from_secs(3600);
+            let mode = 0o751;
+
+            for path in &[src_file.path(), src_dir.path()] {
+                fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+                filetime::set_file_mtime(path, FileTime::from_system_time(time)).unwrap();
+            }
+
+            let policy = TransferPolicy {
+                preserve: if enabled {
+                    PreserveAttrs::all()
+                } else {
+                    PreserveAttrs::default()
+                },
+                ..TransferPolicy::default()
+            };
+
+            assert_eq!(
+                transfer_path(src_dir.path(), dst_dir.path(), TransferMode::Copy, &policy)
+                    .map_err(unpack_fse_error),
+                Ok(TransferAction::Create)
+            );
+
+            dst_file.assert("1");
+
+            for path in &[dst_file.path(), dst_dir.path()] {
+                let metadata = path.metadata().unwrap();
+                let same_time = FileTime::from_last_modification_time(&metadata)
+                    == FileTime::from_system_time(time);
+
+                // Mode of copied files may be preserved even without the policy.
+                if enabled {
+                    assert_eq!(metadata.permissions().mode() & 0o777, mode);
+                }
+                assert_eq!(same_time, enabled);
+            }
+        }
+
+        #[test_case(false ; "off")]
+        #[test_case(true  ; "on")]
+        #[cfg(unix)]
+        fn overwrite_mode(preserve: bool) {
+            use std::os::unix::fs::PermissionsExt;
+
+            let dir = temp_dir();
+            let src_file = write(dir.child("a"), "1");
+            let dst_file = write(dir.child("b"), "2");
+            fs::set_permissions(src_file.path(), fs::Permissions::from_mode(0o750)).unwrap();
+            fs::set_permissions(dst_file.path(), fs::Permissions::from_mode(0o640)).unwrap();
+
+            let policy = TransferPolicy {
+                preserve: PreserveAttrs {
+                    mode: preserve,
+                    ..PreserveAttrs::default()
+                },
+                ..TransferPolicy::default()
+            };
+
+            assert_eq!(
+                transfer_path(
+                    src_file.path(),
+                    dst_file.path(),
+                    TransferMode::Copy,
+                    &policy
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Overwrite)
+            );
+
+            let mode = dst_file.path().metadata().unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, if preserve { 0o750 } else { 0o640 });
+            dst_file.assert("1");
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn read_only_src() {
+            use std::os::unix::fs::PermissionsExt;
+
+            let dir = temp_dir();
+            let src_file = write(dir.child("a"), "1");
+            let dst_file = dir.child("b");
+            fs::set_permissions(src_file.path(), fs::Permissions::from_mode(0o444)).unwrap();
+
+            assert_eq!(
+                transfer_path(
+                    src_file.path(),
+                    dst_file.path(),
+                    TransferMode::Copy,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Create)
+            );
+
+            let mode = dst_file.path().metadata().unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o444);
+            dst_file.assert("1");
+        }
+
+        #[test]
+        fn overwrite_hard_link() {
+            let dir = temp_dir();
+            let src_file = write(dir.child("a"), "1");
+            let dst_file = write(dir.child("b"), "2");
+            let dst_link = dir.child("c");
+            fs::hard_link(dst_file.path(), dst_link.path()).unwrap();
+
+            assert_eq!(
+                transfer_path(
+                    src_file.path(),
+                    dst_file.path(),
+                    TransferMode::Copy,
+                    &TransferPolicy::default()
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Overwrite)
+            );
+
+            // Destination is replaced by a new file, its other links keep the original content.
+            dst_file.assert("1");
+            dst_link.assert("2");
+        }
+
+        fn transfer(
+            src_path: &Path,
+            dst_path: &Path,
+            overwrite: OverwritePolicy,
+        ) -> result::Result<TransferAction, (String, String)> {
+            let policy = TransferPolicy {
+                overwrite,
+                ..TransferPolicy::default()
+            };
+            transfer_path(src_path, dst_path, TransferMode::Move, &policy).map_err(unpack_fse_error)
+        }
+    }
+
+    mod remove_path {
+        use super::*;
+
+        #[test]
+        fn file() {
+            let file = touch(temp_file("a"));
+            assert_eq!(remove_path(file.path()).map_err(unpack_fse_error), Ok(()));
+            file.assert(predicates::path::missing());
+        }
+
+        #[test]
+        fn dir() {
+            let dir = temp_dir();
+            touch(dir.child("a"));
+            assert_eq!(remove_path(dir.path()).map_err(unpack_fse_error), Ok(()));
+            dir.assert(predicates::path::missing());
+        }
+
+        #[test]
+        fn symlink() {
+            let dir = temp_dir();
+            let file = touch(dir.child("a"));
+            let link = super::symlink("a", dir.child("b"));
+            assert_eq!(remove_path(link.path()).map_err(unpack_fse_error), Ok(()));
+            link.assert(predicates::path::missing());
+            file.assert(predicates::path::exists());
+        }
+
+        #[test]
+        fn not_found() {
+            let file = temp_file("a");
+            assert_eq!(
+                remove_path(file.path()).map_err(unpack_fse_error),
+                Err((
+                    debug_fse_error_kind(ErrorKind::NotFound),
+                    format!(
+                        "Path '{}' not found or user lacks permission",
+                        file.path().to_string_lossy()
+                    )
+                ))
+            );
+        }
+    }
+
+    mod dry_run {
+        use super::*;
+
+        #[test]
+        fn create() {
+            let dir = temp_dir();
+            touch(dir.child("a"));
+
+            assert_eq!(
+                DryRun::new(dir.path(), &TransferPolicy::default())
+                    .transfer(Path::new("a"), Path::new("b/c/d"), TransferMode::Move)
+                    .map_err(unpack_fse_error),
+                Ok((
+                    TransferAction::Create,
+                    vec![PathBuf::from("b"), PathBuf::from("b/c")]
+                ))
+            );
+
+            dir.child("a").assert(predicates::path::exists());
+            dir.child("b").assert(predicates::path::missing());
+        }
+
+        #[test]
+        fn overwrite() {
+            let dir = temp_dir();
+            write(dir.child("a"), "1");
+            write(dir.child("b"), "2");
+
+            assert_eq!(
+                DryRun::new(dir.path(), &TransferPolicy::default())
+                    .transfer(Path::new("a"), Path::new("b"), TransferMode::Copy)
+                    .map_err(unpack_fse_error),
+                Ok((TransferAction::Overwrite, Vec::new()))
+            );
+
+            dir.child("b").assert("2");
+        }
+
+        #[test]
+        fn merge_dir() {
+            let dir = temp_dir();
+            mkdir(dir.child("a"));
+            mkdir(dir.child("b"));
+
+            assert_eq!(
+                DryRun::new(dir.path(), &TransferPolicy::default())
+                    .transfer(Path::new("a"), Path::new("b"), TransferMode::Copy)
+                    .map_err(unpack_fse_error),
+                Ok((TransferAction::MergeDir, Vec::new()))
+            );
+        }
+
+        #[test]
+        fn skip() {
+            let dir = temp_dir();
+            touch(dir.child("a"));
+
+            assert_eq!(
+                DryRun::new(dir.path(), &TransferPolicy::default())
+                    .transfer(Path::new("a"), Path::new("./a"), TransferMode::Move)
+                    .map_err(unpack_fse_error),
+                Ok((TransferAction::Skip, Vec::new()))
+            );
+        }
+
+        #[test]
+        fn type_mismatch() {
+            let dir = temp_dir();
+            mkdir(dir.child("a"));
+            touch(dir.child("b"));
+
+            assert_eq!(
+                DryRun::new(dir.path(), &TransferPolicy::default())
+                    .transfer(Path::new("a"), Path::new("b"), TransferMode::Move)
+                    .map_err(unpack_fse_error),
+                Err((
+                    debug_fse_error_kind(ErrorKind::Other),
+                    "Cannot to overwrite file 'b' with directory 'a'".into()
+                ))
+            );
+        }
+
+        #[test]
+        fn sequence() {
+            let dir = temp_dir();
+            touch(dir.child("a"));
+
+            let mut dry_run = DryRun::new(dir.path(), &TransferPolicy::default());
+
+            assert_eq!(
+                dry_run
+                    .transfer(Path::new("a"), Path::new("b/c"), TransferMode::Move)
+                    .map_err(unpack_fse_error),
+                Ok((TransferAction::Create, vec![PathBuf::from("b")]))
+            );
+            assert_eq!(
+                dry_run
+                    .transfer(Path::new("b/c"), Path::new("b/d"), TransferMode::Move)
+                    .map_err(unpack_fse_error),
+                Ok((TransferAction::Create, Vec::new()))
+            );
+            assert_eq!(
+                dry_run
+                    .transfer(Path::new("a"), Path::new("e"), TransferMode::Move)
+                    .map_err(unpack_fse_error),
+                Err((
+                    debug_fse_error_kind(ErrorKind::NotFound),
+                    "Path 'a' not found or user lacks permission".into()
+                ))
+            );
+            assert_eq!(
+                dry_run.remove(Path::new("b/d")).map_err(unpack_fse_error),
+                Ok(())
+            );
+            assert_eq!(
+                dry_run.remove(Path::new("b/d")).map_err(unpack_fse_error),
+                Err((
+                    debug_fse_error_kind(ErrorKind::NotFound),
+                    "Path 'b/d' not found or user lacks permission".into()
+                ))
+            );
+
+            dir.child("a").assert(predicates::path::exists());
+        }
+    }
+
+    mod verify_copy {
+        use test_case::test_case;
+
+        use super::*;
+
+        #[test_case("abc", "abc", true  ; "same")]
+        #[test_case("abc", "abd", false ; "different content")]
+        #[test_case("abc", "ab",  false ; "different size")]
+        #[test_case("",    "",    true  ; "empty")]
+        fn file(src_content: &str, dst_content: &str, same: bool) {
+            let dir = temp_dir();
+            let src_file = write(dir.child("a"), src_content);
+            let dst_file = write(dir.child("b"), dst_content);
+
+            let result = verify_copy(src_file.path(), FileType::File, dst_file.path())
+                .map_err(unpack_fse_error);
+
+            if same {
+                assert_eq!(result, Ok(()));
+            } else {
+                assert_eq!(
+                    result,
+                    Err((
+                        debug_fse_error_kind(ErrorKind::Other),
+                        format!(
+                            "Copy '{}' differs from its source '{}'",
+                            dst_file.path().to_string_lossy(),
+                            src_file.path().to_string_lossy()
+                        )
+                    ))
+                );
+            }
+        }
+
+        #[test]
+        fn large_file() {
+            let dir = temp_dir();
+            let content = "x".repeat(3 * VERIFY_BLOCK_SIZE);
+            let src_file = write(dir.child("a"), &content);
+            let dst_file = write(dir.child("b"), &format!("{}y", &content[1..]));
+
+            assert_true!(verify_copy(src_file.path(), FileType::File, dst_file.path()).is_err());
+        }
+
+        #[test_case("a", true  ; "same")]
+        #[test_case("b", false ; "different")]
+        fn symlink(dst_target: &str, same: bool) {
+            let dir = temp_dir();
+            let src_link = super::symlink("a", dir.child("c"));
+            let dst_link = super::symlink(dst_target, dir.child("d"));
+
+            assert_eq!(
+                verify_copy(src_link.path(), FileType::Symlink, dst_link.path()).is_ok(),
+                same
+            );
+        }
+
+        #[test]
+        fn transfer() {
+            let root_dir = temp_dir();
+            let src_dir = mkdir(root_dir.child("a"));
+            write(src_dir.child("b/c"), "1");
+            let dst_dir = root_dir.child("d");
+
+            let policy = TransferPolicy {
+                verify: true,
+                ..TransferPolicy::default()
+            };
+
+            assert_eq!(
+                transfer_path(src_dir.path(), dst_dir.path(), TransferMode::Copy, &policy)
+                    .map_err(unpack_fse_error),
+                Ok(TransferAction::Create)
+            );
+
+            dst_dir.child("b/c").assert("1");
+        }
+    }
+
+    mod progress {
+        use super::*;
+
+        #[derive(Default)]
+        struct RecordedProgress {
+            events: Vec<String>,
+        }
+
+        impl TransferProgress for RecordedProgress {
+            fn begin_file(
+                &mut self,
+                _src_path: &Path,
+                dst_path: &Path,
+                size: u64,
+            ) -> io::Result<()> {
+                let name = dst_path.file_name().unwrap().to_string_lossy();
+                self.events.push(format!("begin {} {}", name, size));
+                Ok(())
+            }
+
+            fn update_file(&mut self, copied_size: u64) -> io::Result<()> {
+                self.events.push(format!("update {}", copied_size));
+                Ok(())
+            }
+
+            fn end_file(&mut self) -> io::Result<()> {
+                self.events.push("end".into());
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn copy_dir() {
+            let root_dir = temp_dir();
+            let src_dir = mkdir(root_dir.child("a"));
+            write(src_dir.child("b/c"), &"x".repeat(COPY_BLOCK_SIZE + 1));
+            let dst_dir = root_dir.child("d");
+            let mut progress = RecordedProgress::default();
+
+            assert_eq!(
+                transfer_path_with_progress(
+                    src_dir.path(),
+                    dst_dir.path(),
+                    TransferMode::Copy,
+                    &TransferPolicy::default(),
+                    Some(&mut progress)
+                )
+                .map_err(unpack_fse_error),
+                Ok(TransferAction::Create)
+            );
+
+            assert_eq!(
+                progress.events,
+                vec![
+                    format!("begin c {}", COPY_BLOCK_SIZE + 1),
+                    format!("update {}", COPY_BLOCK_SIZE),
+                    format!("update {}", COPY_BLOCK_SIZE + 1),
+                    "end".into(),
+                ]
+            );
+            dst_dir.child("b/c").assert("x".repeat(COPY_BLOCK_SIZE + 1));
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn copy_file_mode() {
+            use std::os::unix::fs::PermissionsExt;
+
+            let dir = temp_dir();
+            let src_file = write(dir.child("a"), "1");
+            let dst_file = dir.child("b");
+            fs::set_permissions(src_file.path(), fs::Permissions::from_mode(0o750)).unwrap();
+
+            transfer_path_with_progress(
+                src_file.path(),
+                dst_file.path(),
+                TransferMode::Copy,
+                &TransferPolicy::default(),
+                Some(&mut RecordedProgress::default()),
+            )
+            .unwrap();
+
+            let mode = dst_file.path().metadata().unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o750);
+        }
+
+        #[test]
+        fn rename() {
+            let dir = temp_dir();
+            let src_file = write(dir.child("a"), "1");
+            let mut progress = RecordedProgress::default();
+
+            transfer_path_with_progress(
+                src_file.path(),
+                dir.child("b").path(),
+                TransferMode::Move,
+                &TransferPolicy::default(),
+                Some(&mut progress),
+            )
+            .unwrap();
+
+            assert_eq!(progress.events, Vec::<String>::new());
+        }
     }
 
     #[test]
-    fn same_dir_and_file_copy_options() {
-        assert_eq!(DIR_COPY_OPTIONS.overwrite, FILE_COPY_OPTIONS.overwrite);
-        assert_eq!(DIR_COPY_OPTIONS.skip_exist, FILE_COPY_OPTIONS.skip_exist);
-        assert_eq!(DIR_COPY_OPTIONS.buffer_size, FILE_COPY_OPTIONS.buffer_size);
+    fn measure_size() {
+        let dir = temp_dir();
+        write(dir.child("a/b"), "12");
+        write(dir.child("a/c/d"), "345");
+        symlink("b", dir.child("a/e"));
+
+        assert_eq!(super::measure_size(dir.child("a").path(), false), 5);
+        assert_eq!(super::measure_size(dir.child("a").path(), true), 7);
+        assert_eq!(super::measure_size(dir.child("a/b").path(), false), 2);
+        assert_eq!(super::measure_size(dir.child("x").path(), false), 0);
+    }
+
+    #[test_case("a",          "/w/a"   ; "relative")]
+    #[test_case("/a",         "/a"     ; "absolute")]
+    #[test_case("./a/./b",    "/w/a/b" ; "current dir")]
+    #[test_case("a/../b",     "/w/b"   ; "parent dir")]
+    #[test_case("../../../a", "/a"     ; "parent of root")]
+    #[test_case("a/",         "/w/a"   ; "trailing separator")]
+    #[cfg(unix)]
+    fn normalize_path(path: &str, result: &str) {
+        assert_eq!(
+            super::normalize_path(Path::new(path), Path::new("/w")),
+            PathBuf::from(result)
+        );
+    }
+
+    #[test]
+    fn find_missing_dirs() {
+        let dir = temp_dir();
+        let existing_dir = mkdir(dir.child("a"));
+
+        assert_eq!(
+            super::find_missing_dirs(&existing_dir.path().join("b/c/d")),
+            vec![
+                existing_dir.path().join("b"),
+                existing_dir.path().join("b/c")
+            ]
+        );
+        assert_eq!(
+            super::find_missing_dirs(&existing_dir.path().join("b")),
+            Vec::<PathBuf>::new()
+        );
+    }
+
+    #[test]
+    fn make_temp_path() {
+        let dir = temp_dir();
+        let path = dir.child("a");
+        let temp_path = super::make_temp_path(path.path());
+
+        assert_eq!(temp_path.parent(), Some(dir.path()));
+        assert_ne!(temp_path, path.path());
+        assert_true!(temp_path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with(".a."));
+        assert_false!(temp_path.exists());
+    }
+
+    fn symlink(target: &str, link: ChildPath) -> ChildPath {
+        create_symlink(Path::new(target), link.path(), false).unwrap();
+        link
+    }
+
+    fn list_dir(path: &Path) -> Vec<String> {
+        let mut names = fs::read_dir(path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
     }
 
     fn temp_dir() -> TempDir {
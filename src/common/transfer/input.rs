@@ -2,9 +2,38 @@ use std::fmt;
 use std::io::{BufRead, Error, ErrorKind, Result};
 use std::path::PathBuf;
 
+use serde_json::Value;
+
+use crate::encoding::{encode_os_string, EncodingPolicy};
 use crate::input::{Splitter, Terminator};
 use crate::symbols::{DIFF_IN, DIFF_OUT};
 
+pub const INPUT_FORMATS: &[&str] = &[DIFF, JSON_LINES, TSV];
+
+const DIFF: &str = "diff";
+const JSON_LINES: &str = "jsonl";
+const TSV: &str = "tsv";
+
+const JSON_IN: &str = "in";
+const JSON_OUT: &str = "out";
+const TSV_SEPARATOR: char = '\t';
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InputFormat {
+    Diff,
+    JsonLines,
+    Tsv,
+}
+
+pub fn parse_input_format(string: &str) -> std::result::Result<InputFormat, &'static str> {
+    match string {
+        DIFF => Ok(InputFormat::Diff),
+        JSON_LINES => Ok(InputFormat::JsonLines),
+        TSV => Ok(InputFormat::Tsv),
+        _ => Err("invalid value"),
+    }
+}
+
 struct Position {
     item: usize,
     offset: usize,
@@ -30,34 +59,76 @@ impl fmt::Display for Position {
 pub struct PathDiff<I: BufRead> {
     splitter: Splitter<I>,
     position: Position,
+    encoding: EncodingPolicy,
 }
 
 impl<I: BufRead> PathDiff<I> {
-    pub fn new(input: I, terminator: Terminator) -> Self {
+    pub fn new(input: I, terminator: Terminator, encoding: EncodingPolicy) -> Self {
         Self {
-            splitter: Splitter::new(input, terminator),
+            splitter: Splitter::with_policy(input, terminator, encoding),
             position: Position::new(),
+            encoding,
         }
     }
 
     pub fn read(&mut self) -> Result<Option<(PathBuf, PathBuf)>> {
-        let (in_path, in_size) = match self.splitter.read()? {
-            Some((value, size)) => (extract_path(value, &self.position, DIFF_IN)?, size),
-            None => return Ok(None),
+        Ok(self
+            .read_pair(None)?
+            .map(|(in_path, out_path, _)| (in_path, out_path)))
+    }
+
+    // Pair can be preceded by a line containing only the marker character.
+    pub fn read_marked(&mut self, marker: char) -> Result<Option<(PathBuf, PathBuf, bool)>> {
+        self.read_pair(Some(marker))
+    }
+
+    fn read_pair(&mut self, marker: Option<char>) -> Result<Option<(PathBuf, PathBuf, bool)>> {
+        let mut marked = false;
+
+        let (in_path, in_size) = loop {
+            match self.splitter.read()? {
+                Some((value, size)) if !marked && is_marker(value, marker) => {
+                    marked = true;
+                    self.position.increment(size);
+                }
+                Some((value, size)) => {
+                    break (
+                        extract_path(value, &self.position, DIFF_IN, self.encoding)?,
+                        size,
+                    )
+                }
+                None if marked => return Err(make_unexpected_eof_error(&self.position, DIFF_IN)),
+                None => return Ok(None),
+            }
         };
         self.position.increment(in_size);
 
         let (out_path, out_size) = match self.splitter.read()? {
-            Some((value, size)) => (extract_path(value, &self.position, DIFF_OUT)?, size),
+            Some((value, size)) => (
+                extract_path(value, &self.position, DIFF_OUT, self.encoding)?,
+                size,
+            ),
             None => return Err(make_unexpected_eof_error(&self.position, DIFF_OUT)),
         };
         self.position.increment(out_size);
 
-        Ok(Some((in_path, out_path)))
+        Ok(Some((in_path, out_path, marked)))
     }
 }
 
-fn extract_path(value: &str, position: &Position, prefix: char) -> Result<PathBuf> {
+fn is_marker(value: &str, marker: Option<char>) -> bool {
+    match marker {
+        Some(marker) => value.len() == marker.len_utf8() && value.starts_with(marker),
+        None => false,
+    }
+}
+
+fn extract_path(
+    value: &str,
+    position: &Position,
+    prefix: char,
+    encoding: EncodingPolicy,
+) -> Result<PathBuf> {
     if let Some(first_char) = value.chars().next() {
         if first_char == prefix {
             let path = &value[prefix.len_utf8()..];
@@ -67,7 +138,7 @@ fn extract_path(value: &str, position: &Position, prefix: char) -> Result<PathBu
                     format!("Expected a path after '{}' ({})", prefix, position),
                 ))
             } else {
-                Ok(path.into())
+                Ok(encode_os_string(path, encoding).into())
             }
         } else {
             Err(Error::new(
@@ -90,10 +161,149 @@ fn make_unexpected_eof_error(position: &Position, prefix: char) -> Error {
     )
 }
 
+type ParseRecord = fn(&str, &Position, EncodingPolicy) -> Result<(PathBuf, PathBuf)>;
+
+pub struct PathRecords<I: BufRead> {
+    splitter: Splitter<I>,
+    position: Position,
+    encoding: EncodingPolicy,
+    parse: ParseRecord,
+}
+
+impl<I: BufRead> PathRecords<I> {
+    pub fn json_lines(input: I, terminator: Terminator, encoding: EncodingPolicy) -> Self {
+        // JSON is always UTF-8, only its string values may contain escaped bytes.
+        let splitter = Splitter::new(input, terminator);
+        Self::new(splitter, encoding, parse_json_record)
+    }
+
+    pub fn tsv(input: I, terminator: Terminator, encoding: EncodingPolicy) -> Self {
+        let splitter = Splitter::with_policy(input, terminator, encoding);
+        Self::new(splitter, encoding, parse_tsv_record)
+    }
+
+    fn new(splitter: Splitter<I>, encoding: EncodingPolicy, parse: ParseRecord) -> Self {
+        Self {
+            splitter,
+            position: Position::new(),
+            encoding,
+            parse,
+        }
+    }
+
+    pub fn read(&mut self) -> Result<Option<(PathBuf, PathBuf)>> {
+        let (pair, size) = match self.splitter.read()? {
+            Some((value, size)) => ((self.parse)(value, &self.position, self.encoding)?, size),
+            None => return Ok(None),
+        };
+        self.position.increment(size);
+        Ok(Some(pair))
+    }
+}
+
+fn parse_json_record(
+    value: &str,
+    position: &Position,
+    encoding: EncodingPolicy,
+) -> Result<(PathBuf, PathBuf)> {
+    let record = serde_json::from_str::<Value>(value).map_err(|error| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid JSON: {} ({})", error, position),
+        )
+    })?;
+
+    let get_path = |key: &str| match record.get(key) {
+        Some(Value::String(path)) => {
+            check_path(path, position, &format!("field '{}'", key), encoding)
+        }
+        _ => Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Expected string field '{}' ({})", key, position),
+        )),
+    };
+
+    Ok((get_path(JSON_IN)?, get_path(JSON_OUT)?))
+}
+
+fn parse_tsv_record(
+    value: &str,
+    position: &Position,
+    encoding: EncodingPolicy,
+) -> Result<(PathBuf, PathBuf)> {
+    let fields = value.split(TSV_SEPARATOR).collect::<Vec<&str>>();
+
+    if let [in_path, out_path] = fields.as_slice() {
+        Ok((
+            check_path(in_path, position, "field #1", encoding)?,
+            check_path(out_path, position, "field #2", encoding)?,
+        ))
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "Expected 2 tab-separated fields but got {} ({})",
+                fields.len(),
+                position
+            ),
+        ))
+    }
+}
+
+fn check_path(
+    path: &str,
+    position: &Position,
+    field: &str,
+    encoding: EncodingPolicy,
+) -> Result<PathBuf> {
+    if path.is_empty() {
+        Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Expected a path in {} ({})", field, position),
+        ))
+    } else {
+        Ok(encode_os_string(path, encoding).into())
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub enum Answer {
+    Yes,
+    No,
+    All,
+    Quit,
+}
+
+pub fn read_answer<I: BufRead>(input: &mut I) -> Result<Option<Answer>> {
+    let mut line = String::new();
+
+    if input.read_line(&mut line)? == 0 {
+        return Ok(Some(Answer::Quit)); // Closed terminal
+    }
+
+    Ok(match line.trim().to_lowercase().as_str() {
+        "y" | "yes" => Some(Answer::Yes),
+        "n" | "no" => Some(Answer::No),
+        "a" | "all" => Some(Answer::All),
+        "q" | "quit" => Some(Answer::Quit),
+        _ => None,
+    })
+}
+
 #[cfg(test)]
 mod tests {
+    use test_case::test_case;
+
     use super::*;
 
+    #[test_case("diff",  Ok(InputFormat::Diff)      ; "diff")]
+    #[test_case("jsonl", Ok(InputFormat::JsonLines) ; "json lines")]
+    #[test_case("tsv",   Ok(InputFormat::Tsv)       ; "tsv")]
+    #[test_case("x",     Err("invalid value")       ; "invalid")]
+    fn parse_input_format(string: &str, result: std::result::Result<InputFormat, &'static str>) {
+        assert_eq!(super::parse_input_format(string), result);
+    }
+
     #[test]
     fn position() {
         let mut position = Position::new();
@@ -104,6 +314,21 @@ mod tests {
         assert_eq!(position.to_string(), "item #3 at offset 3");
     }
 
+    #[test_case("",       Some(Answer::Quit) ; "eof")]
+    #[test_case("y\n",    Some(Answer::Yes)  ; "y")]
+    #[test_case("Yes\n",  Some(Answer::Yes)  ; "yes")]
+    #[test_case("n\n",    Some(Answer::No)   ; "n")]
+    #[test_case(" no \n", Some(Answer::No)   ; "no")]
+    #[test_case("a\n",    Some(Answer::All)  ; "a")]
+    #[test_case("all",    Some(Answer::All)  ; "all")]
+    #[test_case("q\n",    Some(Answer::Quit) ; "q")]
+    #[test_case("QUIT\n", Some(Answer::Quit) ; "quit")]
+    #[test_case("\n",     None               ; "empty")]
+    #[test_case("x\n",    None               ; "invalid")]
+    fn read_answer(input: &str, result: Option<Answer>) {
+        assert_eq!(super::read_answer(&mut input.as_bytes()).unwrap(), result);
+    }
+
     mod path_diff {
         use test_case::test_case;
 
@@ -115,8 +340,11 @@ mod tests {
         #[test_case("<abc\n>def\n< g \n> h ", 1, Some((" g ", " h ")) ; "nonempty 1")]
         #[test_case("<abc\n>def\n< g \n> h ", 2, None                 ; "nonempty 2")]
         fn ok(input: &str, position: usize, result: Option<(&str, &str)>) {
-            let mut path_diff =
-                PathDiff::new(input.as_bytes(), Terminator::Newline { required: false });
+            let mut path_diff = PathDiff::new(
+                input.as_bytes(),
+                Terminator::Newline { required: false },
+                EncodingPolicy::Fail,
+            );
 
             for _ in 0..position {
                 path_diff.read().unwrap_or_default();
@@ -138,11 +366,124 @@ mod tests {
         #[test_case("<a\n>", E::UnexpectedEof, "Expected a path after '>' (item #2 at offset 3)" ; "out path missing")]
         fn err(input: &str, kind: ErrorKind, message: &str) {
             assert_eq!(
-                PathDiff::new(input.as_bytes(), Terminator::Newline { required: false })
-                    .read()
-                    .map_err(unpack_io_error),
+                PathDiff::new(
+                    input.as_bytes(),
+                    Terminator::Newline { required: false },
+                    EncodingPolicy::Fail,
+                )
+                .read()
+                .map_err(unpack_io_error),
                 Err((kind, message.into()))
             )
         }
+
+        #[test]
+        #[cfg(unix)]
+        fn escape() {
+            use std::os::unix::ffi::OsStrExt;
+
+            let mut path_diff = PathDiff::new(
+                &b"<a\xE9%\n>b%E9\n"[..],
+                Terminator::Newline { required: false },
+                EncodingPolicy::Escape,
+            );
+
+            assert_eq!(
+                path_diff.read().map_err(unpack_io_error),
+                Ok(Some((
+                    PathBuf::from(std::ffi::OsStr::from_bytes(b"a\xE9%")),
+                    PathBuf::from("b%E9")
+                )))
+            );
+        }
+    }
+
+    mod path_records {
+        use test_case::test_case;
+
+        use super::*;
+        use crate::testing::unpack_io_error;
+
+        const JSONL: InputFormat = InputFormat::JsonLines;
+        const TSV: InputFormat = InputFormat::Tsv;
+
+        #[test_case(JSONL, "",                                                  0, None                      ; "json lines empty")]
+        #[test_case(JSONL, "{\"in\":\"a\",\"out\":\"b\"}\n{\"out\":\"d\",\"in\":\"c\"}", 1, Some(("c", "d"))          ; "json lines order")]
+        #[test_case(JSONL, "{\"in\":\"a\\nb\",\"out\":\"\\\"c\\u00e9\"}",          0, Some(("a\nb", "\"c\u{e9}")) ; "json lines escapes")]
+        #[test_case(JSONL, "{\"in\":\"a\",\"out\":\"b\",\"x\":1}",                  0, Some(("a", "b"))          ; "json lines extra field")]
+        #[test_case(JSONL, "{\"in\":\"a\",\"out\":\"b\"}\n",                         1, None                      ; "json lines end")]
+        #[test_case(TSV,   "a\tb\n c \t d \n",                                    0, Some(("a", "b"))          ; "tsv 0")]
+        #[test_case(TSV,   "a\tb\n c \t d \n",                                    1, Some((" c ", " d "))      ; "tsv 1")]
+        #[test_case(TSV,   "a\tb\n c \t d \n",                                    2, None                      ; "tsv 2")]
+        fn ok(format: InputFormat, input: &str, position: usize, result: Option<(&str, &str)>) {
+            let mut records = new_records(format, input);
+
+            for _ in 0..position {
+                records.read().unwrap_or_default();
+            }
+
+            assert_eq!(
+                records.read().map_err(unpack_io_error),
+                Ok(result.map(|(first, second)| (first.into(), second.into())))
+            );
+        }
+
+        #[test_case(JSONL, "{\"in\":\"a\"",                 "Invalid JSON: EOF while parsing an object at line 1 column 9 (item #1 at offset 0)" ; "json lines invalid")]
+        #[test_case(JSONL, "{\"out\":\"b\"}",               "Expected string field 'in' (item #1 at offset 0)"                                   ; "json lines in missing")]
+        #[test_case(JSONL, "{\"in\":\"a\",\"out\":1}",      "Expected string field 'out' (item #1 at offset 0)"                                  ; "json lines out number")]
+        #[test_case(JSONL, "{\"in\":\"\",\"out\":\"b\"}",   "Expected a path in field 'in' (item #1 at offset 0)"                                ; "json lines in empty")]
+        #[test_case(JSONL, "[\"a\",\"b\"]",                 "Expected string field 'in' (item #1 at offset 0)"                                   ; "json lines array")]
+        #[test_case(TSV,   "a",                           "Expected 2 tab-separated fields but got 1 (item #1 at offset 0)"                    ; "tsv one field")]
+        #[test_case(TSV,   "a\tb\tc",                     "Expected 2 tab-separated fields but got 3 (item #1 at offset 0)"                    ; "tsv three fields")]
+        #[test_case(TSV,   "a\t",                         "Expected a path in field #2 (item #1 at offset 0)"                                  ; "tsv out empty")]
+        fn err(format: InputFormat, input: &str, message: &str) {
+            assert_eq!(
+                new_records(format, input).read().map_err(unpack_io_error),
+                Err((ErrorKind::InvalidData, message.into()))
+            )
+        }
+
+        fn new_records(format: InputFormat, input: &str) -> PathRecords<&[u8]> {
+            let terminator = Terminator::Newline { required: false };
+            match format {
+                InputFormat::JsonLines => {
+                    PathRecords::json_lines(input.as_bytes(), terminator, EncodingPolicy::Fail)
+                }
+                _ => PathRecords::tsv(input.as_bytes(), terminator, EncodingPolicy::Fail),
+            }
+        }
+
+        #[test]
+        #[cfg(unix)]
+        fn json_lines_escape() {
+            use std::os::unix::ffi::OsStrExt;
+
+            let mut records = PathRecords::json_lines(
+                &b"{\"in\":\"a%E9\",\"out\":\"b%25\"}"[..],
+                Terminator::Newline { required: false },
+                EncodingPolicy::Escape,
+            );
+
+            assert_eq!(
+                records.read().map_err(unpack_io_error),
+                Ok(Some((
+                    PathBuf::from(std::ffi::OsStr::from_bytes(b"a\xE9")),
+                    PathBuf::from("b%")
+                )))
+            );
+        }
+
+        #[test]
+        fn err_position() {
+            let mut records = new_records(TSV, "a\tb\nc\n");
+            records.read().unwrap();
+            assert_eq!(
+                records.read().map_err(unpack_io_error),
+                Err((
+                    ErrorKind::InvalidData,
+                    "Expected 2 tab-separated fields but got 1 (item #2 at offset 4)".into()
+                ))
+            );
+        }
     }
 }
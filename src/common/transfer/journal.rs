@@ -0,0 +1,286 @@
+use std::collections::HashSet;
+use std::io::{BufRead, Result, Write};
+use std::path::{Path, PathBuf};
+
+use crate::encoding::EncodingPolicy;
+use crate::input::Terminator;
+use crate::transfer::fs::TransferMode;
+use crate::transfer::input::PathDiff;
+use crate::transfer::output::PathDiffWriter;
+use crate::transfer::plan::{plan_transfer, Conflict, Step};
+
+// Line preceding a record whose destination path existed before the transfer.
+const EXISTED_MARKER: char = '!';
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct JournalRecord {
+    pub src_path: PathBuf,
+    pub dst_path: PathBuf,
+    // Whether destination path existed before the transfer.
+    pub existed: bool,
+}
+
+pub struct JournalWriter<O: Write> {
+    writer: PathDiffWriter<O>,
+    records: Vec<JournalRecord>,
+}
+
+impl<O: Write> JournalWriter<O> {
+    pub fn new(output: O, terminator: &str, records: Vec<JournalRecord>) -> Self {
+        Self {
+            writer: PathDiffWriter::new(output, terminator),
+            records,
+        }
+    }
+
+    pub fn write(&mut self, pair_index: usize) -> Result<()> {
+        let record = &self.records[pair_index];
+        if record.existed {
+            self.writer.write_marker(EXISTED_MARKER)?;
+        }
+        // Direction is inverted, so the journal can be directly used as instructions.
+        self.writer.write(&record.dst_path, &record.src_path)
+    }
+}
+
+pub fn read_journal<I: BufRead>(input: I, terminator: Terminator) -> Result<Vec<JournalRecord>> {
+    // Paths are written as raw bytes, so escaping reads them back losslessly.
+    let mut path_diff = PathDiff::new(input, terminator, EncodingPolicy::Escape);
+    let mut records = Vec::new();
+
+    while let Some((dst_path, src_path, existed)) = path_diff.read_marked(EXISTED_MARKER)? {
+        records.push(JournalRecord {
+            src_path,
+            dst_path,
+            existed,
+        });
+    }
+
+    Ok(records)
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Undo {
+    pub steps: Vec<Step>,
+    // Paths created by the recorded transfers which have to be removed.
+    pub removed_paths: Vec<PathBuf>,
+    // Paths which existed before the recorded transfers but their content is lost.
+    pub kept_paths: Vec<PathBuf>,
+}
+
+pub fn plan_undo(
+    records: &[JournalRecord],
+    mode: TransferMode,
+    working_dir: &Path,
+) -> std::result::Result<Undo, Vec<Conflict>> {
+    let src_paths = records
+        .iter()
+        .map(|record| record.src_path.as_path())
+        .collect::<HashSet<&Path>>();
+
+    let dst_paths = records
+        .iter()
+        .map(|record| record.dst_path.as_path())
+        .collect::<HashSet<&Path>>();
+
+    let mut pairs = Vec::new();
+    let mut modes = Vec::new();
+    let mut removed_paths = Vec::new();
+    let mut kept_paths = Vec::new();
+    let mut restored_paths = HashSet::new();
+
+    for record in records {
+        // Source was either moved away or overwritten by another record,
+        // in both cases its original content is now in the destination.
+        let src_changed =
+            mode == TransferMode::Move || dst_paths.contains(record.src_path.as_path());
+
+        // Destination which was also a source gets restored by the other record.
+        let dst_restored = src_paths.contains(record.dst_path.as_path());
+        let dst_kept = record.existed && !dst_restored;
+
+        // Source copied to multiple destinations is restored only from the first one.
+        if src_changed && restored_paths.insert(record.src_path.as_path()) {
+            pairs.push((record.dst_path.clone(), record.src_path.clone()));
+            // Path which existed before is never moved away (removed).
+            modes.push(if dst_kept {
+                TransferMode::Copy
+            } else {
+                TransferMode::Move
+            });
+        } else if !record.existed {
+            removed_paths.push(record.dst_path.clone());
+        }
+
+        if dst_kept {
+            kept_paths.push(record.dst_path.clone());
+        }
+    }
+
+    // Newer paths might be nested in older ones.
+    removed_paths.reverse();
+
+    // Only pairs which are part of a cycle are staged and those are always moved.
+    let mut steps = plan_transfer(&pairs, TransferMode::Move, working_dir)?;
+
+    for step in &mut steps {
+        if let Some(index) = step.pair_index {
+            step.mode = modes[index];
+        }
+    }
+
+    Ok(Undo {
+        steps,
+        removed_paths,
+        kept_paths,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::testing::unpack_io_error;
+
+    fn record(src_path: &str, dst_path: &str, existed: bool) -> JournalRecord {
+        JournalRecord {
+            src_path: src_path.into(),
+            dst_path: dst_path.into(),
+            existed,
+        }
+    }
+
+    fn step(src_path: &str, dst_path: &str, mode: TransferMode, pair_index: usize) -> Step {
+        Step {
+            src_path: src_path.into(),
+            dst_path: dst_path.into(),
+            mode,
+            pair_index: Some(pair_index),
+        }
+    }
+
+    #[test]
+    fn write_read() {
+        let records = vec![record("/a", "/b%", false), record("/b", "/c", true)];
+        let mut output = Vec::new();
+        let mut writer = JournalWriter::new(&mut output, "\n", records.clone());
+
+        writer.write(1).unwrap();
+        writer.write(0).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output.clone()).unwrap(),
+            "!\n</c\n>/b\n</b%\n>/a\n"
+        );
+        assert_eq!(
+            read_journal(&output[..], Terminator::Newline { required: true }).unwrap(),
+            vec![records[1].clone(), records[0].clone()]
+        );
+    }
+
+    #[test_case("!",         ErrorKind::UnexpectedEof, "Expected '<' (item #2 at offset 1)"               ; "marker only")]
+    #[test_case("!\n!\n</b", ErrorKind::InvalidData,   "Expected '<' but got '!' (item #2 at offset 2)" ; "marker repeated")]
+    #[test_case("</b\n!\n",  ErrorKind::InvalidData,   "Expected '>' but got '!' (item #2 at offset 4)" ; "marker between")]
+    fn read_journal_err(input: &str, kind: ErrorKind, message: &str) {
+        assert_eq!(
+            read_journal(input.as_bytes(), Terminator::Newline { required: false })
+                .map_err(unpack_io_error),
+            Err((kind, message.into()))
+        );
+    }
+
+    #[test_case(TransferMode::Move, &[("/b", "/a")] ; "move ")]
+    #[test_case(TransferMode::Copy, &[]             ; "copy")]
+    fn plan_undo_overwrite(mode: TransferMode, copied_paths: &[(&str, &str)]) {
+        let undo = plan_undo(&[record("/a", "/b", true)], mode, Path::new("/")).unwrap();
+
+        assert_eq!(
+            undo,
+            Undo {
+                steps: copied_paths
+                    .iter()
+                    .map(|(src_path, dst_path)| step(src_path, dst_path, TransferMode::Copy, 0))
+                    .collect(),
+                removed_paths: Vec::new(),
+                kept_paths: vec!["/b".into()],
+            }
+        );
+    }
+
+    #[test_case(TransferMode::Move ; "move ")]
+    #[test_case(TransferMode::Copy ; "copy")]
+    fn plan_undo_chain(mode: TransferMode) {
+        let records = [record("/b", "/c", false), record("/a", "/b", true)];
+        let undo = plan_undo(&records, mode, Path::new("/")).unwrap();
+
+        let mut steps = vec![step("/c", "/b", TransferMode::Move, 0)];
+        if mode == TransferMode::Move {
+            steps.insert(0, step("/b", "/a", TransferMode::Move, 1));
+        }
+
+        assert_eq!(
+            undo,
+            Undo {
+                steps,
+                removed_paths: Vec::new(),
+                kept_paths: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn plan_undo_copies() {
+        let records = [record("/a", "/b", false), record("/a", "/b/c", false)];
+        let undo = plan_undo(&records, TransferMode::Copy, Path::new("/")).unwrap();
+
+        assert_eq!(
+            undo,
+            Undo {
+                steps: Vec::new(),
+                removed_paths: vec!["/b/c".into(), "/b".into()],
+                kept_paths: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn plan_undo_copied_cycle() {
+        let records = [
+            record("/a", "/c", false),
+            record("/a", "/b", true),
+            record("/b", "/a", true),
+        ];
+        let undo = plan_undo(&records, TransferMode::Copy, Path::new("/")).unwrap();
+
+        assert_eq!(
+            undo,
+            Undo {
+                steps: vec![
+                    step("/a", "/b", TransferMode::Move, 1),
+                    step("/c", "/a", TransferMode::Move, 0),
+                ],
+                removed_paths: Vec::new(),
+                kept_paths: Vec::new(),
+            }
+        );
+    }
+
+    #[test_case(TransferMode::Move ; "move ")]
+    #[test_case(TransferMode::Copy ; "copy")]
+    fn plan_undo_cycle(mode: TransferMode) {
+        let records = [record("/a", "/b", true), record("/b", "/a", true)];
+        let undo = plan_undo(&records, mode, Path::new("/")).unwrap();
+
+        assert_eq!(undo.removed_paths, Vec::<PathBuf>::new());
+        assert_eq!(undo.kept_paths, Vec::<PathBuf>::new());
+        assert_eq!(undo.steps.len(), 3);
+        assert_eq!(undo.steps[0].src_path, Path::new("/b"));
+        assert_eq!(undo.steps[0].pair_index, None);
+        assert_eq!(undo.steps[1], step("/a", "/b", TransferMode::Move, 1));
+        assert_eq!(undo.steps[2].dst_path, Path::new("/a"));
+        assert_eq!(undo.steps[2].pair_index, Some(0));
+    }
+}
@@ -1,9 +1,25 @@
 pub use fs::TransferMode;
+pub use input::{parse_input_format, InputFormat, INPUT_FORMATS};
+pub use policy::{
+    parse_backup_mode, parse_preserve_attrs, BackupMode, PreserveAttrs, BACKUP_MODES,
+    PRESERVE_ATTRS,
+};
+pub use report::{parse_report_format, ReportFormat, REPORT_FORMATS};
 pub use run::{run_transfer, TransferOptions};
+pub use trash::Trash;
 
 mod fs;
 mod input;
+mod journal;
 mod output;
+mod parallel;
+mod plan;
+mod policy;
+mod progress;
+mod report;
+mod resume;
 mod run;
+mod signal;
 #[cfg(test)]
 mod testing;
+mod trash;
@@ -1,10 +1,20 @@
+use std::fs;
 use std::io::{Result, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+use fs_extra::error::{Error as TransferError, ErrorKind as TransferErrorKind};
 use termcolor::{Color, WriteColor};
 
 use crate::color::spec_color;
-use crate::transfer::fs::TransferMode;
+use crate::encoding::os_str_bytes;
+use crate::symbols::{DIFF_IN, DIFF_OUT};
+use crate::transfer::fs::{
+    check_file_types, find_missing_dirs, is_newer, is_same_entry, make_temp_path, remove_path,
+    select_action, transfer_path, transfer_path_with_progress, FileType, TransferAction,
+    TransferMode, TransferProgress,
+};
+use crate::transfer::policy::{make_backup_path, OverwritePolicy, TransferPolicy};
+use crate::transfer::trash::{move_to_trash, Trash};
 
 pub struct TransferLog<O: Write + WriteColor> {
     output: O,
@@ -24,7 +34,58 @@ impl<O: Write + WriteColor> TransferLog<O> {
         let action = match mode {
             TransferMode::Move => "Moving",
             TransferMode::Copy => "Copying",
+            TransferMode::HardLink => "Linking",
+            TransferMode::Symlink | TransferMode::RelativeSymlink => "Symlinking",
+            TransferMode::Reflink => "Cloning",
         };
+        self.write_transfer(action, src_path, dst_path)?;
+        write!(self.output, " ... ")?;
+        self.output.flush()
+    }
+
+    pub fn begin_confirmation(
+        &mut self,
+        mode: TransferMode,
+        src_path: &Path,
+        dst_path: &Path,
+        staged: bool,
+        action: Option<&TransferAction>,
+    ) -> Result<()> {
+        let mode_action = match mode {
+            TransferMode::Move => "Move",
+            TransferMode::Copy => "Copy",
+            TransferMode::HardLink => "Link",
+            TransferMode::Symlink | TransferMode::RelativeSymlink => "Symlink",
+            TransferMode::Reflink => "Clone",
+        };
+        self.write_transfer(mode_action, src_path, dst_path)?;
+
+        let notes = [
+            Some("staged").filter(|_| staged),
+            match action {
+                Some(TransferAction::Create) => None,
+                Some(TransferAction::Overwrite) => Some("overwrite"),
+                Some(TransferAction::MergeDir) => Some("merge"),
+                Some(TransferAction::Skip) => Some("skip"),
+                None => Some("error"),
+            },
+        ];
+
+        for (index, note) in notes.iter().flatten().enumerate() {
+            write!(self.output, "{}", if index > 0 { ", " } else { " (" })?;
+            self.output.set_color(&spec_color(Color::Yellow))?;
+            write!(self.output, "{}", note)?;
+            self.output.reset()?;
+        }
+        if notes.iter().any(Option::is_some) {
+            write!(self.output, ")")?;
+        }
+
+        write!(self.output, "? [y]es, [n]o, [a]ll, [q]uit: ")?;
+        self.output.flush()
+    }
+
+    fn write_transfer(&mut self, action: &str, src_path: &Path, dst_path: &Path) -> Result<()> {
         write!(self.output, "{} '", action)?;
         self.output.set_color(&spec_color(Color::Blue))?;
         write!(self.output, "{}", src_path.to_string_lossy())?;
@@ -33,10 +94,77 @@ impl<O: Write + WriteColor> TransferLog<O> {
         self.output.set_color(&spec_color(Color::Blue))?;
         write!(self.output, "{}", dst_path.to_string_lossy())?;
         self.output.reset()?;
+        write!(self.output, "'")
+    }
+
+    pub fn begin_removal(&mut self, path: &Path) -> Result<()> {
+        write!(self.output, "Removing '")?;
+        self.output.set_color(&spec_color(Color::Blue))?;
+        write!(self.output, "{}", path.to_string_lossy())?;
+        self.output.reset()?;
+        write!(self.output, "' ... ")?;
+        self.output.flush()
+    }
+
+    pub fn begin_dir_creation(&mut self, path: &Path) -> Result<()> {
+        write!(self.output, "Creating directory '")?;
+        self.output.set_color(&spec_color(Color::Blue))?;
+        write!(self.output, "{}", path.to_string_lossy())?;
+        self.output.reset()?;
+        write!(self.output, "' ... ")?;
+        self.output.flush()
+    }
+
+    pub fn begin_revert(&mut self, entry: &JournalEntry) -> Result<()> {
+        let action = match entry.mode {
+            TransferMode::Move => "move",
+            TransferMode::Copy => "copy",
+            TransferMode::HardLink => "link",
+            TransferMode::Symlink | TransferMode::RelativeSymlink => "symlink",
+            TransferMode::Reflink => "clone",
+        };
+        write!(self.output, "Reverting {} of '", action)?;
+        self.output.set_color(&spec_color(Color::Blue))?;
+        write!(self.output, "{}", entry.src_path.to_string_lossy())?;
+        self.output.reset()?;
+        write!(self.output, "' to '")?;
+        self.output.set_color(&spec_color(Color::Blue))?;
+        write!(self.output, "{}", entry.dst_path.to_string_lossy())?;
+        self.output.reset()?;
         write!(self.output, "' ... ")?;
         self.output.flush()
     }
 
+    pub fn write_rollback_result(&mut self, reverted: usize, total: usize) -> Result<()> {
+        write!(
+            self.output,
+            "Rolled back {} of {} completed transfer(s) ... ",
+            reverted, total
+        )?;
+        if reverted == total {
+            self.end_with_success()
+        } else {
+            self.end_with_failure()
+        }
+    }
+
+    pub fn write_interrupt_result(
+        &mut self,
+        completed: usize,
+        skipped: usize,
+        failed: usize,
+        pending: usize,
+    ) -> Result<()> {
+        self.output.set_color(&spec_color(Color::Yellow))?;
+        write!(self.output, "Interrupted")?;
+        self.output.reset()?;
+        writeln!(
+            self.output,
+            " with {} completed, {} skipped, {} failed and {} pending instruction(s)",
+            completed, skipped, failed, pending
+        )
+    }
+
     pub fn end_with_success(&mut self) -> Result<()> {
         self.end_transfer(Color::Green, "OK")
     }
@@ -45,6 +173,28 @@ impl<O: Write + WriteColor> TransferLog<O> {
         self.end_transfer(Color::Red, "FAILED")
     }
 
+    pub fn end_with_action(&mut self, mode: TransferMode, action: &TransferAction) -> Result<()> {
+        self.end_with_plan(match (action, mode) {
+            (TransferAction::Create, TransferMode::Move) => "would-move",
+            (TransferAction::Create, TransferMode::Copy) => "would-copy",
+            (TransferAction::Create, TransferMode::HardLink) => "would-link",
+            (TransferAction::Create, TransferMode::Symlink) => "would-symlink",
+            (TransferAction::Create, TransferMode::RelativeSymlink) => "would-symlink",
+            (TransferAction::Create, TransferMode::Reflink) => "would-clone",
+            (TransferAction::Overwrite, _) => "would-overwrite",
+            (TransferAction::MergeDir, _) => "would-merge-dir",
+            (TransferAction::Skip, _) => "would-skip",
+        })
+    }
+
+    pub fn end_with_plan(&mut self, plan: &str) -> Result<()> {
+        self.end_transfer(Color::Yellow, plan)
+    }
+
+    pub fn end_with_error(&mut self) -> Result<()> {
+        self.end_transfer(Color::Red, "error")
+    }
+
     pub fn end_transfer(&mut self, color: Color, result: &str) -> Result<()> {
         self.output.set_color(&spec_color(color))?;
         write!(self.output, "{}", result)?;
@@ -53,6 +203,172 @@ impl<O: Write + WriteColor> TransferLog<O> {
     }
 }
 
+pub struct PathDiffWriter<O: Write> {
+    output: O,
+    terminator: String,
+}
+
+impl<O: Write> PathDiffWriter<O> {
+    pub fn new(output: O, terminator: &str) -> Self {
+        Self {
+            output,
+            terminator: terminator.into(),
+        }
+    }
+
+    pub fn write_marker(&mut self, marker: char) -> Result<()> {
+        write!(self.output, "{}{}", marker, self.terminator)
+    }
+
+    pub fn write(&mut self, src_path: &Path, dst_path: &Path) -> Result<()> {
+        // Paths are written as raw bytes, so they can be read back without any loss.
+        write!(self.output, "{}", DIFF_IN)?;
+        self.output.write_all(&os_str_bytes(src_path.as_os_str()))?;
+        write!(self.output, "{}{}", self.terminator, DIFF_OUT)?;
+        self.output.write_all(&os_str_bytes(dst_path.as_os_str()))?;
+        write!(self.output, "{}", self.terminator)?;
+        self.output.flush()
+    }
+}
+
+pub struct JournalEntry {
+    src_path: PathBuf,
+    dst_path: PathBuf,
+    mode: TransferMode,
+    backup_path: Option<PathBuf>,
+    keep_backup: bool,
+    trash: Option<Trash>,
+    created_dirs: Vec<PathBuf>,
+}
+
+impl JournalEntry {
+    pub fn revert(&self) -> fs_extra::error::Result<()> {
+        match self.mode {
+            TransferMode::Move => {
+                let policy = TransferPolicy::default();
+                transfer_path(&self.dst_path, &self.src_path, TransferMode::Move, &policy)?;
+            }
+            _ => remove_path(&self.dst_path)?,
+        }
+
+        if let Some(backup_path) = &self.backup_path {
+            fs::rename(backup_path, &self.dst_path)?;
+        }
+
+        for dir in self.created_dirs.iter().rev() {
+            fs::remove_dir(dir)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn commit(&self) -> fs_extra::error::Result<()> {
+        if let Some(backup_path) = self.backup_path.as_ref().filter(|_| !self.keep_backup) {
+            if let Some(trash) = &self.trash {
+                // Trash should refer to the original path, not to the temporary one.
+                move_to_trash(backup_path, &self.dst_path, trash)?;
+            } else {
+                remove_path(backup_path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct TransferJournal {
+    entries: Vec<JournalEntry>,
+}
+
+impl TransferJournal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn transfer(
+        &mut self,
+        src_path: &Path,
+        dst_path: &Path,
+        mode: TransferMode,
+        policy: &TransferPolicy,
+        progress: Option<&mut dyn TransferProgress>,
+    ) -> fs_extra::error::Result<TransferAction> {
+        let src_type = FileType::resolve(src_path, policy.dereference);
+        let dst_type = FileType::from(dst_path);
+
+        check_file_types(src_path, src_type, dst_path, dst_type)?;
+
+        let action = if dst_type == FileType::Unknown {
+            TransferAction::Create
+        } else if is_same_entry(src_path, dst_path, policy.dereference)? {
+            // Nothing to record, transfer_path does nothing.
+            return transfer_path_with_progress(src_path, dst_path, mode, policy, progress);
+        } else {
+            select_action(src_path, dst_path, dst_type, policy, || {
+                is_newer(src_path, dst_path, policy.dereference)
+            })?
+        };
+
+        let (backup_path, keep_backup) = match action {
+            TransferAction::Create => (None, false),
+            TransferAction::Skip => return Ok(action),
+            TransferAction::MergeDir => {
+                return Err(TransferError::new(
+                    TransferErrorKind::Other,
+                    &format!(
+                        "Cannot merge directory '{}' into existing directory '{}' in atomic mode",
+                        src_path.to_string_lossy(),
+                        dst_path.to_string_lossy()
+                    ),
+                ))
+            }
+            TransferAction::Overwrite => {
+                let (backup_path, keep_backup) = match &policy.overwrite {
+                    OverwritePolicy::Backup(backup_mode) => {
+                        (make_backup_path(dst_path, *backup_mode), true)
+                    }
+                    _ => (make_temp_path(dst_path), false),
+                };
+                fs::rename(dst_path, &backup_path)?;
+                (Some(backup_path), keep_backup)
+            }
+        };
+
+        let created_dirs = find_missing_dirs(dst_path);
+
+        match transfer_path_with_progress(src_path, dst_path, mode, policy, progress) {
+            Ok(_) => {
+                self.entries.push(JournalEntry {
+                    src_path: src_path.into(),
+                    dst_path: dst_path.into(),
+                    mode,
+                    backup_path,
+                    keep_backup,
+                    trash: match &policy.overwrite {
+                        OverwritePolicy::Trash(trash) => Some(trash.clone()),
+                        _ => None,
+                    },
+                    created_dirs,
+                });
+                Ok(action)
+            }
+            Err(error) => {
+                if let Some(backup_path) = backup_path {
+                    fs::rename(backup_path, dst_path)?;
+                }
+                for dir in created_dirs.iter().rev() {
+                    fs::remove_dir(dir).unwrap_or_default(); // Might not be empty after a partial transfer
+                }
+                Err(error)
+            }
+        }
+    }
+
+    pub fn into_entries(self) -> Vec<JournalEntry> {
+        self.entries
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use test_case::test_case;
@@ -60,8 +376,12 @@ pub mod tests {
     use super::*;
     use crate::testing::{ColoredOuput, OutputChunk};
 
-    #[test_case(TransferMode::Move, "Moving"  ; "move ")]
-    #[test_case(TransferMode::Copy, "Copying" ; "copy")]
+    #[test_case(TransferMode::Move,            "Moving"     ; "move ")]
+    #[test_case(TransferMode::Copy,            "Copying"    ; "copy")]
+    #[test_case(TransferMode::HardLink,        "Linking"    ; "hard link")]
+    #[test_case(TransferMode::Symlink,         "Symlinking" ; "symlink")]
+    #[test_case(TransferMode::RelativeSymlink, "Symlinking" ; "relative symlink")]
+    #[test_case(TransferMode::Reflink,         "Cloning"    ; "reflink")]
     fn begin_transfer(mode: TransferMode, output_action: &str) {
         let mut output = ColoredOuput::new();
 
@@ -81,6 +401,90 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn begin_confirmation() {
+        let mut output = ColoredOuput::new();
+
+        TransferLog::new(&mut output)
+            .begin_confirmation(
+                TransferMode::Move,
+                Path::new("a/b.c"),
+                Path::new("d/e.f"),
+                false,
+                Some(&TransferAction::Create),
+            )
+            .unwrap();
+
+        assert_eq!(
+            output.chunks(),
+            &[
+                OutputChunk::plain("Move '"),
+                OutputChunk::color(Color::Blue, "a/b.c"),
+                OutputChunk::plain("' to '"),
+                OutputChunk::color(Color::Blue, "d/e.f"),
+                OutputChunk::plain("'? [y]es, [n]o, [a]ll, [q]uit: ")
+            ]
+        );
+    }
+
+    #[test]
+    fn begin_confirmation_of_overwrite() {
+        let mut output = ColoredOuput::new();
+
+        TransferLog::new(&mut output)
+            .begin_confirmation(
+                TransferMode::Copy,
+                Path::new("a/b.c"),
+                Path::new("d/e.f"),
+                false,
+                Some(&TransferAction::Overwrite),
+            )
+            .unwrap();
+
+        assert_eq!(
+            output.chunks(),
+            &[
+                OutputChunk::plain("Copy '"),
+                OutputChunk::color(Color::Blue, "a/b.c"),
+                OutputChunk::plain("' to '"),
+                OutputChunk::color(Color::Blue, "d/e.f"),
+                OutputChunk::plain("' ("),
+                OutputChunk::color(Color::Yellow, "overwrite"),
+                OutputChunk::plain(")? [y]es, [n]o, [a]ll, [q]uit: ")
+            ]
+        );
+    }
+
+    #[test]
+    fn begin_confirmation_of_staged_merge() {
+        let mut output = ColoredOuput::new();
+
+        TransferLog::new(&mut output)
+            .begin_confirmation(
+                TransferMode::Move,
+                Path::new("a/b.c"),
+                Path::new("d/e.f"),
+                true,
+                Some(&TransferAction::MergeDir),
+            )
+            .unwrap();
+
+        assert_eq!(
+            output.chunks(),
+            &[
+                OutputChunk::plain("Move '"),
+                OutputChunk::color(Color::Blue, "a/b.c"),
+                OutputChunk::plain("' to '"),
+                OutputChunk::color(Color::Blue, "d/e.f"),
+                OutputChunk::plain("' ("),
+                OutputChunk::color(Color::Yellow, "staged"),
+                OutputChunk::plain(", "),
+                OutputChunk::color(Color::Yellow, "merge"),
+                OutputChunk::plain(")? [y]es, [n]o, [a]ll, [q]uit: ")
+            ]
+        );
+    }
+
     #[test]
     fn end_with_success() {
         let mut output = ColoredOuput::new();
@@ -108,4 +512,379 @@ pub mod tests {
             ]
         );
     }
+
+    #[test_case(TransferMode::Move,            TransferAction::Create,    "would-move"      ; "create move")]
+    #[test_case(TransferMode::Copy,            TransferAction::Create,    "would-copy"      ; "create copy")]
+    #[test_case(TransferMode::HardLink,        TransferAction::Create,    "would-link"      ; "create hard link")]
+    #[test_case(TransferMode::Symlink,         TransferAction::Create,    "would-symlink"   ; "create symlink")]
+    #[test_case(TransferMode::RelativeSymlink, TransferAction::Create,    "would-symlink"   ; "create relative symlink")]
+    #[test_case(TransferMode::Reflink,         TransferAction::Create,    "would-clone"     ; "create reflink")]
+    #[test_case(TransferMode::Move,            TransferAction::Overwrite, "would-overwrite" ; "overwrite")]
+    #[test_case(TransferMode::Copy,            TransferAction::MergeDir,  "would-merge-dir" ; "merge dir")]
+    #[test_case(TransferMode::Move,            TransferAction::Skip,      "would-skip"      ; "skip")]
+    fn end_with_action(mode: TransferMode, action: TransferAction, result: &str) {
+        let mut output = ColoredOuput::new();
+        TransferLog::new(&mut output)
+            .end_with_action(mode, &action)
+            .unwrap();
+
+        assert_eq!(
+            output.chunks(),
+            &[
+                OutputChunk::color(Color::Yellow, result),
+                OutputChunk::plain("\n")
+            ]
+        );
+    }
+
+    #[test]
+    fn end_with_error() {
+        let mut output = ColoredOuput::new();
+        TransferLog::new(&mut output).end_with_error().unwrap();
+
+        assert_eq!(
+            output.chunks(),
+            &[
+                OutputChunk::color(Color::Red, "error"),
+                OutputChunk::plain("\n")
+            ]
+        );
+    }
+
+    #[test]
+    fn begin_dir_creation() {
+        let mut output = ColoredOuput::new();
+        TransferLog::new(&mut output)
+            .begin_dir_creation(Path::new("a/b"))
+            .unwrap();
+
+        assert_eq!(
+            output.chunks(),
+            &[
+                OutputChunk::plain("Creating directory '"),
+                OutputChunk::color(Color::Blue, "a/b"),
+                OutputChunk::plain("' ... ")
+            ]
+        );
+    }
+
+    #[test]
+    fn begin_removal() {
+        let mut output = ColoredOuput::new();
+        TransferLog::new(&mut output)
+            .begin_removal(Path::new("a/b.c"))
+            .unwrap();
+
+        assert_eq!(
+            output.chunks(),
+            &[
+                OutputChunk::plain("Removing '"),
+                OutputChunk::color(Color::Blue, "a/b.c"),
+                OutputChunk::plain("' ... ")
+            ]
+        );
+    }
+
+    #[test_case(TransferMode::Move,            "move"    ; "move ")]
+    #[test_case(TransferMode::Copy,            "copy"    ; "copy")]
+    #[test_case(TransferMode::HardLink,        "link"    ; "hard link")]
+    #[test_case(TransferMode::Symlink,         "symlink" ; "symlink")]
+    #[test_case(TransferMode::RelativeSymlink, "symlink" ; "relative symlink")]
+    #[test_case(TransferMode::Reflink,         "clone"   ; "reflink")]
+    fn begin_revert(mode: TransferMode, output_action: &str) {
+        let mut output = ColoredOuput::new();
+        let entry = JournalEntry {
+            src_path: "a/b.c".into(),
+            dst_path: "d/e.f".into(),
+            mode,
+            backup_path: None,
+            keep_backup: false,
+            trash: None,
+            created_dirs: Vec::new(),
+        };
+
+        TransferLog::new(&mut output).begin_revert(&entry).unwrap();
+
+        assert_eq!(
+            output.chunks(),
+            &[
+                OutputChunk::plain(&format!("Reverting {} of '", output_action)),
+                OutputChunk::color(Color::Blue, "a/b.c"),
+                OutputChunk::plain("' to '"),
+                OutputChunk::color(Color::Blue, "d/e.f"),
+                OutputChunk::plain("' ... ")
+            ]
+        );
+    }
+
+    #[test_case(2, 2, OutputChunk::color(Color::Green, "OK")   ; "success")]
+    #[test_case(1, 2, OutputChunk::color(Color::Red, "FAILED") ; "failure")]
+    fn write_rollback_result(reverted: usize, total: usize, result: OutputChunk) {
+        let mut output = ColoredOuput::new();
+        TransferLog::new(&mut output)
+            .write_rollback_result(reverted, total)
+            .unwrap();
+
+        assert_eq!(
+            output.chunks(),
+            &[
+                OutputChunk::plain(&format!(
+                    "Rolled back {} of {} completed transfer(s) ... ",
+                    reverted, total
+                )),
+                result,
+                OutputChunk::plain("\n")
+            ]
+        );
+    }
+
+    #[test]
+    fn write_interrupt_result() {
+        let mut output = ColoredOuput::new();
+        TransferLog::new(&mut output)
+            .write_interrupt_result(1, 2, 3, 4)
+            .unwrap();
+
+        assert_eq!(
+            output.chunks(),
+            &[
+                OutputChunk::color(Color::Yellow, "Interrupted"),
+                OutputChunk::plain(
+                    " with 1 completed, 2 skipped, 3 failed and 4 pending instruction(s)\n"
+                ),
+            ]
+        );
+    }
+
+    #[test_case("\n", "<a\n>b\n<c\n>d\n" ; "newline")]
+    #[test_case("\0", "<a\0>b\0<c\0>d\0" ; "null")]
+    fn path_diff_writer(terminator: &str, result: &str) {
+        let mut output = Vec::new();
+        let mut writer = PathDiffWriter::new(&mut output, terminator);
+        writer.write(Path::new("a"), Path::new("b")).unwrap();
+        writer.write(Path::new("c"), Path::new("d")).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), result);
+    }
+
+    mod transfer_journal {
+        use assert_fs::prelude::*;
+        use assert_fs::TempDir;
+        use test_case::test_case;
+
+        use super::*;
+        use crate::transfer::policy::BackupMode;
+
+        #[test_case(TransferMode::Move ; "move ")]
+        #[test_case(TransferMode::Copy ; "copy")]
+        fn revert(mode: TransferMode) {
+            let dir = TempDir::new().unwrap();
+            let src_file = dir.child("a");
+            let dst_file = dir.child("b/c/d");
+            src_file.write_str("1").unwrap();
+
+            let mut journal = TransferJournal::new();
+            journal
+                .transfer(
+                    src_file.path(),
+                    dst_file.path(),
+                    mode,
+                    &TransferPolicy::default(),
+                    None,
+                )
+                .unwrap();
+            dst_file.assert("1");
+
+            for entry in journal.into_entries() {
+                entry.revert().unwrap();
+            }
+
+            src_file.assert("1");
+            dir.child("b").assert(predicates::path::missing());
+        }
+
+        #[test]
+        fn revert_overwrite() {
+            let dir = TempDir::new().unwrap();
+            let src_file = dir.child("a");
+            let dst_file = dir.child("b");
+            src_file.write_str("1").unwrap();
+            dst_file.write_str("2").unwrap();
+
+            let mut journal = TransferJournal::new();
+            journal
+                .transfer(
+                    src_file.path(),
+                    dst_file.path(),
+                    TransferMode::Move,
+                    &TransferPolicy::default(),
+                    None,
+                )
+                .unwrap();
+
+            src_file.assert(predicates::path::missing());
+            dst_file.assert("1");
+
+            for entry in journal.into_entries() {
+                entry.revert().unwrap();
+            }
+
+            src_file.assert("1");
+            dst_file.assert("2");
+            assert_eq!(dir.path().read_dir().unwrap().count(), 2);
+        }
+
+        #[test]
+        fn commit_overwrite() {
+            let dir = TempDir::new().unwrap();
+            let src_file = dir.child("a");
+            let dst_file = dir.child("b");
+            src_file.write_str("1").unwrap();
+            dst_file.write_str("2").unwrap();
+
+            let mut journal = TransferJournal::new();
+            journal
+                .transfer(
+                    src_file.path(),
+                    dst_file.path(),
+                    TransferMode::Copy,
+                    &TransferPolicy::default(),
+                    None,
+                )
+                .unwrap();
+
+            for entry in journal.into_entries() {
+                entry.commit().unwrap();
+            }
+
+            src_file.assert("1");
+            dst_file.assert("1");
+            assert_eq!(dir.path().read_dir().unwrap().count(), 2);
+        }
+
+        #[test]
+        fn commit_backup() {
+            let dir = TempDir::new().unwrap();
+            let src_file = dir.child("a");
+            let dst_file = dir.child("b");
+            src_file.write_str("1").unwrap();
+            dst_file.write_str("2").unwrap();
+
+            let policy = TransferPolicy {
+                overwrite: OverwritePolicy::Backup(BackupMode::Suffix),
+                ..TransferPolicy::default()
+            };
+
+            let mut journal = TransferJournal::new();
+            journal
+                .transfer(
+                    src_file.path(),
+                    dst_file.path(),
+                    TransferMode::Copy,
+                    &policy,
+                    None,
+                )
+                .unwrap();
+
+            for entry in journal.into_entries() {
+                entry.commit().unwrap();
+            }
+
+            src_file.assert("1");
+            dst_file.assert("1");
+            dir.child("b~").assert("2");
+        }
+
+        #[test]
+        fn commit_trash() {
+            let dir = TempDir::new().unwrap();
+            let src_file = dir.child("a");
+            let dst_file = dir.child("b");
+            let trash_dir = dir.child("trash");
+            src_file.write_str("1").unwrap();
+            dst_file.write_str("2").unwrap();
+
+            let policy = TransferPolicy {
+                overwrite: OverwritePolicy::Trash(Trash::Dir(trash_dir.path().into())),
+                ..TransferPolicy::default()
+            };
+
+            let mut journal = TransferJournal::new();
+            journal
+                .transfer(
+                    src_file.path(),
+                    dst_file.path(),
+                    TransferMode::Copy,
+                    &policy,
+                    None,
+                )
+                .unwrap();
+
+            for entry in journal.into_entries() {
+                entry.commit().unwrap();
+            }
+
+            src_file.assert("1");
+            dst_file.assert("1");
+            trash_dir.child("b").assert("2");
+            assert_eq!(dir.path().read_dir().unwrap().count(), 3);
+        }
+
+        #[test]
+        fn skip_existing() {
+            let dir = TempDir::new().unwrap();
+            let src_file = dir.child("a");
+            let dst_file = dir.child("b");
+            src_file.write_str("1").unwrap();
+            dst_file.write_str("2").unwrap();
+
+            let policy = TransferPolicy {
+                overwrite: OverwritePolicy::Skip,
+                ..TransferPolicy::default()
+            };
+
+            let mut journal = TransferJournal::new();
+            journal
+                .transfer(
+                    src_file.path(),
+                    dst_file.path(),
+                    TransferMode::Move,
+                    &policy,
+                    None,
+                )
+                .unwrap();
+
+            src_file.assert("1");
+            dst_file.assert("2");
+            assert_eq!(journal.into_entries().len(), 0);
+        }
+
+        #[test]
+        fn merge_dir() {
+            let dir = TempDir::new().unwrap();
+            let src_dir = dir.child("a");
+            let dst_dir = dir.child("b");
+            src_dir.create_dir_all().unwrap();
+            dst_dir.create_dir_all().unwrap();
+
+            assert_eq!(
+                TransferJournal::new()
+                    .transfer(
+                        src_dir.path(),
+                        dst_dir.path(),
+                        TransferMode::Move,
+                        &TransferPolicy::default(),
+                        None
+                    )
+                    .map_err(|error| error.to_string()),
+                Err(format!(
+                    "Cannot merge directory '{}' into existing directory '{}' in atomic mode",
+                    src_dir.path().to_string_lossy(),
+                    dst_dir.path().to_string_lossy()
+                ))
+            );
+
+            src_dir.assert(predicates::path::is_dir());
+            dst_dir.assert(predicates::path::is_dir());
+        }
+    }
 }
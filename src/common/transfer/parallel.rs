@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::Scope;
+
+use fs_extra::error::Result;
+
+use crate::transfer::fs::{
+    normalize_path, transfer_path_with_progress, TransferAction, TransferProgress,
+};
+use crate::transfer::plan::Step;
+use crate::transfer::policy::TransferPolicy;
+use crate::transfer::progress::CopiedSize;
+
+pub struct StepResult {
+    pub result: Result<TransferAction>,
+    pub copied_size: u64,
+}
+
+pub struct TransferPool {
+    jobs: usize,
+    step_paths: Vec<[PathBuf; 2]>,
+    task_sender: Sender<usize>,
+    result_receiver: Receiver<(usize, StepResult)>,
+    running: Vec<usize>,
+    finished: HashMap<usize, StepResult>,
+    next_dispatched: usize,
+    next_finished: usize,
+}
+
+impl TransferPool {
+    pub fn new<'scope, 'env>(
+        scope: &'scope Scope<'scope, 'env>,
+        steps: &'env [Step],
+        jobs: usize,
+        policy: &'env TransferPolicy,
+        working_dir: &Path,
+        count_copied_size: bool,
+    ) -> Self {
+        let (task_sender, task_receiver) = channel::<usize>();
+        let (result_sender, result_receiver) = channel();
+        let task_receiver = Arc::new(Mutex::new(task_receiver));
+
+        for _ in 0..jobs {
+            let task_receiver = Arc::clone(&task_receiver);
+            let result_sender = result_sender.clone();
+
+            scope.spawn(move || loop {
+                // The lock has to be released before the transfer starts.
+                let task = task_receiver.lock().unwrap().recv();
+                let index = match task {
+                    Ok(index) => index,
+                    Err(_) => break, // The pool was dropped.
+                };
+
+                let step = &steps[index];
+                let mut copied_size = CopiedSize::default();
+                let progress = if count_copied_size {
+                    Some(&mut copied_size as &mut dyn TransferProgress)
+                } else {
+                    // Allows faster copying without progress tracking.
+                    None
+                };
+                let result = transfer_path_with_progress(
+                    &step.src_path,
+                    &step.dst_path,
+                    step.mode,
+                    policy,
+                    progress,
+                );
+                let step_result = StepResult {
+                    result,
+                    copied_size: copied_size.get(),
+                };
+
+                if result_sender.send((index, step_result)).is_err() {
+                    break;
+                }
+            });
+        }
+
+        let step_paths = steps
+            .iter()
+            .map(|step| {
+                [
+                    normalize_path(&step.src_path, working_dir),
+                    normalize_path(&step.dst_path, working_dir),
+                ]
+            })
+            .collect();
+
+        Self {
+            jobs,
+            step_paths,
+            task_sender,
+            result_receiver,
+            running: Vec::new(),
+            finished: HashMap::new(),
+            next_dispatched: 0,
+            next_finished: 0,
+        }
+    }
+
+    /// Returns results in the same order as steps.
+    /// New steps are started only when `dispatch` is true.
+    pub fn next(&mut self, dispatch: bool) -> Option<(usize, StepResult)> {
+        loop {
+            if let Some(step_result) = self.finished.remove(&self.next_finished) {
+                let index = self.next_finished;
+                self.next_finished += 1;
+                return Some((index, step_result));
+            }
+
+            if dispatch {
+                self.dispatch();
+            }
+
+            if self.running.is_empty() {
+                return None;
+            }
+
+            let (index, step_result) = self
+                .result_receiver
+                .recv()
+                .expect("Expected result of a running step");
+
+            self.running.retain(|running_index| *running_index != index);
+            self.finished.insert(index, step_result);
+        }
+    }
+
+    fn dispatch(&mut self) {
+        while self.next_dispatched < self.step_paths.len() && self.running.len() < self.jobs {
+            let paths = &self.step_paths[self.next_dispatched];
+
+            // Steps are started in order, so all preceding dependent steps are either finished or running.
+            if self
+                .running
+                .iter()
+                .any(|index| are_dependent(&self.step_paths[*index], paths))
+            {
+                break;
+            }
+
+            self.task_sender
+                .send(self.next_dispatched)
+                .expect("Expected running workers");
+            self.running.push(self.next_dispatched);
+            self.next_dispatched += 1;
+        }
+    }
+}
+
+fn are_dependent(paths: &[PathBuf; 2], other_paths: &[PathBuf; 2]) -> bool {
+    // Transfer of a path affects also its parent and child paths.
+    paths.iter().any(|path| {
+        other_paths
+            .iter()
+            .any(|other_path| path.starts_with(other_path) || other_path.starts_with(path))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::transfer::fs::TransferMode;
+
+    #[test_case("/a",   "/b",   "/c",   "/d",   false ; "independent")]
+    #[test_case("/a",   "/b",   "/b",   "/c",   true  ; "destination is source")]
+    #[test_case("/a",   "/b",   "/c",   "/a",   true  ; "source is destination")]
+    #[test_case("/a",   "/b",   "/c",   "/b",   true  ; "same destination")]
+    #[test_case("/a",   "/b",   "/c",   "/b/d", true  ; "destination is parent")]
+    #[test_case("/a",   "/b/c", "/b",   "/d",   true  ; "source is parent")]
+    #[test_case("/a",   "/b",   "/c",   "/bc",  false ; "common prefix")]
+    fn are_dependent(src_1: &str, dst_1: &str, src_2: &str, dst_2: &str, result: bool) {
+        let paths = [PathBuf::from(src_1), PathBuf::from(dst_1)];
+        let other_paths = [PathBuf::from(src_2), PathBuf::from(dst_2)];
+        assert_eq!(super::are_dependent(&paths, &other_paths), result);
+        assert_eq!(super::are_dependent(&other_paths, &paths), result);
+    }
+
+    #[test]
+    fn ordered_results() {
+        let dir = TempDir::new().unwrap();
+        let steps = ["a", "b", "c", "d"]
+            .iter()
+            .map(|name| {
+                dir.child(name).write_str(name).unwrap();
+                Step {
+                    src_path: dir.child(name).path().into(),
+                    dst_path: dir.child("e").child(name).path().into(),
+                    mode: TransferMode::Copy,
+                    pair_index: None,
+                }
+            })
+            .collect::<Vec<Step>>();
+        let policy = TransferPolicy::default();
+
+        let indices = thread::scope(|scope| {
+            let mut pool = TransferPool::new(scope, &steps, 3, &policy, dir.path(), true);
+            let mut indices = Vec::new();
+
+            while let Some((index, step_result)) = pool.next(true) {
+                assert_eq!(step_result.result.ok(), Some(TransferAction::Create));
+                assert_eq!(step_result.copied_size, 1);
+                indices.push(index);
+            }
+
+            indices
+        });
+
+        assert_eq!(indices, vec![0, 1, 2, 3]);
+        dir.child("e/a").assert("a");
+        dir.child("e/d").assert("d");
+    }
+
+    #[test]
+    fn dependent_steps() {
+        let dir = TempDir::new().unwrap();
+        dir.child("a").write_str("1").unwrap();
+        let steps = vec![
+            Step {
+                src_path: dir.child("a").path().into(),
+                dst_path: dir.child("b").path().into(),
+                mode: TransferMode::Move,
+                pair_index: None,
+            },
+            Step {
+                src_path: dir.child("b").path().into(),
+                dst_path: dir.child("c").path().into(),
+                mode: TransferMode::Move,
+                pair_index: None,
+            },
+        ];
+        let policy = TransferPolicy::default();
+
+        thread::scope(|scope| {
+            let mut pool = TransferPool::new(scope, &steps, 2, &policy, dir.path(), true);
+            while let Some((_, step_result)) = pool.next(true) {
+                assert!(step_result.result.is_ok());
+            }
+        });
+
+        dir.child("a").assert(predicates::path::missing());
+        dir.child("c").assert("1");
+    }
+
+    #[test]
+    fn stopped_dispatch() {
+        let dir = TempDir::new().unwrap();
+        dir.child("a").write_str("1").unwrap();
+        let steps = vec![Step {
+            src_path: dir.child("a").path().into(),
+            dst_path: dir.child("b").path().into(),
+            mode: TransferMode::Copy,
+            pair_index: None,
+        }];
+        let policy = TransferPolicy::default();
+
+        thread::scope(|scope| {
+            let mut pool = TransferPool::new(scope, &steps, 2, &policy, dir.path(), true);
+            assert!(pool.next(false).is_none());
+        });
+
+        dir.child("b").assert(predicates::path::missing());
+    }
+}
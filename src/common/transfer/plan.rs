@@ -0,0 +1,596 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::{error, fmt, result};
+
+use crate::transfer::fs::{make_temp_path, normalize_path, TransferMode};
+
+#[derive(PartialEq, Debug)]
+pub enum Conflict {
+    DuplicateDestination {
+        dst_path: PathBuf,
+        src_paths: Vec<PathBuf>,
+    },
+    PendingSource {
+        src_path: PathBuf,
+        dst_path: PathBuf,
+        next_dst_path: PathBuf,
+    },
+    LinkedSource {
+        src_path: PathBuf,
+        dst_path: PathBuf,
+        prev_dst_path: PathBuf,
+    },
+    Cycle {
+        paths: Vec<PathBuf>,
+    },
+}
+
+impl error::Error for Conflict {}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DuplicateDestination {
+                dst_path,
+                src_paths,
+            } => {
+                write!(formatter, "Paths ")?;
+                for (index, src_path) in src_paths.iter().enumerate() {
+                    if index > 0 {
+                        write!(formatter, ", ")?;
+                    }
+                    write!(formatter, "'{}'", src_path.to_string_lossy())?;
+                }
+                write!(
+                    formatter,
+                    " would be transferred to the same destination '{}'",
+                    dst_path.to_string_lossy()
+                )
+            }
+            Self::PendingSource {
+                src_path,
+                dst_path,
+                next_dst_path,
+            } => write!(
+                formatter,
+                "Path '{}' would be overwritten by '{}' before being transferred to '{}'",
+                dst_path.to_string_lossy(),
+                src_path.to_string_lossy(),
+                next_dst_path.to_string_lossy()
+            ),
+            Self::LinkedSource {
+                src_path,
+                dst_path,
+                prev_dst_path,
+            } => write!(
+                formatter,
+                "Path '{}' would be overwritten by '{}' after being linked to '{}'",
+                dst_path.to_string_lossy(),
+                src_path.to_string_lossy(),
+                prev_dst_path.to_string_lossy()
+            ),
+            Self::Cycle { paths } => {
+                write!(formatter, "Paths ")?;
+                for (index, path) in paths.iter().enumerate() {
+                    if index > 0 {
+                        write!(formatter, " -> ")?;
+                    }
+                    write!(formatter, "'{}'", path.to_string_lossy())?;
+                }
+                write!(formatter, " form a cycle")
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Step {
+    pub src_path: PathBuf,
+    pub dst_path: PathBuf,
+    pub mode: TransferMode,
+    // Only the last step of a staged pair (e.g. part of a cycle) completes it.
+    pub pair_index: Option<usize>,
+}
+
+pub type Result = result::Result<Vec<Step>, Vec<Conflict>>;
+
+pub fn plan_transfer(
+    pairs: &[(PathBuf, PathBuf)],
+    mode: TransferMode,
+    working_dir: &Path,
+) -> Result {
+    let src_keys = pairs
+        .iter()
+        .map(|(src_path, _)| normalize_path(src_path, working_dir))
+        .collect::<Vec<PathBuf>>();
+
+    let dst_keys = pairs
+        .iter()
+        .map(|(_, dst_path)| normalize_path(dst_path, working_dir))
+        .collect::<Vec<PathBuf>>();
+
+    // Pairs where source and destination are the same path do nothing.
+    let active = (0..pairs.len())
+        .map(|index| src_keys[index] != dst_keys[index])
+        .collect::<Vec<bool>>();
+
+    let mut conflicts = find_conflicts(pairs, &dst_keys, &active);
+
+    let mut src_indices: HashMap<&Path, Vec<usize>> = HashMap::new();
+    for index in (0..pairs.len()).filter(|index| active[*index]) {
+        src_indices.entry(&src_keys[index]).or_default().push(index);
+    }
+
+    // Edge `i -> j` means that destination of pair `i` is source of pair `j`.
+    // Pair `j` has to be executed before pair `i`, otherwise its source would be overwritten.
+    let mut successors = (0..pairs.len())
+        .map(|index| {
+            if active[index] {
+                src_indices
+                    .get(dst_keys[index].as_path())
+                    .cloned()
+                    .unwrap_or_default()
+            } else {
+                Vec::new()
+            }
+        })
+        .collect::<Vec<Vec<usize>>>();
+
+    if !can_reorder(mode) {
+        conflicts.extend(find_order_conflicts(pairs, &successors));
+    }
+
+    if !conflicts.is_empty() {
+        return Err(conflicts);
+    }
+
+    if !can_reorder(mode) {
+        // Pairs are executed in the given order which is known to be safe.
+        return Ok(pairs
+            .iter()
+            .enumerate()
+            .map(|(index, (src_path, dst_path))| Step {
+                src_path: src_path.clone(),
+                dst_path: dst_path.clone(),
+                mode,
+                pair_index: Some(index),
+            })
+            .collect());
+    }
+
+    // There are no duplicate destinations, so each pair has at most one predecessor
+    // and cycles are disjoint. Each cycle is broken by staging source of its first pair
+    // under a temporary name before anything else is executed.
+    let mut staged_paths = vec![None; pairs.len()];
+
+    for cycle in find_cycles(&successors) {
+        let staged_index = cycle[0];
+        let predecessor_index = cycle[cycle.len() - 1];
+
+        successors[predecessor_index].retain(|index| *index != staged_index);
+        staged_paths[staged_index] = Some(make_temp_path(&pairs[staged_index].0));
+    }
+
+    let mut steps = Vec::with_capacity(pairs.len());
+
+    for (index, staged_path) in staged_paths.iter().enumerate() {
+        if let Some(staged_path) = staged_path {
+            steps.push(Step {
+                src_path: pairs[index].0.clone(),
+                dst_path: staged_path.clone(),
+                mode,
+                pair_index: None,
+            });
+        }
+    }
+
+    let mut visited = vec![false; pairs.len()];
+
+    for root in 0..pairs.len() {
+        if visited[root] {
+            continue;
+        }
+
+        let mut stack = vec![(root, 0)];
+        visited[root] = true;
+
+        while let Some((index, next)) = stack.last_mut() {
+            if let Some(&next_index) = successors[*index].get(*next) {
+                *next += 1;
+                if !visited[next_index] {
+                    visited[next_index] = true;
+                    stack.push((next_index, 0));
+                }
+            } else {
+                let index = *index;
+                stack.pop();
+
+                steps.push(if let Some(staged_path) = &staged_paths[index] {
+                    Step {
+                        src_path: staged_path.clone(),
+                        dst_path: pairs[index].1.clone(),
+                        mode: TransferMode::Move,
+                        pair_index: Some(index),
+                    }
+                } else {
+                    Step {
+                        src_path: pairs[index].0.clone(),
+                        dst_path: pairs[index].1.clone(),
+                        mode,
+                        pair_index: Some(index),
+                    }
+                });
+            }
+        }
+    }
+
+    Ok(steps)
+}
+
+fn find_conflicts(
+    pairs: &[(PathBuf, PathBuf)],
+    dst_keys: &[PathBuf],
+    active: &[bool],
+) -> Vec<Conflict> {
+    let mut dst_indices: HashMap<&Path, Vec<usize>> = HashMap::new();
+    for index in (0..pairs.len()).filter(|index| active[*index]) {
+        dst_indices.entry(&dst_keys[index]).or_default().push(index);
+    }
+
+    let mut duplicates = dst_indices
+        .values()
+        .filter(|indices| indices.len() > 1)
+        .collect::<Vec<&Vec<usize>>>();
+    duplicates.sort();
+
+    duplicates
+        .into_iter()
+        .map(|indices| Conflict::DuplicateDestination {
+            dst_path: pairs[indices[0]].1.clone(),
+            src_paths: indices
+                .iter()
+                .map(|index| pairs[*index].0.clone())
+                .collect(),
+        })
+        .collect()
+}
+
+fn can_reorder(mode: TransferMode) -> bool {
+    // Staging and reordering is valid only when a destination gets content of its source.
+    // A link makes destination refer to its source which might be replaced by another pair.
+    match mode {
+        TransferMode::Move | TransferMode::Copy | TransferMode::Reflink => true,
+        TransferMode::HardLink | TransferMode::Symlink | TransferMode::RelativeSymlink => false,
+    }
+}
+
+fn find_order_conflicts(pairs: &[(PathBuf, PathBuf)], successors: &[Vec<usize>]) -> Vec<Conflict> {
+    let cycles = find_cycles(successors);
+    let mut in_cycle = vec![false; pairs.len()];
+    let mut conflicts = Vec::new();
+
+    for cycle in &cycles {
+        for index in cycle {
+            in_cycle[*index] = true;
+        }
+        let mut paths = cycle
+            .iter()
+            .map(|index| pairs[*index].0.clone())
+            .collect::<Vec<PathBuf>>();
+        paths.push(pairs[cycle[0]].0.clone());
+        conflicts.push(Conflict::Cycle { paths });
+    }
+
+    // Destination which is also a source is a conflict in either order.
+    // When linked before being overwritten, the link would refer to the new content.
+    for (index, next_indices) in successors.iter().enumerate() {
+        for next_index in next_indices {
+            if in_cycle[index] || in_cycle[*next_index] {
+                continue;
+            }
+            conflicts.push(if *next_index > index {
+                Conflict::PendingSource {
+                    src_path: pairs[index].0.clone(),
+                    dst_path: pairs[index].1.clone(),
+                    next_dst_path: pairs[*next_index].1.clone(),
+                }
+            } else {
+                Conflict::LinkedSource {
+                    src_path: pairs[index].0.clone(),
+                    dst_path: pairs[index].1.clone(),
+                    prev_dst_path: pairs[*next_index].1.clone(),
+                }
+            });
+        }
+    }
+
+    conflicts
+}
+
+fn find_cycles(successors: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        New,
+        Open,
+        Closed,
+    }
+
+    let mut states = vec![State::New; successors.len()];
+    let mut cycles = Vec::new();
+
+    for root in 0..successors.len() {
+        if states[root] != State::New {
+            continue;
+        }
+
+        let mut stack = vec![(root, 0)];
+        states[root] = State::Open;
+
+        while let Some((index, next)) = stack.last_mut() {
+            if let Some(&next_index) = successors[*index].get(*next) {
+                *next += 1;
+                match states[next_index] {
+                    State::New => {
+                        states[next_index] = State::Open;
+                        stack.push((next_index, 0));
+                    }
+                    State::Open => {
+                        let start = stack
+                            .iter()
+                            .position(|(index, _)| *index == next_index)
+                            .expect("Expected open index to be on stack");
+                        cycles.push(stack[start..].iter().map(|(index, _)| *index).collect());
+                    }
+                    State::Closed => {}
+                }
+            } else {
+                states[*index] = State::Closed;
+                stack.pop();
+            }
+        }
+    }
+
+    cycles
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(&[],                                   &[]                 ; "empty")]
+    #[test_case(&[vec![1], vec![2], vec![]],           &[]                 ; "chain")]
+    #[test_case(&[vec![0]],                            &[&[0]]             ; "self loop")]
+    #[test_case(&[vec![1], vec![0]],                   &[&[0, 1]]          ; "swap")]
+    #[test_case(&[vec![1], vec![2], vec![0]],          &[&[0, 1, 2]]       ; "rotation")]
+    #[test_case(&[vec![1], vec![0], vec![3], vec![2]], &[&[0, 1], &[2, 3]] ; "two swaps")]
+    fn find_cycles(successors: &[Vec<usize>], result: &[&[usize]]) {
+        assert_eq!(super::find_cycles(successors), result);
+    }
+
+    mod plan_transfer {
+        use super::*;
+
+        #[test]
+        fn empty() {
+            assert_eq!(plan(&[]), Ok(Vec::new()));
+        }
+
+        #[test]
+        fn independent() {
+            assert_eq!(
+                plan(&[("a", "b"), ("c", "d"), ("e", "e")]),
+                Ok(vec![
+                    step("a", "b", 0),
+                    step("c", "d", 1),
+                    step("e", "e", 2)
+                ])
+            );
+        }
+
+        #[test]
+        fn chain_in_order() {
+            assert_eq!(
+                plan(&[("b", "c"), ("a", "b")]),
+                Ok(vec![step("b", "c", 0), step("a", "b", 1)])
+            );
+        }
+
+        #[test]
+        fn chain_reversed() {
+            assert_eq!(
+                plan(&[("a", "b"), ("b", "c"), ("c", "d"), ("e", "f")]),
+                Ok(vec![
+                    step("c", "d", 2),
+                    step("b", "c", 1),
+                    step("a", "b", 0),
+                    step("e", "f", 3)
+                ])
+            );
+        }
+
+        #[test]
+        fn swap() {
+            let steps = plan(&[("a", "b"), ("b", "a")]).unwrap();
+            let temp_path = steps[0].dst_path.clone();
+
+            assert_eq!(
+                steps,
+                vec![
+                    staging_step("a", &temp_path),
+                    step("b", "a", 1),
+                    Step {
+                        src_path: temp_path.clone(),
+                        dst_path: "b".into(),
+                        mode: TransferMode::Move,
+                        pair_index: Some(0),
+                    }
+                ]
+            );
+            assert_ne!(temp_path, PathBuf::from("a"));
+            assert_ne!(temp_path, PathBuf::from("b"));
+        }
+
+        #[test]
+        fn rotation() {
+            let steps = plan(&[("1", "2"), ("2", "3"), ("3", "1")]).unwrap();
+            let temp_path = steps[0].dst_path.clone();
+
+            assert_eq!(
+                steps,
+                vec![
+                    staging_step("1", &temp_path),
+                    step("3", "1", 2),
+                    step("2", "3", 1),
+                    step(&temp_path, "2", 0),
+                ]
+            );
+        }
+
+        #[test]
+        fn staged_copy() {
+            let steps = plan_with_mode(&[("a", "b"), ("b", "a")], TransferMode::Copy).unwrap();
+            assert_eq!(
+                steps.iter().map(|step| step.mode).collect::<Vec<_>>(),
+                vec![TransferMode::Copy, TransferMode::Copy, TransferMode::Move]
+            );
+        }
+
+        #[test]
+        fn duplicate_destination() {
+            assert_eq!(
+                plan(&[("a", "c"), ("b", "./c"), ("d", "e")]),
+                Err(vec![Conflict::DuplicateDestination {
+                    dst_path: "c".into(),
+                    src_paths: vec!["a".into(), "b".into()],
+                }])
+            );
+        }
+
+        #[test]
+        fn unordered_links() {
+            assert_eq!(
+                plan_with_mode(&[("c", "d"), ("a", "b")], TransferMode::Symlink),
+                Ok(vec![
+                    Step {
+                        src_path: "c".into(),
+                        dst_path: "d".into(),
+                        mode: TransferMode::Symlink,
+                        pair_index: Some(0),
+                    },
+                    Step {
+                        src_path: "a".into(),
+                        dst_path: "b".into(),
+                        mode: TransferMode::Symlink,
+                        pair_index: Some(1),
+                    }
+                ])
+            );
+        }
+
+        #[test]
+        fn pending_source() {
+            assert_eq!(
+                plan_with_mode(&[("a", "b"), ("b", "c")], TransferMode::HardLink),
+                Err(vec![Conflict::PendingSource {
+                    src_path: "a".into(),
+                    dst_path: "b".into(),
+                    next_dst_path: "c".into(),
+                }])
+            );
+        }
+
+        #[test]
+        fn linked_source() {
+            assert_eq!(
+                plan_with_mode(&[("b", "c"), ("a", "b")], TransferMode::Symlink),
+                Err(vec![Conflict::LinkedSource {
+                    src_path: "a".into(),
+                    dst_path: "b".into(),
+                    prev_dst_path: "c".into(),
+                }])
+            );
+        }
+
+        #[test]
+        fn cycle() {
+            assert_eq!(
+                plan_with_mode(
+                    &[("a", "b"), ("b", "c"), ("c", "a")],
+                    TransferMode::RelativeSymlink
+                ),
+                Err(vec![Conflict::Cycle {
+                    paths: vec!["a".into(), "b".into(), "c".into(), "a".into()],
+                }])
+            );
+        }
+
+        fn plan(pairs: &[(&str, &str)]) -> Result {
+            plan_with_mode(pairs, TransferMode::Move)
+        }
+
+        fn plan_with_mode(pairs: &[(&str, &str)], mode: TransferMode) -> Result {
+            let pairs = pairs
+                .iter()
+                .map(|(src, dst)| (src.into(), dst.into()))
+                .collect::<Vec<(PathBuf, PathBuf)>>();
+            plan_transfer(&pairs, mode, &std::env::current_dir().unwrap())
+        }
+
+        fn step<S: AsRef<Path>, D: AsRef<Path>>(src_path: S, dst_path: D, index: usize) -> Step {
+            Step {
+                src_path: src_path.as_ref().into(),
+                dst_path: dst_path.as_ref().into(),
+                mode: TransferMode::Move,
+                pair_index: Some(index),
+            }
+        }
+
+        fn staging_step<S: AsRef<Path>, D: AsRef<Path>>(src_path: S, dst_path: D) -> Step {
+            Step {
+                src_path: src_path.as_ref().into(),
+                dst_path: dst_path.as_ref().into(),
+                mode: TransferMode::Move,
+                pair_index: None,
+            }
+        }
+    }
+
+    #[test_case(duplicate_conflict(), "Paths 'a', 'b' would be transferred to the same destination 'c'"      ; "duplicate destination")]
+    #[test_case(pending_conflict(),   "Path 'b' would be overwritten by 'a' before being transferred to 'c'" ; "pending source")]
+    #[test_case(linked_conflict(),    "Path 'b' would be overwritten by 'a' after being linked to 'c'"       ; "linked source")]
+    #[test_case(cycle_conflict(),     "Paths 'a' -> 'b' -> 'a' form a cycle"                                 ; "cycle")]
+    fn display_conflict(conflict: Conflict, result: &str) {
+        assert_eq!(conflict.to_string(), result);
+    }
+
+    fn duplicate_conflict() -> Conflict {
+        Conflict::DuplicateDestination {
+            dst_path: "c".into(),
+            src_paths: vec!["a".into(), "b".into()],
+        }
+    }
+
+    fn pending_conflict() -> Conflict {
+        Conflict::PendingSource {
+            src_path: "a".into(),
+            dst_path: "b".into(),
+            next_dst_path: "c".into(),
+        }
+    }
+
+    fn linked_conflict() -> Conflict {
+        Conflict::LinkedSource {
+            src_path: "a".into(),
+            dst_path: "b".into(),
+            prev_dst_path: "c".into(),
+        }
+    }
+
+    fn cycle_conflict() -> Conflict {
+        Conflict::Cycle {
+            paths: vec!["a".into(), "b".into(), "a".into()],
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::transfer::trash::Trash;
+
+pub const BACKUP_MODES: &[&str] = &[SUFFIX, NUMBERED];
+
+const SUFFIX: &str = "suffix";
+const NUMBERED: &str = "numbered";
+
+pub const PRESERVE_ATTRS: &[&str] = &[MODE, TIMESTAMPS, OWNERSHIP, XATTR];
+
+const MODE: &str = "mode";
+const TIMESTAMPS: &str = "timestamps";
+const OWNERSHIP: &str = "ownership";
+const XATTR: &str = "xattr";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BackupMode {
+    Suffix,
+    Numbered,
+}
+
+pub fn parse_backup_mode(string: &str) -> Result<BackupMode, &'static str> {
+    match string {
+        SUFFIX => Ok(BackupMode::Suffix),
+        NUMBERED => Ok(BackupMode::Numbered),
+        _ => Err("invalid value"),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct PreserveAttrs {
+    pub mode: bool,
+    pub timestamps: bool,
+    pub ownership: bool,
+    pub xattr: bool,
+}
+
+impl PreserveAttrs {
+    pub fn all() -> Self {
+        Self {
+            mode: true,
+            timestamps: true,
+            ownership: true,
+            xattr: true,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+pub fn parse_preserve_attrs<S: AsRef<str>>(strings: &[S]) -> Result<PreserveAttrs, &'static str> {
+    let mut attrs = PreserveAttrs::default();
+
+    for string in strings {
+        match string.as_ref() {
+            MODE => attrs.mode = true,
+            TIMESTAMPS => attrs.timestamps = true,
+            OWNERSHIP => attrs.ownership = true,
+            XATTR => attrs.xattr = true,
+            _ => return Err("invalid value"),
+        }
+    }
+
+    Ok(attrs)
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum OverwritePolicy {
+    Overwrite,
+    Skip,
+    Fail,
+    Backup(BackupMode),
+    Trash(Trash),
+    Update,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct TransferPolicy {
+    pub overwrite: OverwritePolicy,
+    pub merge_dirs: bool,
+    pub dereference: bool,
+    pub preserve: PreserveAttrs,
+    pub verify: bool,
+}
+
+impl Default for TransferPolicy {
+    fn default() -> Self {
+        Self {
+            overwrite: OverwritePolicy::Overwrite,
+            merge_dirs: true,
+            dereference: false,
+            preserve: PreserveAttrs::default(),
+            verify: false,
+        }
+    }
+}
+
+pub fn make_backup_path(path: &Path, mode: BackupMode) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+    match mode {
+        BackupMode::Suffix => path.with_file_name(format!("{}~", name)),
+        BackupMode::Numbered => {
+            let prefix = format!("{}.~", name);
+            let parent = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+
+            // Like GNU tools, use number following the highest existing one.
+            let last_number = fs::read_dir(parent)
+                .into_iter()
+                .flatten()
+                .filter_map(|entry| {
+                    let entry_name = entry.ok()?.file_name();
+                    let number = entry_name
+                        .to_str()?
+                        .strip_prefix(prefix.as_str())?
+                        .strip_suffix('~')?;
+                    number.parse::<usize>().ok()
+                })
+                .max()
+                .unwrap_or(0);
+
+            path.with_file_name(format!("{}.~{}~", name, last_number + 1))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("suffix",   Ok(BackupMode::Suffix)   ; "suffix")]
+    #[test_case("numbered", Ok(BackupMode::Numbered) ; "numbered")]
+    #[test_case("other",    Err("invalid value")     ; "invalid")]
+    fn parse_backup_mode(string: &str, result: Result<BackupMode, &'static str>) {
+        assert_eq!(super::parse_backup_mode(string), result);
+    }
+
+    #[test]
+    fn default_policy() {
+        assert_eq!(
+            TransferPolicy::default(),
+            TransferPolicy {
+                overwrite: OverwritePolicy::Overwrite,
+                merge_dirs: true,
+                dereference: false,
+                preserve: PreserveAttrs::default(),
+                verify: false,
+            }
+        );
+    }
+
+    #[test_case(&[],                Ok(PreserveAttrs::default()) ; "none")]
+    #[test_case(PRESERVE_ATTRS,     Ok(PreserveAttrs::all())     ; "all")]
+    #[test_case(&["mode", "other"], Err("invalid value")         ; "invalid")]
+    fn parse_preserve_attrs(strings: &[&str], result: Result<PreserveAttrs, &'static str>) {
+        assert_eq!(super::parse_preserve_attrs(strings), result);
+    }
+
+    #[test]
+    fn parse_some_preserve_attrs() {
+        assert_eq!(
+            super::parse_preserve_attrs(&["timestamps", "mode"]),
+            Ok(PreserveAttrs {
+                mode: true,
+                timestamps: true,
+                ownership: false,
+                xattr: false,
+            })
+        );
+    }
+
+    #[test_case(PreserveAttrs::default(), true  ; "none")]
+    #[test_case(PreserveAttrs::all(),     false ; "all")]
+    fn preserve_attrs_is_empty(attrs: PreserveAttrs, result: bool) {
+        assert_eq!(attrs.is_empty(), result);
+    }
+
+    #[test]
+    fn make_suffix_backup_path() {
+        assert_eq!(
+            make_backup_path(Path::new("a/b.c"), BackupMode::Suffix),
+            PathBuf::from("a/b.c~")
+        );
+    }
+
+    #[test]
+    fn make_numbered_backup_path() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.child("a");
+
+        assert_eq!(
+            make_backup_path(path.path(), BackupMode::Numbered),
+            dir.path().join("a.~1~")
+        );
+
+        dir.child("a.~1~").touch().unwrap();
+        dir.child("a.~3~").touch().unwrap();
+        dir.child("a.~x~").touch().unwrap();
+        dir.child("b.~4~").touch().unwrap();
+
+        assert_eq!(
+            make_backup_path(path.path(), BackupMode::Numbered),
+            dir.path().join("a.~4~")
+        );
+    }
+}
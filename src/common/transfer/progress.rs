@@ -0,0 +1,331 @@
+use std::io::{Result, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::transfer::fs::TransferProgress;
+
+const UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+const SIZE_UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+pub struct ProgressLog<O: Write> {
+    output: O,
+    live: bool,
+    pair_count: usize,
+    pair_index: usize,
+    pair_files: usize,
+    total_size: Option<u64>,
+    copied_size: CopiedSize,
+    path: PathBuf,
+    started: Instant,
+    updated: Option<Instant>,
+    line_visible: bool,
+}
+
+impl<O: Write> ProgressLog<O> {
+    pub fn new(output: O, live: bool, pair_count: usize, total_size: Option<u64>) -> Self {
+        Self {
+            output,
+            live,
+            pair_count,
+            pair_index: 0,
+            pair_files: 0,
+            total_size,
+            copied_size: CopiedSize::default(),
+            path: PathBuf::new(),
+            started: Instant::now(),
+            updated: None,
+            line_visible: false,
+        }
+    }
+
+    pub fn begin_pair(&mut self, dst_path: &Path) -> Result<()> {
+        self.pair_index += 1;
+        self.pair_files = 0;
+        self.path = dst_path.into();
+        self.update(false)
+    }
+
+    pub fn end_pair(&mut self) -> Result<()> {
+        if !self.live && self.pair_files == 0 {
+            // Pairs without copied files (e.g. renames) are reported at once.
+            self.write_line()?;
+        }
+        Ok(())
+    }
+
+    pub fn clear(&mut self) -> Result<()> {
+        if self.line_visible {
+            // Return to the line start and erase it.
+            write!(self.output, "\r\x1b[K")?;
+            self.output.flush()?;
+            self.line_visible = false;
+        }
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> Result<()> {
+        if self.live && self.pair_index > 0 {
+            self.update(true)?;
+            writeln!(self.output)?;
+            self.line_visible = false;
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, force: bool) -> Result<()> {
+        if !self.live {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        if !force && matches!(self.updated, Some(updated) if now - updated < UPDATE_INTERVAL) {
+            return Ok(());
+        }
+
+        self.updated = Some(now);
+        write!(self.output, "\r\x1b[K{}", self.format_status())?;
+        self.output.flush()?;
+        self.line_visible = true;
+        Ok(())
+    }
+
+    fn write_line(&mut self) -> Result<()> {
+        writeln!(self.output, "{}", self.format_status())
+    }
+
+    fn format_status(&self) -> String {
+        format_status(
+            self.pair_index,
+            self.pair_count,
+            self.copied_size.get(),
+            self.total_size,
+            self.started.elapsed(),
+            &self.path,
+        )
+    }
+}
+
+impl<O: Write> TransferProgress for ProgressLog<O> {
+    fn begin_file(&mut self, src_path: &Path, dst_path: &Path, size: u64) -> Result<()> {
+        self.copied_size.begin_file(src_path, dst_path, size)?;
+        self.pair_files += 1;
+        self.path = dst_path.into();
+        self.update(false)
+    }
+
+    fn update_file(&mut self, copied_size: u64) -> Result<()> {
+        self.copied_size.update_file(copied_size)?;
+        self.update(false)
+    }
+
+    fn end_file(&mut self) -> Result<()> {
+        self.copied_size.end_file()?;
+
+        if self.live {
+            self.update(false)
+        } else {
+            self.write_line()
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct CopiedSize {
+    finished_size: u64,
+    file_size: u64,
+}
+
+impl CopiedSize {
+    pub fn get(&self) -> u64 {
+        self.finished_size + self.file_size
+    }
+
+    pub fn add(&mut self, size: u64) {
+        self.finished_size += size;
+    }
+}
+
+impl TransferProgress for CopiedSize {
+    fn begin_file(&mut self, _src_path: &Path, _dst_path: &Path, _size: u64) -> Result<()> {
+        self.file_size = 0;
+        Ok(())
+    }
+
+    fn update_file(&mut self, copied_size: u64) -> Result<()> {
+        self.file_size = copied_size;
+        Ok(())
+    }
+
+    fn end_file(&mut self) -> Result<()> {
+        self.finished_size += self.file_size;
+        self.file_size = 0;
+        Ok(())
+    }
+}
+
+fn format_status(
+    pair_index: usize,
+    pair_count: usize,
+    copied_size: u64,
+    total_size: Option<u64>,
+    elapsed: Duration,
+    path: &Path,
+) -> String {
+    let mut status = format!("[{}/{}]", pair_index, pair_count);
+
+    // Size of moved files is unknown until they have to be copied between file systems.
+    if total_size.is_some() || copied_size > 0 {
+        status.push(' ');
+        status.push_str(&format_size(copied_size));
+
+        if let Some(total_size) = total_size {
+            let percents = (copied_size.min(total_size) * 100)
+                .checked_div(total_size)
+                .unwrap_or(100);
+            status.push_str(&format!(" / {} ({}%)", format_size(total_size), percents));
+        }
+
+        let seconds = elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            let speed = copied_size as f64 / seconds;
+            status.push_str(&format!(" at {}/s", format_size(speed as u64)));
+
+            if let Some(total_size) = total_size.filter(|_| speed > 0.0) {
+                let remaining_size = total_size.saturating_sub(copied_size);
+                let remaining_time = (remaining_size as f64 / speed).ceil() as u64;
+                status.push_str(&format!(", ETA {}", format_duration(remaining_time)));
+            }
+        }
+    }
+
+    status.push_str(&format!(" '{}'", path.to_string_lossy()));
+    status
+}
+
+fn format_size(size: u64) -> String {
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit < SIZE_UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", value, SIZE_UNITS[unit])
+}
+
+fn format_duration(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    if hours > 0 {
+        format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(0,                "0 B"        ; "zero")]
+    #[test_case(1023,             "1023 B"     ; "bytes")]
+    #[test_case(1024,             "1.0 KiB"    ; "kibibytes")]
+    #[test_case(1536,             "1.5 KiB"    ; "kibibytes fraction")]
+    #[test_case(10 * 1024 * 1024, "10.0 MiB"   ; "mebibytes")]
+    #[test_case(3 << 30,          "3.0 GiB"    ; "gibibytes")]
+    #[test_case(1 << 50,          "1.0 PiB"    ; "pebibytes")]
+    #[test_case(1 << 60,          "1024.0 PiB" ; "largest unit")]
+    fn format_size(size: u64, result: &str) {
+        assert_eq!(super::format_size(size), result);
+    }
+
+    #[test_case(0,    "0s"          ; "zero")]
+    #[test_case(59,   "59s"         ; "seconds")]
+    #[test_case(65,   "1m 05s"      ; "minutes")]
+    #[test_case(3723, "1h 02m 03s"  ; "hours")]
+    fn format_duration(seconds: u64, result: &str) {
+        assert_eq!(super::format_duration(seconds), result);
+    }
+
+    #[test_case(0,    None,       0, "[1/2] 'a'"                                              ; "nothing copied")]
+    #[test_case(1024, None,       0, "[1/2] 1.0 KiB 'a'"                                      ; "unknown total")]
+    #[test_case(1024, None,       1, "[1/2] 1.0 KiB at 1.0 KiB/s 'a'"                         ; "unknown total speed")]
+    #[test_case(1024, Some(4096), 0, "[1/2] 1.0 KiB / 4.0 KiB (25%) 'a'"                      ; "known total")]
+    #[test_case(1024, Some(4096), 1, "[1/2] 1.0 KiB / 4.0 KiB (25%) at 1.0 KiB/s, ETA 3s 'a'" ; "known total speed")]
+    #[test_case(0,    Some(0),    1, "[1/2] 0 B / 0 B (100%) at 0 B/s 'a'"                    ; "empty total")]
+    fn format_status(copied_size: u64, total_size: Option<u64>, seconds: u64, result: &str) {
+        assert_eq!(
+            super::format_status(
+                1,
+                2,
+                copied_size,
+                total_size,
+                Duration::from_secs(seconds),
+                Path::new("a")
+            ),
+            result
+        );
+    }
+
+    #[test]
+    fn plain_lines() {
+        let mut output = Vec::new();
+        let mut log = ProgressLog::new(&mut output, false, 2, None);
+
+        log.begin_pair(Path::new("a")).unwrap();
+        log.end_pair().unwrap();
+        log.begin_pair(Path::new("b")).unwrap();
+        log.begin_file(Path::new("c/d"), Path::new("b/d"), 0)
+            .unwrap();
+        log.end_file().unwrap();
+        log.begin_file(Path::new("c/e"), Path::new("b/e"), 0)
+            .unwrap();
+        log.end_file().unwrap();
+        log.end_pair().unwrap();
+        log.finish().unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "[1/2] 'a'\n[2/2] 'b/d'\n[2/2] 'b/e'\n"
+        );
+    }
+
+    #[test]
+    fn live_line() {
+        let mut output = Vec::new();
+        let mut log = ProgressLog::new(&mut output, true, 1, None);
+
+        log.begin_pair(Path::new("a")).unwrap();
+        log.clear().unwrap();
+        log.clear().unwrap();
+        log.end_pair().unwrap();
+        log.finish().unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\r\x1b[K[1/1] 'a'\r\x1b[K\r\x1b[K[1/1] 'a'\n"
+        );
+    }
+
+    #[test]
+    fn live_line_throttled() {
+        let mut output = Vec::new();
+        let mut log = ProgressLog::new(&mut output, true, 1, None);
+
+        log.begin_pair(Path::new("a")).unwrap();
+        log.begin_file(Path::new("b"), Path::new("a"), 0).unwrap();
+        log.end_file().unwrap();
+        log.end_pair().unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "\r\x1b[K[1/1] 'a'");
+    }
+}
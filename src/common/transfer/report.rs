@@ -0,0 +1,217 @@
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::encoding::{decode_os_str, EncodingPolicy};
+use crate::transfer::fs::{TransferAction, TransferMode, TransferProgress};
+use crate::transfer::progress::CopiedSize;
+
+pub const REPORT_FORMATS: &[&str] = &[JSON_LINES];
+
+const JSON_LINES: &str = "jsonl";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReportFormat {
+    JsonLines,
+}
+
+pub fn parse_report_format(string: &str) -> Result<ReportFormat, &'static str> {
+    match string {
+        JSON_LINES => Ok(ReportFormat::JsonLines),
+        _ => Err("invalid value"),
+    }
+}
+
+pub struct TransferReport<O: Write> {
+    output: O,
+    encoding: EncodingPolicy,
+    copied_size: CopiedSize,
+}
+
+impl<O: Write> TransferReport<O> {
+    pub fn new(output: O, encoding: EncodingPolicy) -> Self {
+        Self {
+            output,
+            encoding,
+            copied_size: CopiedSize::default(),
+        }
+    }
+
+    pub fn add_copied_size(&mut self, size: u64) {
+        self.copied_size.add(size);
+    }
+
+    pub fn write_transfer<E: Display>(
+        &mut self,
+        mode: TransferMode,
+        src_path: &Path,
+        dst_path: &Path,
+        result: Result<&TransferAction, E>,
+    ) -> io::Result<()> {
+        let action = match mode {
+            TransferMode::Move => "move",
+            TransferMode::Copy => "copy",
+            TransferMode::HardLink => "link",
+            TransferMode::Symlink | TransferMode::RelativeSymlink => "symlink",
+            TransferMode::Reflink => "clone",
+        };
+        let (result, error) = match result {
+            Ok(TransferAction::Create) => ("created", None),
+            Ok(TransferAction::Overwrite) => ("overwritten", None),
+            Ok(TransferAction::MergeDir) => ("merged", None),
+            Ok(TransferAction::Skip) => ("skipped", None),
+            Err(error) => ("failed", Some(error.to_string())),
+        };
+        let record = json!({
+            "src": decode_os_str(src_path.as_os_str(), self.encoding)?,
+            "dst": decode_os_str(dst_path.as_os_str(), self.encoding)?,
+            "action": action,
+            "result": result,
+            "error": error,
+        });
+        self.write_record(record)
+    }
+
+    pub fn write_removal<E: Display>(
+        &mut self,
+        path: &Path,
+        result: Result<(), E>,
+    ) -> io::Result<()> {
+        let (result, error) = match result {
+            Ok(()) => ("removed", None),
+            Err(error) => ("failed", Some(error.to_string())),
+        };
+        let record = json!({
+            "src": decode_os_str(path.as_os_str(), self.encoding)?,
+            "dst": null,
+            "action": "remove",
+            "result": result,
+            "error": error,
+        });
+        self.write_record(record)
+    }
+
+    pub fn write_summary(
+        &mut self,
+        completed: usize,
+        skipped: usize,
+        failed: usize,
+        pending: usize,
+    ) -> io::Result<()> {
+        self.write_record(json!({
+            "summary": {
+                "completed": completed,
+                "skipped": skipped,
+                "failed": failed,
+                "pending": pending,
+                "bytes": self.copied_size.get(),
+            }
+        }))
+    }
+
+    fn write_record(&mut self, record: Value) -> io::Result<()> {
+        writeln!(self.output, "{}", record)?;
+        // Each record should be available to a consumer as soon as possible.
+        self.output.flush()
+    }
+}
+
+impl<O: Write> TransferProgress for TransferReport<O> {
+    fn begin_file(&mut self, src_path: &Path, dst_path: &Path, size: u64) -> io::Result<()> {
+        self.copied_size.begin_file(src_path, dst_path, size)
+    }
+
+    fn update_file(&mut self, copied_size: u64) -> io::Result<()> {
+        self.copied_size.update_file(copied_size)
+    }
+
+    fn end_file(&mut self) -> io::Result<()> {
+        self.copied_size.end_file()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("jsonl", Ok(ReportFormat::JsonLines) ; "json lines")]
+    #[test_case("x",     Err("invalid value")        ; "invalid")]
+    fn parse_report_format(string: &str, result: Result<ReportFormat, &'static str>) {
+        assert_eq!(super::parse_report_format(string), result);
+    }
+
+    #[test_case(TransferMode::Move,            Ok(&TransferAction::Create),    r#"{"src":"a","dst":"b","action":"move","result":"created","error":null}"#     ; "move ")]
+    #[test_case(TransferMode::Copy,            Ok(&TransferAction::Overwrite), r#"{"src":"a","dst":"b","action":"copy","result":"overwritten","error":null}"# ; "copy")]
+    #[test_case(TransferMode::HardLink,        Ok(&TransferAction::Skip),      r#"{"src":"a","dst":"b","action":"link","result":"skipped","error":null}"#     ; "link")]
+    #[test_case(TransferMode::RelativeSymlink, Ok(&TransferAction::MergeDir),  r#"{"src":"a","dst":"b","action":"symlink","result":"merged","error":null}"#   ; "symlink")]
+    #[test_case(TransferMode::Reflink,         Err("\"x\""),                   r#"{"src":"a","dst":"b","action":"clone","result":"failed","error":"\"x\""}"#  ; "failure")]
+    fn write_transfer(mode: TransferMode, result: Result<&TransferAction, &str>, record: &str) {
+        let mut output = Vec::new();
+        TransferReport::new(&mut output, EncodingPolicy::Fail)
+            .write_transfer(mode, Path::new("a"), Path::new("b"), result)
+            .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), format!("{}\n", record));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn write_transfer_invalid_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let src_path = Path::new(OsStr::from_bytes(b"a\xff"));
+        let mut output = Vec::new();
+
+        TransferReport::new(&mut output, EncodingPolicy::Escape)
+            .write_transfer(
+                TransferMode::Move,
+                src_path,
+                Path::new("b%"),
+                Ok::<_, &str>(&TransferAction::Create),
+            )
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"src\":\"a%FF\",\"dst\":\"b%25\",\"action\":\"move\",\"result\":\"created\",\"error\":null}\n"
+        );
+    }
+
+    #[test_case(Ok(()),   r#"{"src":"a","dst":null,"action":"remove","result":"removed","error":null}"# ; "success")]
+    #[test_case(Err("x"), r#"{"src":"a","dst":null,"action":"remove","result":"failed","error":"x"}"#  ; "failure")]
+    fn write_removal(result: Result<(), &str>, record: &str) {
+        let mut output = Vec::new();
+        TransferReport::new(&mut output, EncodingPolicy::Fail)
+            .write_removal(Path::new("a"), result)
+            .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), format!("{}\n", record));
+    }
+
+    #[test]
+    fn write_summary() {
+        let mut output = Vec::new();
+        let mut report = TransferReport::new(&mut output, EncodingPolicy::Fail);
+
+        report
+            .begin_file(Path::new("a"), Path::new("b"), 10)
+            .unwrap();
+        report.update_file(5).unwrap();
+        report.update_file(10).unwrap();
+        report.end_file().unwrap();
+        report
+            .begin_file(Path::new("c"), Path::new("d"), 10)
+            .unwrap();
+        report.update_file(2).unwrap();
+        report.add_copied_size(100);
+        report.write_summary(1, 2, 3, 4).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"summary\":{\"completed\":1,\"skipped\":2,\"failed\":3,\"pending\":4,\"bytes\":112}}\n"
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Error, ErrorKind, Result, Write};
+use std::path::{Path, PathBuf};
+
+use crate::encoding::{decode_os_str, encode_os_string, EncodingPolicy};
+
+// Staged paths are always escaped, so they can be losslessly read back.
+const ENCODING: EncodingPolicy = EncodingPolicy::Escape;
+
+#[derive(Default, PartialEq, Debug)]
+pub struct State {
+    pub finished: HashSet<usize>,
+    // Temporary paths where sources of unfinished pairs were staged (e.g. part of a cycle).
+    pub staged: HashMap<usize, PathBuf>,
+}
+
+pub struct ResumeState<O: Write> {
+    state: State,
+    output: O,
+}
+
+impl ResumeState<File> {
+    pub fn open(path: &Path) -> Result<Self> {
+        let (state, valid_len) = match File::open(path) {
+            Ok(file) => read_state(BufReader::new(file), path)?,
+            Err(error) if error.kind() == ErrorKind::NotFound => (State::default(), 0),
+            Err(error) => return Err(error),
+        };
+
+        let output = OpenOptions::new().create(true).append(true).open(path)?;
+        // Drop incomplete last line, so it is not joined with the next one.
+        output.set_len(valid_len)?;
+
+        Ok(Self::new(state, output))
+    }
+}
+
+impl<O: Write> ResumeState<O> {
+    pub fn new(state: State, output: O) -> Self {
+        Self { state, output }
+    }
+
+    pub fn is_finished(&self, pair_index: usize) -> bool {
+        self.state.finished.contains(&pair_index)
+    }
+
+    pub fn staged_path(&self, pair_index: usize) -> Option<&Path> {
+        self.state.staged.get(&pair_index).map(PathBuf::as_path)
+    }
+
+    pub fn stage(&mut self, pair_index: usize, path: &Path) -> Result<()> {
+        let value = serde_json::to_string(&decode_os_str(path.as_os_str(), ENCODING)?)?;
+        self.write_line(&format!("{} {}", pair_index, value))?;
+        self.state.staged.insert(pair_index, path.into());
+        Ok(())
+    }
+
+    pub fn finish(&mut self, pair_index: usize) -> Result<()> {
+        if self.state.finished.insert(pair_index) {
+            self.write_line(&pair_index.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        // Flushed immediately, so the state survives an unexpected termination.
+        self.output.write_all(format!("{}\n", line).as_bytes())?;
+        self.output.flush()
+    }
+}
+
+fn read_state<I: BufRead>(mut input: I, path: &Path) -> Result<(State, u64)> {
+    let mut state = State::default();
+    let mut line = String::new();
+    let mut line_number = 0;
+    let mut valid_len = 0;
+
+    loop {
+        line.clear();
+        let line_len = input.read_line(&mut line)?;
+
+        if line_len == 0 || !line.ends_with('\n') {
+            // Last line might not be completely written due to an unexpected termination.
+            return Ok((state, valid_len));
+        }
+
+        line_number += 1;
+        valid_len += line_len as u64;
+
+        let value = line.trim();
+        if value.is_empty() {
+            continue;
+        }
+
+        // Line is either `index` of a finished pair or `index "path"` of a staged one.
+        let (index, staged_path) = match value.split_once(' ') {
+            Some((index, staged_path)) => (index, Some(staged_path)),
+            None => (value, None),
+        };
+
+        let pair_index = index.parse::<usize>();
+        let staged_path = staged_path.map(serde_json::from_str::<String>);
+
+        match (pair_index, staged_path) {
+            (Ok(pair_index), None) => {
+                state.finished.insert(pair_index);
+            }
+            (Ok(pair_index), Some(Ok(staged_path))) => {
+                let staged_path = encode_os_string(&staged_path, ENCODING).into();
+                state.staged.insert(pair_index, staged_path);
+            }
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "Invalid value '{}' in resume state '{}' at line {}",
+                        value,
+                        path.to_string_lossy(),
+                        line_number
+                    ),
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn open_missing() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.child("state");
+
+        let mut state = ResumeState::open(file.path()).unwrap();
+        assert!(!state.is_finished(0));
+
+        state.finish(1).unwrap();
+        state.finish(0).unwrap();
+        state.finish(1).unwrap();
+
+        file.assert("1\n0\n");
+    }
+
+    #[test]
+    fn open_existing() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.child("state");
+        file.write_str("2\n\n0\n").unwrap();
+
+        let mut state = ResumeState::open(file.path()).unwrap();
+        assert!(state.is_finished(0));
+        assert!(!state.is_finished(1));
+        assert!(state.is_finished(2));
+
+        state.finish(1).unwrap();
+        file.assert("2\n\n0\n1\n");
+    }
+
+    #[test]
+    fn open_incomplete() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.child("state");
+        file.write_str("0\n12").unwrap();
+
+        let mut state = ResumeState::open(file.path()).unwrap();
+        assert!(state.is_finished(0));
+        assert!(!state.is_finished(12));
+
+        state.finish(1).unwrap();
+        file.assert("0\n1\n");
+    }
+
+    #[test]
+    fn open_invalid() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.child("state");
+        file.write_str("0\nx\n").unwrap();
+
+        let error = ResumeState::open(file.path()).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Invalid value 'x' in resume state '{}' at line 2",
+                file.path().to_string_lossy()
+            )
+        );
+    }
+
+    #[test]
+    fn open_staged() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.child("state");
+        file.write_str("1 \"a/.b.tmp\"\n0\n").unwrap();
+
+        let state = ResumeState::open(file.path()).unwrap();
+        assert!(state.is_finished(0));
+        assert!(!state.is_finished(1));
+        assert_eq!(state.staged_path(0), None);
+        assert_eq!(state.staged_path(1), Some(Path::new("a/.b.tmp")));
+    }
+
+    #[test]
+    fn open_invalid_staged() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.child("state");
+        file.write_str("1 a\n").unwrap();
+
+        let error = ResumeState::open(file.path()).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Invalid value '1 a' in resume state '{}' at line 1",
+                file.path().to_string_lossy()
+            )
+        );
+    }
+
+    #[test]
+    fn finish() {
+        let mut output = Vec::new();
+        let mut state = ResumeState::new(State::default(), &mut output);
+
+        state.finish(3).unwrap();
+        state.finish(3).unwrap();
+        assert!(state.is_finished(3));
+
+        assert_eq!(output, b"3\n");
+    }
+
+    #[test]
+    fn stage() {
+        let mut output = Vec::new();
+        let mut state = ResumeState::new(State::default(), &mut output);
+
+        state.stage(2, Path::new("a/.b\n%.tmp")).unwrap();
+        assert_eq!(state.staged_path(2), Some(Path::new("a/.b\n%.tmp")));
+        assert!(!state.is_finished(2));
+
+        assert_eq!(output, b"2 \"a/.b\\n%25.tmp\"\n");
+    }
+}
@@ -1,43 +1,750 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::{env, io, thread};
+
+use termcolor::WriteColor;
+
+use crate::encoding::EncodingPolicy;
 use crate::input::Terminator;
 use crate::output::write_error;
 use crate::run::{Io, Options, Result, EXIT_CODE_IO_ERROR, EXIT_CODE_OK};
-use crate::transfer::fs::{transfer_path, TransferMode};
-use crate::transfer::input::PathDiff;
-use crate::transfer::output::TransferLog;
+use crate::transfer::fs::{
+    measure_size, normalize_path, remove_path, transfer_path_with_progress, DryRun, TransferAction,
+    TransferMode, TransferProgress,
+};
+use crate::transfer::input::{read_answer, Answer, InputFormat, PathDiff, PathRecords};
+use crate::transfer::journal::{plan_undo, read_journal, JournalRecord, JournalWriter};
+use crate::transfer::output::{PathDiffWriter, TransferJournal, TransferLog};
+use crate::transfer::parallel::TransferPool;
+use crate::transfer::plan::{plan_transfer, Conflict, Step};
+use crate::transfer::policy::{BackupMode, OverwritePolicy, PreserveAttrs, TransferPolicy};
+use crate::transfer::progress::ProgressLog;
+use crate::transfer::report::{ReportFormat, TransferReport};
+use crate::transfer::resume::ResumeState;
+use crate::transfer::signal::{handle_interrupts, is_interrupted, EXIT_CODE_INTERRUPTED};
+use crate::transfer::trash::{move_to_trash, Trash};
+
+const EXIT_CODE_CONFLICT_ERROR: i32 = 3;
+const EXIT_CODE_ROLLBACK_ERROR: i32 = 4;
+
+const STDIN_PATH: &str = "-";
 
 pub trait TransferOptions {
+    fn files(&self) -> &[PathBuf];
+    fn read(&self) -> Option<u8>;
     fn read_nul(&self) -> bool;
+    fn input_format(&self) -> InputFormat;
+    fn encoding(&self) -> EncodingPolicy;
     fn verbose(&self) -> bool;
     fn fail_at_end(&self) -> bool;
+    fn atomic(&self) -> bool;
+    fn journal(&self) -> Option<&Path>;
+    fn undo(&self) -> Option<&Path>;
+    fn dry_run(&self) -> bool;
+    fn no_clobber(&self) -> bool;
+    fn fail_if_exists(&self) -> bool;
+    fn backup(&self) -> Option<BackupMode>;
+    fn trash(&self) -> Option<Trash>;
+    fn update(&self) -> bool;
+    fn no_merge(&self) -> bool;
+    fn interactive(&self) -> bool;
+    fn dereference(&self) -> bool;
+    fn preserve(&self) -> PreserveAttrs;
+    fn verify(&self) -> bool;
+    fn remaining(&self) -> Option<&Path>;
+    fn resume(&self) -> Option<&Path>;
+    fn progress(&self) -> bool;
+    fn report(&self) -> Option<ReportFormat>;
+    fn jobs(&self) -> usize;
 }
 
 pub fn run_transfer<O>(options: &O, io: &Io, mode: TransferMode) -> Result
 where
     O: Options + TransferOptions,
 {
-    let terminator = if options.read_nul() {
+    let working_dir = env::current_dir()?;
+    let policy = get_policy(options);
+
+    let mut resume_state = match options.resume() {
+        Some(resume_path) => Some(ResumeState::open(resume_path)?),
+        None => None,
+    };
+
+    let mut exit_code = EXIT_CODE_OK;
+    let mut journal_records = Vec::new();
+    let mut removed_paths = Vec::new();
+    // Original sources (and modes) of staged paths, so they can be reported as such.
+    let mut staged_sources = HashMap::new();
+
+    let steps = if let Some(undo_path) = options.undo() {
+        let records = read_journal(
+            BufReader::new(File::open(undo_path)?),
+            get_input_terminator(options),
+        )?;
+
+        match plan_undo(&records, mode, &working_dir) {
+            Ok(undo) => {
+                for path in &undo.kept_paths {
+                    let error = io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!(
+                            "Path '{}' existed before and cannot be restored",
+                            path.to_string_lossy()
+                        ),
+                    );
+                    write_error(&mut io.stderr(), &error)?;
+                    exit_code = EXIT_CODE_IO_ERROR;
+                }
+                removed_paths = undo.removed_paths;
+                undo.steps
+            }
+            Err(conflicts) => return write_conflicts(&conflicts, io),
+        }
+    } else {
+        let mut pairs = read_instructions(options, io)?;
+
+        if options.journal().is_some() {
+            // Existence of destinations has to be known before anything is transferred.
+            journal_records = pairs
+                .iter()
+                .map(|(src_path, dst_path)| JournalRecord {
+                    src_path: normalize_path(src_path, &working_dir),
+                    dst_path: normalize_path(dst_path, &working_dir),
+                    existed: dst_path.symlink_metadata().is_ok(),
+                })
+                .collect();
+        }
+
+        let mut pair_indices = (0..pairs.len()).collect::<Vec<usize>>();
+
+        let mut staged = vec![false; pairs.len()];
+
+        if let Some(resume_state) = &resume_state {
+            let unfinished = resume_pairs(pairs, resume_state)?;
+            pair_indices = unfinished.0;
+            pairs = unfinished.1;
+            staged = Vec::with_capacity(pairs.len());
+
+            for ((staged_path, _), src_path) in pairs.iter().zip(unfinished.2) {
+                staged.push(src_path.is_some());
+                if let Some(src_path) = src_path {
+                    staged_sources.insert(staged_path.clone(), (src_path, mode));
+                }
+            }
+        }
+
+        let mut steps = match plan_transfer(&pairs, mode, &working_dir) {
+            Ok(steps) => steps,
+            Err(conflicts) => return write_conflicts(&conflicts, io),
+        };
+
+        for step in &mut steps {
+            if let Some(true) = step.pair_index.map(|index| staged[index]) {
+                // Staged source is always moved to its destination.
+                step.mode = TransferMode::Move;
+            }
+        }
+
+        if options.interactive() && !steps.is_empty() {
+            // Standard input might be already consumed by instructions.
+            let confirmed_indices = match confirm_steps(
+                &steps,
+                &pairs,
+                mode,
+                &working_dir,
+                &policy,
+                &mut io.tty()?,
+                io.stderr(),
+            )? {
+                Some(indices) => indices,
+                None => return Ok(EXIT_CODE_OK),
+            };
+
+            if confirmed_indices.len() < pairs.len() {
+                // Declined pair might be a part of chain or cycle, so the rest is planned again.
+                pair_indices = confirmed_indices
+                    .iter()
+                    .map(|index| pair_indices[*index])
+                    .collect();
+                pairs = confirmed_indices
+                    .iter()
+                    .map(|index| pairs[*index].clone())
+                    .collect();
+                steps = match plan_transfer(&pairs, mode, &working_dir) {
+                    Ok(steps) => steps,
+                    Err(conflicts) => return write_conflicts(&conflicts, io),
+                };
+            }
+        }
+
+        for step in &mut steps {
+            step.pair_index = step.pair_index.map(|index| pair_indices[index]);
+        }
+
+        steps
+    };
+
+    for step in steps.iter().filter(|step| step.pair_index.is_none()) {
+        staged_sources.insert(step.dst_path.clone(), (step.src_path.clone(), step.mode));
+    }
+
+    if options.dry_run() {
+        let simulate_code = simulate_transfer(&steps, &removed_paths, &working_dir, &policy, io)?;
+        return Ok(exit_code.max(simulate_code));
+    }
+
+    let mut journal_file = if let Some(journal_path) = options.journal() {
+        let output = BufWriter::new(File::create(journal_path)?);
+        Some(JournalWriter::new(
+            output,
+            &get_output_terminator(options),
+            journal_records,
+        ))
+    } else {
+        None
+    };
+
+    let pair_count = steps
+        .iter()
+        .filter(|step| step.pair_index.is_some())
+        .count();
+
+    let mut progress = if options.progress() {
+        let live = atty::is(atty::Stream::Stderr);
+        let total_size = measure_total_size(&steps, mode, &policy);
+        Some(ProgressLog::new(io.stderr(), live, pair_count, total_size))
+    } else {
+        None
+    };
+
+    // Staging step belongs to the pair which is finished by moving the staged source.
+    let staged_steps = steps
+        .iter()
+        .filter(|step| step.pair_index.is_some() && staged_sources.contains_key(&step.src_path))
+        .map(|step| (step.src_path.as_path(), step))
+        .collect::<HashMap<&Path, &Step>>();
+
+    let mut report = options
+        .report()
+        .map(|_| TransferReport::new(io.stdout(), options.encoding()));
+    let mut log = TransferLog::new(io.stdout());
+    let mut journal = TransferJournal::new();
+
+    let mut processed = 0;
+    let mut completed = 0;
+    let mut skipped = 0;
+    let mut failed_steps = Vec::new();
+    let mut failed_pairs = HashSet::new();
+    let mut completed_pairs = Vec::new();
+
+    handle_interrupts()?;
+
+    thread::scope(|scope| -> io::Result<()> {
+        let mut pool = if options.jobs() > 1 {
+            let count_copied_size = report.is_some();
+            let pool = TransferPool::new(
+                scope,
+                &steps,
+                options.jobs(),
+                &policy,
+                &working_dir,
+                count_copied_size,
+            );
+            Some(pool)
+        } else {
+            None
+        };
+
+        let mut stopped = false;
+
+        loop {
+            let (step, result) = if let Some(pool) = pool.as_mut() {
+                let (index, step_result) = match pool.next(!stopped && !is_interrupted()) {
+                    Some(next) => next,
+                    None => break,
+                };
+                let step = &steps[index];
+
+                // Log is written in order once the transfer is finished.
+                if options.verbose() {
+                    log.begin_transfer(step.mode, &step.src_path, &step.dst_path)?;
+                }
+                if let Some(report) = report.as_mut() {
+                    report.add_copied_size(step_result.copied_size);
+                }
+
+                (step, step_result.result)
+            } else {
+                if stopped || processed == steps.len() || is_interrupted() {
+                    break;
+                }
+
+                let step = &steps[processed];
+
+                if options.verbose() {
+                    log.begin_transfer(step.mode, &step.src_path, &step.dst_path)?;
+                }
+                // Staging step is counted as a part of its pair.
+                if let (Some(progress), Some(_)) = (progress.as_mut(), step.pair_index) {
+                    progress.begin_pair(&step.dst_path)?;
+                }
+
+                let mut step_progress = (progress.as_mut(), report.as_mut());
+                let step_progress = if step_progress.0.is_some() || step_progress.1.is_some() {
+                    Some(&mut step_progress as &mut dyn TransferProgress)
+                } else {
+                    None
+                };
+
+                let result = if options.atomic() {
+                    journal.transfer(
+                        &step.src_path,
+                        &step.dst_path,
+                        step.mode,
+                        &policy,
+                        step_progress,
+                    )
+                } else {
+                    transfer_path_with_progress(
+                        &step.src_path,
+                        &step.dst_path,
+                        step.mode,
+                        &policy,
+                        step_progress,
+                    )
+                };
+
+                (step, result)
+            };
+
+            let Step {
+                dst_path,
+                pair_index,
+                ..
+            } = step;
+
+            processed += 1;
+
+            let pair_step = match pair_index {
+                Some(_) => Some(step),
+                None => staged_steps.get(dst_path.as_path()).copied(),
+            };
+
+            // Pair is reported either by its last step or by the first failed one.
+            let reported = match pair_step.and_then(|step| step.pair_index) {
+                Some(index) => {
+                    !failed_pairs.contains(&index) && (pair_index.is_some() || result.is_err())
+                }
+                None => false,
+            };
+
+            if let (Some(report), Some(pair_step), true) = (report.as_mut(), pair_step, reported) {
+                let (src_path, mode) = match staged_sources.get(&pair_step.src_path) {
+                    Some((src_path, mode)) => (src_path, *mode),
+                    None => (&pair_step.src_path, pair_step.mode),
+                };
+                report.write_transfer(mode, src_path, &pair_step.dst_path, result.as_ref())?;
+            }
+
+            match result {
+                Ok(action) => {
+                    if let (Some(progress), Some(_)) = (progress.as_mut(), pair_index) {
+                        progress.end_pair()?;
+                    }
+                    if pair_index.is_none() {
+                        // Staging step is counted as a part of its pair.
+                    } else if action == TransferAction::Skip {
+                        skipped += 1;
+                    } else {
+                        completed += 1;
+                        completed_pairs.extend(pair_index);
+                    }
+                    if let Some(resume_state) = resume_state.as_mut() {
+                        if let Some(pair_index) = pair_index {
+                            resume_state.finish(*pair_index)?;
+                        } else if let Some(pair_index) = pair_step.and_then(|step| step.pair_index)
+                        {
+                            resume_state
+                                .stage(pair_index, &normalize_path(dst_path, &working_dir))?;
+                        }
+                    }
+                    if options.verbose() {
+                        log.end_with_success()?;
+                    }
+                    if let (Some(journal_file), Some(pair_index)) = (
+                        journal_file.as_mut().filter(|_| !options.atomic()),
+                        pair_index.filter(|_| action != TransferAction::Skip),
+                    ) {
+                        journal_file.write(pair_index)?;
+                    }
+                }
+                Err(error) => {
+                    if options.verbose() {
+                        log.end_with_failure()?;
+                    }
+                    if let Some(progress) = progress.as_mut() {
+                        progress.clear()?;
+                    }
+
+                    write_error(&mut io.stderr(), &error)?;
+                    failed_steps.push(step);
+                    failed_pairs.extend(pair_step.and_then(|step| step.pair_index));
+                    exit_code = EXIT_CODE_IO_ERROR;
+
+                    // Already running parallel transfers are let to finish.
+                    if options.atomic() || !options.fail_at_end() {
+                        stopped = true;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    })?;
+
+    if let Some(mut progress) = progress {
+        progress.finish()?;
+    }
+
+    let mut failed_removals = 0;
+
+    // Removals cannot be rolled back, so they are done only after successful transfers.
+    if !is_interrupted() && (failed_steps.is_empty() || options.fail_at_end()) {
+        let (removed, failed) =
+            remove_paths(&removed_paths, options, &mut log, report.as_mut(), io)?;
+        completed += removed;
+        failed_removals = failed;
+
+        if failed_removals > 0 {
+            exit_code = EXIT_CODE_IO_ERROR;
+        }
+    }
+
+    if let Some(remaining_path) = options.remaining() {
+        let unfinished_steps = failed_steps.iter().copied().chain(&steps[processed..]);
+        write_steps(remaining_path, unfinished_steps, options, &working_dir)?;
+    }
+
+    let failed = failed_pairs.len() + failed_removals;
+    let pending = pair_count + removed_paths.len() - completed - skipped - failed;
+
+    if let Some(report) = report.as_mut() {
+        report.write_summary(completed, skipped, failed, pending)?;
+    }
+
+    if is_interrupted() {
+        let mut stderr_log = TransferLog::new(io.stderr());
+        stderr_log.write_interrupt_result(completed, skipped, failed, pending)?;
+
+        if options.atomic() {
+            let rollback_code = rollback(journal, &mut log, options, io)?;
+            if rollback_code == EXIT_CODE_ROLLBACK_ERROR {
+                return Ok(rollback_code);
+            }
+        }
+
+        return Ok(EXIT_CODE_INTERRUPTED);
+    }
+
+    if options.atomic() && !failed_steps.is_empty() {
+        return rollback(journal, &mut log, options, io);
+    }
+
+    for entry in journal.into_entries() {
+        if let Err(error) = entry.commit() {
+            write_error(&mut io.stderr(), &error)?;
+            exit_code = EXIT_CODE_IO_ERROR;
+        }
+    }
+
+    if let Some(journal_file) = journal_file.as_mut().filter(|_| options.atomic()) {
+        for pair_index in completed_pairs {
+            journal_file.write(pair_index)?;
+        }
+    }
+
+    Ok(exit_code)
+}
+
+type ResumedPairs = (Vec<usize>, Vec<(PathBuf, PathBuf)>, Vec<Option<PathBuf>>);
+
+fn resume_pairs<O: Write>(
+    pairs: Vec<(PathBuf, PathBuf)>,
+    resume_state: &ResumeState<O>,
+) -> io::Result<ResumedPairs> {
+    let mut unfinished_indices = Vec::new();
+    let mut unfinished_pairs = Vec::new();
+    let mut staged_sources = Vec::new();
+
+    for (index, (src_path, dst_path)) in pairs.into_iter().enumerate() {
+        let dst_exists = dst_path.symlink_metadata().is_ok();
+
+        // Finished pairs are skipped only when their result still exists.
+        if resume_state.is_finished(index) && dst_exists {
+            continue;
+        }
+
+        // Staged source continues from its temporary path. When the path is gone,
+        // it was already moved to the destination, just not recorded as finished.
+        let (src_path, staged_source) = match resume_state.staged_path(index) {
+            Some(staged_path) if staged_path.symlink_metadata().is_ok() => {
+                (staged_path.to_path_buf(), Some(src_path))
+            }
+            Some(_) if dst_exists => continue,
+            _ => (src_path, None),
+        };
+
+        // Sources are never created by other pairs, so those of recorded pairs have to exist.
+        // Unrecorded pairs with a missing source simply fail as in a normal run.
+        let recorded = resume_state.is_finished(index) || resume_state.staged_path(index).is_some();
+        if recorded && src_path.symlink_metadata().is_err() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "Resume state does not match file system, source '{}' of instruction #{} not found",
+                    src_path.to_string_lossy(),
+                    index + 1
+                ),
+            ));
+        }
+
+        unfinished_indices.push(index);
+        unfinished_pairs.push((src_path, dst_path));
+        staged_sources.push(staged_source);
+    }
+
+    Ok((unfinished_indices, unfinished_pairs, staged_sources))
+}
+
+fn write_conflicts(conflicts: &[Conflict], io: &Io) -> Result {
+    let mut stderr = io.stderr();
+    for conflict in conflicts {
+        write_error(&mut stderr, conflict)?;
+    }
+    Ok(EXIT_CODE_CONFLICT_ERROR)
+}
+
+fn measure_total_size(steps: &[Step], mode: TransferMode, policy: &TransferPolicy) -> Option<u64> {
+    match mode {
+        TransferMode::Copy | TransferMode::Reflink => Some(
+            steps
+                .iter()
+                .filter(|step| step.mode == mode)
+                .map(|step| measure_size(&step.src_path, policy.dereference))
+                .sum(),
+        ),
+        // Links do not copy any data, moved files are copied only between file systems.
+        _ => None,
+    }
+}
+
+fn write_steps<'a, O, S>(path: &Path, steps: S, options: &O, working_dir: &Path) -> io::Result<()>
+where
+    O: TransferOptions,
+    S: Iterator<Item = &'a Step>,
+{
+    let output = BufWriter::new(File::create(path)?);
+    let mut writer = PathDiffWriter::new(output, &get_output_terminator(options));
+
+    for step in steps {
+        writer.write(
+            &normalize_path(&step.src_path, working_dir),
+            &normalize_path(&step.dst_path, working_dir),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn get_input_terminator<O: TransferOptions>(options: &O) -> Terminator {
+    if let Some(value) = options.read() {
+        Terminator::Byte {
+            value,
+            required: false,
+        }
+    } else if options.read_nul() {
         Terminator::Byte {
             value: 0,
             required: false,
         }
     } else {
         Terminator::Newline { required: false }
-    };
+    }
+}
 
-    let mut path_diff = PathDiff::new(io.stdin(), terminator);
-    let mut log = TransferLog::new(io.stdout());
-    let mut exit_code = EXIT_CODE_OK;
+fn get_output_terminator<O: TransferOptions>(options: &O) -> String {
+    if let Some(value) = options.read() {
+        char::from(value).to_string()
+    } else if options.read_nul() {
+        "\0".into()
+    } else {
+        "\n".into()
+    }
+}
+
+fn read_instructions<O: TransferOptions>(
+    options: &O,
+    io: &Io,
+) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    let format = options.input_format();
+    let encoding = options.encoding();
+
+    if options.files().is_empty() {
+        return read_pairs(io.stdin(), get_input_terminator(options), format, encoding);
+    }
+
+    let mut pairs = Vec::new();
+
+    for path in options.files() {
+        let terminator = get_input_terminator(options);
+
+        if path == Path::new(STDIN_PATH) {
+            pairs.extend(read_pairs(io.stdin(), terminator, format, encoding)?);
+        } else {
+            let result = File::open(path)
+                .and_then(|file| read_pairs(BufReader::new(file), terminator, format, encoding));
+            pairs.extend(result.map_err(|error| {
+                io::Error::new(
+                    error.kind(),
+                    format!(
+                        "Cannot read instructions from '{}': {}",
+                        path.to_string_lossy(),
+                        error
+                    ),
+                )
+            })?);
+        }
+    }
+
+    Ok(pairs)
+}
+
+fn read_pairs<I: BufRead>(
+    input: I,
+    terminator: Terminator,
+    format: InputFormat,
+    encoding: EncodingPolicy,
+) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut pairs = Vec::new();
+
+    match format {
+        InputFormat::Diff => {
+            let mut path_diff = PathDiff::new(input, terminator, encoding);
+            while let Some(pair) = path_diff.read()? {
+                pairs.push(pair);
+            }
+        }
+        InputFormat::JsonLines | InputFormat::Tsv => {
+            let mut records = if format == InputFormat::JsonLines {
+                PathRecords::json_lines(input, terminator, encoding)
+            } else {
+                PathRecords::tsv(input, terminator, encoding)
+            };
+            while let Some(pair) = records.read()? {
+                pairs.push(pair);
+            }
+        }
+    }
+
+    Ok(pairs)
+}
+
+fn confirm_steps<I, O>(
+    steps: &[Step],
+    pairs: &[(PathBuf, PathBuf)],
+    mode: TransferMode,
+    working_dir: &Path,
+    policy: &TransferPolicy,
+    input: &mut I,
+    output: O,
+) -> io::Result<Option<Vec<usize>>>
+where
+    I: BufRead,
+    O: Write + WriteColor,
+{
+    let mut log = TransferLog::new(output);
+    let mut dry_run = DryRun::new(working_dir, policy);
+    let mut confirmed_indices = Vec::with_capacity(pairs.len());
+    let mut confirm_all = false;
+
+    for step in steps {
+        // Each pair is confirmed with its action planned after all previous steps.
+        let result = dry_run.transfer(&step.src_path, &step.dst_path, step.mode);
+
+        let pair_index = match step.pair_index {
+            Some(pair_index) => pair_index,
+            None => continue,
+        };
+
+        if !confirm_all {
+            let (src_path, dst_path) = &pairs[pair_index];
+            let staged = step.src_path != *src_path;
+            let action = result.as_ref().ok().map(|(action, _)| action);
+
+            let answer = loop {
+                log.begin_confirmation(mode, src_path, dst_path, staged, action)?;
+                if let Some(answer) = read_answer(input)? {
+                    break answer;
+                }
+            };
+
+            match answer {
+                Answer::Yes => {}
+                Answer::No => continue,
+                Answer::All => confirm_all = true,
+                Answer::Quit => return Ok(None),
+            }
+        }
+
+        confirmed_indices.push(pair_index);
+    }
+
+    confirmed_indices.sort_unstable();
+    Ok(Some(confirmed_indices))
+}
+
+fn remove_paths<O, L, R>(
+    paths: &[PathBuf],
+    options: &O,
+    log: &mut TransferLog<L>,
+    mut report: Option<&mut TransferReport<R>>,
+    io: &Io,
+) -> io::Result<(usize, usize)>
+where
+    O: TransferOptions,
+    L: Write + WriteColor,
+    R: Write,
+{
+    let trash = options.trash();
+    let mut completed = 0;
+    let mut failed = 0;
+
+    for path in paths {
+        if is_interrupted() {
+            break;
+        }
 
-    while let Some((src_path, dst_path)) = path_diff.read()? {
         if options.verbose() {
-            log.begin_transfer(mode, &src_path, &dst_path)?;
+            log.begin_removal(path)?;
         }
 
-        match transfer_path(&src_path, &dst_path, mode) {
+        let result = match &trash {
+            Some(trash) => move_to_trash(path, path, trash).map(|_| ()),
+            None => remove_path(path),
+        };
+
+        if let Some(report) = report.as_mut() {
+            report.write_removal(path, result.as_ref().map(|_| ()))?;
+        }
+
+        match result {
             Ok(()) => {
                 if options.verbose() {
                     log.end_with_success()?;
                 }
+                completed += 1;
             }
             Err(error) => {
                 if options.verbose() {
@@ -45,15 +752,200 @@ where
                 }
 
                 write_error(&mut io.stderr(), &error)?;
+                failed += 1;
 
-                if options.fail_at_end() {
-                    exit_code = EXIT_CODE_IO_ERROR;
-                } else {
-                    return Ok(EXIT_CODE_IO_ERROR);
+                if !options.fail_at_end() {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok((completed, failed))
+}
+
+fn get_policy<O: TransferOptions>(options: &O) -> TransferPolicy {
+    let overwrite = if options.no_clobber() {
+        OverwritePolicy::Skip
+    } else if options.fail_if_exists() {
+        OverwritePolicy::Fail
+    } else if let Some(backup_mode) = options.backup() {
+        OverwritePolicy::Backup(backup_mode)
+    } else if let Some(trash) = options.trash() {
+        OverwritePolicy::Trash(trash)
+    } else if options.update() {
+        OverwritePolicy::Update
+    } else {
+        OverwritePolicy::Overwrite
+    };
+
+    TransferPolicy {
+        overwrite,
+        merge_dirs: !options.no_merge(),
+        dereference: options.dereference(),
+        preserve: options.preserve(),
+        verify: options.verify(),
+    }
+}
+
+fn simulate_transfer(
+    steps: &[Step],
+    removed_paths: &[PathBuf],
+    working_dir: &Path,
+    policy: &TransferPolicy,
+    io: &Io,
+) -> Result {
+    let mut log = TransferLog::new(io.stdout());
+    let mut dry_run = DryRun::new(working_dir, policy);
+    let mut exit_code = EXIT_CODE_OK;
+
+    for Step {
+        src_path,
+        dst_path,
+        mode,
+        ..
+    } in steps
+    {
+        match dry_run.transfer(src_path, dst_path, *mode) {
+            Ok((action, created_dirs)) => {
+                for dir in &created_dirs {
+                    log.begin_dir_creation(dir)?;
+                    log.end_with_plan("would-create-dir")?;
                 }
+                log.begin_transfer(*mode, src_path, dst_path)?;
+                log.end_with_action(*mode, &action)?;
+            }
+            Err(error) => {
+                log.begin_transfer(*mode, src_path, dst_path)?;
+                log.end_with_error()?;
+                write_error(&mut io.stderr(), &error)?;
+                exit_code = EXIT_CODE_IO_ERROR;
+            }
+        }
+    }
+
+    for path in removed_paths {
+        log.begin_removal(path)?;
+
+        match dry_run.remove(path) {
+            Ok(()) => log.end_with_plan("would-remove")?,
+            Err(error) => {
+                log.end_with_error()?;
+                write_error(&mut io.stderr(), &error)?;
+                exit_code = EXIT_CODE_IO_ERROR;
             }
         }
     }
 
     Ok(exit_code)
 }
+
+fn rollback<O, W>(
+    journal: TransferJournal,
+    log: &mut TransferLog<W>,
+    options: &O,
+    io: &Io,
+) -> Result
+where
+    O: TransferOptions,
+    W: Write + WriteColor,
+{
+    let entries = journal.into_entries();
+    let mut reverted = 0;
+
+    for entry in entries.iter().rev() {
+        if options.verbose() {
+            log.begin_revert(entry)?;
+        }
+
+        match entry.revert() {
+            Ok(()) => {
+                if options.verbose() {
+                    log.end_with_success()?;
+                }
+                reverted += 1;
+            }
+            Err(error) => {
+                if options.verbose() {
+                    log.end_with_failure()?;
+                }
+                write_error(&mut io.stderr(), &error)?;
+            }
+        }
+    }
+
+    TransferLog::new(io.stderr()).write_rollback_result(reverted, entries.len())?;
+
+    if reverted == entries.len() {
+        Ok(EXIT_CODE_IO_ERROR)
+    } else {
+        Ok(EXIT_CODE_ROLLBACK_ERROR)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use termcolor::Color;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::testing::{ColoredOuput, OutputChunk};
+
+    #[test_case("y\nn\ny\n",    Some(&[0, 2]) ; "yes and no")]
+    #[test_case("x\ny\nn\nn\n", Some(&[0])    ; "invalid answer")]
+    #[test_case("n\na\n",       Some(&[1, 2]) ; "all")]
+    #[test_case("y\nq\n",       None          ; "quit")]
+    #[test_case("y\n",          None          ; "closed input")]
+    fn confirm_steps(input: &str, confirmed_indices: Option<&[usize]>) {
+        let pairs = vec![
+            ("a".into(), "b".into()),
+            ("c".into(), "d".into()),
+            ("e".into(), "f".into()),
+        ];
+        let working_dir = Path::new("/");
+        let steps = plan_transfer(&pairs, TransferMode::Move, working_dir).unwrap();
+
+        assert_eq!(
+            super::confirm_steps(
+                &steps,
+                &pairs,
+                TransferMode::Move,
+                working_dir,
+                &TransferPolicy::default(),
+                &mut input.as_bytes(),
+                ColoredOuput::new()
+            )
+            .unwrap(),
+            confirmed_indices.map(<[usize]>::to_vec)
+        );
+    }
+
+    #[test]
+    fn confirm_steps_staged() {
+        let pairs = vec![("a".into(), "b".into()), ("b".into(), "a".into())];
+        let working_dir = Path::new("/");
+        let steps = plan_transfer(&pairs, TransferMode::Move, working_dir).unwrap();
+        let mut output = ColoredOuput::new();
+
+        assert_eq!(
+            super::confirm_steps(
+                &steps,
+                &pairs,
+                TransferMode::Move,
+                working_dir,
+                &TransferPolicy::default(),
+                &mut "n\ny\n".as_bytes(),
+                &mut output
+            )
+            .unwrap(),
+            Some(vec![0])
+        );
+
+        let staged_chunks = output
+            .chunks()
+            .iter()
+            .filter(|chunk| *chunk == &OutputChunk::color(Color::Yellow, "staged"))
+            .count();
+        assert_eq!(staged_chunks, 1);
+    }
+}
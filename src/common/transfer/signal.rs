@@ -0,0 +1,43 @@
+use std::io;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub const EXIT_CODE_INTERRUPTED: i32 = 130;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+pub fn handle_interrupts() -> io::Result<()> {
+    ctrlc::set_handler(|| {
+        if interrupt(&INTERRUPTED) {
+            process::exit(EXIT_CODE_INTERRUPTED);
+        }
+    })
+    .map_err(|error| io::Error::other(format!("Cannot handle interrupt signals: {}", error)))
+}
+
+// The first signal lets the current transfer finish, the second one terminates immediately.
+fn interrupt(interrupted: &AtomicBool) -> bool {
+    interrupted.swap(true, Ordering::SeqCst)
+}
+
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+#[cfg(test)]
+mod tests {
+    use ntest::*;
+
+    use super::*;
+
+    #[test]
+    fn interrupt() {
+        // Global flag is left intact, so other tests in this binary are not interrupted.
+        let interrupted = AtomicBool::new(false);
+
+        assert_false!(super::interrupt(&interrupted));
+        assert_true!(interrupted.load(Ordering::SeqCst));
+        assert_true!(super::interrupt(&interrupted));
+        assert_false!(is_interrupted());
+    }
+}
@@ -0,0 +1,378 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{env, ffi::OsString};
+
+use fs_extra::error::{Error, ErrorKind, Result};
+
+use crate::transfer::fs::{make_temp_path, move_path, normalize_path, FileType};
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Trash {
+    // Home trash according to the freedesktop.org trash specification.
+    Home,
+    Dir(PathBuf),
+}
+
+pub fn move_to_trash(path: &Path, original_path: &Path, trash: &Trash) -> Result<PathBuf> {
+    match trash {
+        Trash::Home => move_to_home_trash(path, original_path, &get_home_trash_dir()?),
+        Trash::Dir(trash_dir) => move_to_trash_dir(path, original_path, trash_dir),
+    }
+}
+
+fn move_to_trash_dir(path: &Path, original_path: &Path, trash_dir: &Path) -> Result<PathBuf> {
+    fs::create_dir_all(trash_dir)?;
+
+    // Directory can be renamed only over a directory, anything else only over a non-directory.
+    let is_dir = FileType::from(path) == FileType::Dir;
+
+    for name in make_trash_names(original_path) {
+        let trashed_path = trash_dir.join(name);
+
+        // Atomic creation of an empty placeholder reserves the name.
+        let reserved = if is_dir {
+            fs::create_dir(&trashed_path)
+        } else {
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&trashed_path)
+                .map(|_| ())
+        };
+
+        match reserved {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error.into()),
+        }
+
+        // Path might be on a different file system, so it is moved next to the placeholder first.
+        let temp_path = make_temp_path(&trashed_path);
+        let result = move_path(path, &temp_path)
+            .and_then(|()| fs::rename(&temp_path, &trashed_path).map_err(Error::from));
+
+        return match result {
+            Ok(()) => Ok(trashed_path),
+            Err(error) => {
+                // The original error is more important than a failed cleanup.
+                if is_dir {
+                    fs::remove_dir(&trashed_path).ok();
+                } else {
+                    fs::remove_file(&trashed_path).ok();
+                }
+                Err(error)
+            }
+        };
+    }
+
+    unreachable!("Expected infinite iterator of names")
+}
+
+fn move_to_home_trash(path: &Path, original_path: &Path, trash_dir: &Path) -> Result<PathBuf> {
+    let files_dir = trash_dir.join("files");
+    let info_dir = trash_dir.join("info");
+
+    fs::create_dir_all(&files_dir)?;
+    fs::create_dir_all(&info_dir)?;
+
+    let info = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        encode_path(&normalize_path(original_path, &env::current_dir()?)),
+        format_deletion_date(SystemTime::now())?
+    );
+
+    for name in make_trash_names(original_path) {
+        let trashed_path = files_dir.join(&name);
+        if trashed_path.symlink_metadata().is_ok() {
+            continue;
+        }
+
+        let mut info_name = name;
+        info_name.push(".trashinfo");
+        let info_path = info_dir.join(info_name);
+
+        // Atomic creation of the info file reserves the name.
+        let mut info_file = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+        {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error.into()),
+        };
+
+        let result = info_file
+            .write_all(info.as_bytes())
+            .map_err(Error::from)
+            .and_then(|()| move_path(path, &trashed_path));
+
+        return match result {
+            Ok(()) => Ok(trashed_path),
+            Err(error) => {
+                // The original error is more important than a failed cleanup.
+                fs::remove_file(&info_path).ok();
+                Err(error)
+            }
+        };
+    }
+
+    unreachable!("Expected infinite iterator of names")
+}
+
+fn get_home_trash_dir() -> Result<PathBuf> {
+    if let Some(data_dir) = env::var_os("XDG_DATA_HOME").map(PathBuf::from) {
+        if data_dir.is_absolute() {
+            return Ok(data_dir.join("Trash"));
+        }
+    }
+
+    if let Some(home_dir) = env::var_os("HOME").filter(|home_dir| !home_dir.is_empty()) {
+        return Ok(PathBuf::from(home_dir).join(".local/share/Trash"));
+    }
+
+    Err(Error::new(
+        ErrorKind::Other,
+        "Cannot find home trash directory, neither XDG_DATA_HOME nor HOME is set",
+    ))
+}
+
+fn make_trash_names(path: &Path) -> impl Iterator<Item = OsString> {
+    let name = path.file_name().unwrap_or_default().to_owned();
+
+    (1..).map(move |number| {
+        let mut name = name.clone();
+        if number > 1 {
+            name.push(format!(".{}", number));
+        }
+        name
+    })
+}
+
+fn encode_path(path: &Path) -> String {
+    let mut result = String::new();
+
+    // Path is percent-encoded the same way as in URLs.
+    for byte in path_to_bytes(path).iter() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                result.push(*byte as char)
+            }
+            _ => result.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    result
+}
+
+#[cfg(unix)]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().as_bytes().to_vec()
+}
+
+#[cfg(unix)]
+fn format_deletion_date(time: SystemTime) -> Result<String> {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs()) as libc::time_t;
+
+    // Both pointers are valid for the duration of the call.
+    let mut tm = unsafe { std::mem::zeroed::<libc::tm>() };
+    if unsafe { libc::localtime_r(&seconds, &mut tm) }.is_null() {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    ))
+}
+
+#[cfg(not(unix))]
+fn format_deletion_date(_time: SystemTime) -> Result<String> {
+    Err(Error::new(
+        ErrorKind::Other,
+        "Home trash is not supported on this platform",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+    use ntest::*;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn move_to_trash_dir() {
+        let dir = TempDir::new().unwrap();
+        let trash_dir = dir.child("trash");
+
+        for content in &["1", "2", "3"] {
+            let file = dir.child("a");
+            file.write_str(content).unwrap();
+            move_to_trash(
+                file.path(),
+                file.path(),
+                &Trash::Dir(trash_dir.path().into()),
+            )
+            .unwrap();
+            file.assert(predicates::path::missing());
+        }
+
+        trash_dir.child("a").assert("1");
+        trash_dir.child("a.2").assert("2");
+        trash_dir.child("a.3").assert("3");
+    }
+
+    #[test]
+    fn move_to_trash_dir_parallel() {
+        let dir = TempDir::new().unwrap();
+        let trash = Trash::Dir(dir.child("trash").path().into());
+
+        std::thread::scope(|scope| {
+            for thread in 0..8 {
+                let dir = &dir;
+                let trash = &trash;
+                scope.spawn(move || {
+                    for index in 0..25 {
+                        let file = dir.child(format!("{}/{}/a", thread, index));
+                        file.write_str("1").unwrap();
+                        move_to_trash(file.path(), file.path(), trash).unwrap();
+                    }
+                });
+            }
+        });
+
+        assert_eq!(
+            fs::read_dir(dir.child("trash").path()).unwrap().count(),
+            200
+        );
+    }
+
+    #[test]
+    fn move_to_trash_dir_dir() {
+        let dir = TempDir::new().unwrap();
+        let trash_dir = dir.child("trash");
+        trash_dir.child("a").write_str("1").unwrap();
+
+        let src_dir = dir.child("a");
+        src_dir.child("b").write_str("2").unwrap();
+
+        assert_eq!(
+            move_to_trash(
+                src_dir.path(),
+                src_dir.path(),
+                &Trash::Dir(trash_dir.path().into()),
+            )
+            .ok(),
+            Some(trash_dir.child("a.2").path().into())
+        );
+
+        src_dir.assert(predicates::path::missing());
+        trash_dir.child("a").assert("1");
+        trash_dir.child("a.2/b").assert("2");
+    }
+
+    #[test]
+    fn move_to_trash_dir_cleanup() {
+        let dir = TempDir::new().unwrap();
+        let trash_dir = dir.child("trash");
+
+        assert_true!(move_to_trash(
+            dir.child("a").path(),
+            dir.child("a").path(),
+            &Trash::Dir(trash_dir.path().into()),
+        )
+        .is_err());
+        assert_eq!(fs::read_dir(trash_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn move_to_home_trash() {
+        let dir = TempDir::new().unwrap();
+        let trash_dir = dir.child("Trash");
+        let src_dir = dir.child("a b");
+        let file = src_dir.child("c%");
+        let original_path = normalize_path(file.path(), &env::current_dir().unwrap());
+
+        for (content, name) in &[("1", "c%"), ("2", "c%.2")] {
+            file.write_str(content).unwrap();
+            assert_eq!(
+                super::move_to_home_trash(file.path(), file.path(), trash_dir.path()).ok(),
+                Some(trash_dir.child("files").child(name).path().into())
+            );
+            file.assert(predicates::path::missing());
+        }
+
+        trash_dir.child("files/c%").assert("1");
+        trash_dir.child("files/c%.2").assert("2");
+
+        for name in &["c%.trashinfo", "c%.2.trashinfo"] {
+            let info = fs::read_to_string(trash_dir.child("info").child(name).path()).unwrap();
+            let lines = info.lines().collect::<Vec<&str>>();
+
+            assert_eq!(lines.len(), 3);
+            assert_eq!(lines[0], "[Trash Info]");
+            assert_eq!(lines[1], format!("Path={}", encode_path(&original_path)));
+            assert_true!(lines[1].ends_with("/a%20b/c%25"));
+            assert_true!(lines[2].starts_with("DeletionDate="));
+        }
+    }
+
+    #[test]
+    fn move_to_home_trash_cleanup() {
+        let dir = TempDir::new().unwrap();
+        let trash_dir = dir.child("Trash");
+
+        assert_true!(super::move_to_home_trash(
+            dir.child("a").path(),
+            dir.child("a").path(),
+            trash_dir.path()
+        )
+        .is_err());
+        assert_eq!(
+            fs::read_dir(trash_dir.child("info").path())
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+
+    #[test_case("/a/b",   "/a/b"       ; "plain")]
+    #[test_case("/a b/c", "/a%20b/c"   ; "space")]
+    #[test_case("/a%/č",  "/a%25/%C4%8D" ; "special")]
+    fn encode_path(path: &str, result: &str) {
+        assert_eq!(super::encode_path(Path::new(path)), result);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn format_deletion_date() {
+        let date = super::format_deletion_date(SystemTime::now()).unwrap();
+        let bytes = date.as_bytes();
+
+        assert_eq!(date.len(), 19);
+        assert_eq!(bytes[4], b'-');
+        assert_eq!(bytes[7], b'-');
+        assert_eq!(bytes[10], b'T');
+        assert_eq!(bytes[13], b':');
+        assert_eq!(bytes[16], b':');
+    }
+}
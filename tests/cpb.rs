@@ -1,7 +1,10 @@
 #[path = "utils.rs"]
 mod utils;
 
+use std::fs;
+
 use assert_fs::prelude::*;
+use indoc::indoc;
 use predicates::prelude::*;
 use utils::{cpb, temp_dir, write};
 
@@ -53,6 +56,55 @@ mod input_terminator {
     }
 }
 
+mod input_files {
+    use super::*;
+
+    #[test]
+    fn single() {
+        let dir = temp_dir();
+
+        write(dir.child("plan"), "<a|>b|");
+        let src_file = write(dir.child("a"), "1");
+        let dst_file = dir.child("b");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--read=|")
+            .arg("plan")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        src_file.assert("1");
+        dst_file.assert("1");
+    }
+}
+
+mod input_format {
+    use super::*;
+
+    #[test]
+    fn json_lines() {
+        let dir = temp_dir();
+
+        let src_file = write(dir.child("a\tb"), "1");
+        let dst_file = dir.child("c\nd");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--input-format=jsonl")
+            .write_stdin("{\"in\":\"a\\tb\",\"out\":\"c\\nd\"}\n")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        src_file.assert("1");
+        dst_file.assert("1");
+    }
+}
+
 mod failure {
     use super::*;
 
@@ -110,6 +162,168 @@ mod failure {
     }
 }
 
+mod remaining {
+    use super::*;
+
+    #[test]
+    fn failure_at_end() {
+        let dir = temp_dir();
+        let remaining_file = dir.child("remaining");
+        write(dir.child("a2"), "2");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--remaining")
+            .arg(remaining_file.path())
+            .arg("--fail-at-end")
+            .write_stdin("<a1\n>b1\n<a2\n>b2")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout("")
+            .stderr("error: Path 'a1' not found or user lacks permission\n");
+
+        dir.child("b2").assert("2");
+        remaining_file.assert(format!(
+            "<{}\n>{}\n",
+            dir.child("a1").path().to_string_lossy(),
+            dir.child("b1").path().to_string_lossy(),
+        ));
+    }
+}
+
+#[test]
+fn resume() {
+    let dir = temp_dir();
+    let state_file = dir.child("state");
+    let src_file_1 = write(dir.child("a1"), "1");
+    let src_file_2 = dir.child("a2");
+    let dst_file_1 = dir.child("b1");
+    let dst_file_2 = dir.child("b2");
+
+    let run = || {
+        cpb()
+            .current_dir(dir.path())
+            .arg("--resume")
+            .arg(state_file.path())
+            .arg("--fail-at-end")
+            .write_stdin("<a1\n>b1\n<a2\n>b2")
+            .assert()
+    };
+
+    run()
+        .failure()
+        .code(1)
+        .stdout("")
+        .stderr("error: Path 'a2' not found or user lacks permission\n");
+
+    dst_file_1.assert("1");
+    state_file.assert("0\n");
+
+    write(src_file_1, "3");
+    write(src_file_2, "2");
+    run().success().stdout("").stderr("");
+
+    dst_file_1.assert("1"); // Finished instruction is skipped
+    dst_file_2.assert("2");
+    state_file.assert("0\n1\n");
+
+    fs::remove_file(dst_file_1.path()).unwrap();
+    run().success().stdout("").stderr("");
+
+    dst_file_1.assert("3"); // Finished instruction without result is executed again
+    dst_file_2.assert("2");
+}
+
+#[test]
+fn resume_failed() {
+    let dir = temp_dir();
+    let state_file = dir.child("state");
+    write(dir.child("a1"), "1");
+    write(dir.child("a2"), "2");
+    write(dir.child("a4"), "4");
+    let dst_file_1 = dir.child("b1");
+
+    let run = || {
+        cpb()
+            .current_dir(dir.path())
+            .arg("--resume")
+            .arg(state_file.path())
+            .arg("--fail-at-end")
+            .write_stdin("<a1\n>b1\n<a2\n>b2\n<nope\n>b3\n<a4\n>b4")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout("")
+            .stderr("error: Path 'nope' not found or user lacks permission\n")
+    };
+
+    run();
+    state_file.assert("0\n1\n3\n");
+
+    fs::remove_file(dst_file_1.path()).unwrap();
+    run(); // Failed instruction does not prevent resume
+
+    dst_file_1.assert("1"); // Finished instruction without result is executed again
+    dir.child("b2").assert("2");
+    dir.child("b3").assert(predicates::path::missing());
+    dir.child("b4").assert("4");
+    state_file.assert("0\n1\n3\n");
+}
+
+#[test]
+fn resume_staged() {
+    let dir = temp_dir();
+    let state_file = dir.child("state");
+    let staged_file = write(dir.child(".a.12345678.tmp"), "1");
+    let file_1 = write(dir.child("a"), "2");
+    let file_2 = write(dir.child("b"), "2");
+
+    // Swap was interrupted after the first pair was staged and the second one finished.
+    write(
+        dir.child("state"),
+        &format!("0 \"{}\"\n1\n", staged_file.path().to_string_lossy()),
+    );
+
+    cpb()
+        .current_dir(dir.path())
+        .arg("--resume")
+        .arg(state_file.path())
+        .write_stdin("<a\n>b\n<b\n>a")
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+
+    file_1.assert("2");
+    file_2.assert("1");
+    staged_file.assert(predicates::path::missing());
+}
+
+#[test]
+fn resume_mismatch() {
+    let dir = temp_dir();
+    let state_file = write(dir.child("state"), "0\n");
+    let src_file = write(dir.child("c"), "2");
+
+    cpb()
+        .current_dir(dir.path())
+        .arg("--resume")
+        .arg(state_file.path())
+        .write_stdin("<a\n>b\n<c\n>d")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("")
+        .stderr(
+            "error: Resume state does not match file system, \
+             source 'a' of instruction #1 not found\n",
+        );
+
+    src_file.assert("2");
+    dir.child("d").assert(predicates::path::missing());
+}
+
 mod verbose {
     use super::*;
 
@@ -196,6 +410,822 @@ mod verbose {
     }
 }
 
+mod ordering {
+    use super::*;
+
+    #[test]
+    fn duplicate_destination() {
+        let dir = temp_dir();
+
+        let src_file_1 = write(dir.child("a1"), "1");
+        let src_file_2 = write(dir.child("a2"), "2");
+        let dst_file = dir.child("b");
+
+        cpb()
+            .current_dir(dir.path())
+            .write_stdin("<a1\n>b\n<a2\n>b")
+            .assert()
+            .failure()
+            .code(3)
+            .stdout("")
+            .stderr("error: Paths 'a1', 'a2' would be transferred to the same destination 'b'\n");
+
+        src_file_1.assert("1");
+        src_file_2.assert("2");
+        dst_file.assert(predicates::path::missing());
+    }
+
+    #[test]
+    fn chain() {
+        let dir = temp_dir();
+
+        let file_1 = write(dir.child("1"), "1");
+        let file_2 = write(dir.child("2"), "2");
+        let file_3 = dir.child("3");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--verbose")
+            .write_stdin("<1\n>2\n<2\n>3")
+            .assert()
+            .success()
+            .stdout("Copying '2' to '3' ... OK\nCopying '1' to '2' ... OK\n")
+            .stderr("");
+
+        file_1.assert("1");
+        file_2.assert("1");
+        file_3.assert("2");
+    }
+
+    #[test]
+    fn swap() {
+        let dir = temp_dir();
+
+        let file_1 = write(dir.child("a"), "1");
+        let file_2 = write(dir.child("b"), "2");
+
+        cpb()
+            .current_dir(dir.path())
+            .write_stdin("<a\n>b\n<b\n>a")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        file_1.assert("2");
+        file_2.assert("1");
+        assert_eq!(dir.path().read_dir().unwrap().count(), 2);
+    }
+
+    #[test]
+    fn rotation() {
+        let dir = temp_dir();
+
+        let file_1 = write(dir.child("1"), "1");
+        let file_2 = write(dir.child("2"), "2");
+        let file_3 = write(dir.child("3"), "3");
+
+        cpb()
+            .current_dir(dir.path())
+            .write_stdin("<1\n>2\n<2\n>3\n<3\n>1")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        file_1.assert("3");
+        file_2.assert("1");
+        file_3.assert("2");
+        assert_eq!(dir.path().read_dir().unwrap().count(), 3);
+    }
+}
+
+mod atomic {
+    use super::*;
+
+    #[test]
+    fn rollback() {
+        let dir = temp_dir();
+
+        let src_file_1 = write(dir.child("a1"), "1");
+        let src_file_2 = dir.child("a2");
+
+        let dst_file_1 = write(dir.child("b1"), "2");
+        let dst_file_2 = dir.child("b2");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--atomic")
+            .arg("--verbose")
+            .write_stdin("<a1\n>b1\n<a2\n>b2")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout(indoc! {"
+                Copying 'a1' to 'b1' ... OK
+                Copying 'a2' to 'b2' ... FAILED
+                Reverting copy of 'a1' to 'b1' ... OK
+            "})
+            .stderr(indoc! {"
+                error: Path 'a2' not found or user lacks permission
+                Rolled back 1 of 1 completed transfer(s) ... OK
+            "});
+
+        src_file_1.assert("1");
+        src_file_2.assert(predicates::path::missing());
+
+        dst_file_1.assert("2");
+        dst_file_2.assert(predicates::path::missing());
+
+        assert_eq!(dir.path().read_dir().unwrap().count(), 2);
+    }
+
+    #[test]
+    fn commit() {
+        let dir = temp_dir();
+
+        let src_file = write(dir.child("a"), "1");
+        let dst_file = write(dir.child("b"), "2");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--atomic")
+            .write_stdin("<a\n>b")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        src_file.assert("1");
+        dst_file.assert("1");
+
+        assert_eq!(dir.path().read_dir().unwrap().count(), 2);
+    }
+}
+
+mod link {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn hard_link() {
+        let dir = temp_dir();
+        let src_file = write(dir.child("a"), "1");
+        let dst_file = dir.child("b/c");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--link")
+            .arg("--verbose")
+            .write_stdin("<a\n>b/c")
+            .assert()
+            .success()
+            .stdout("Linking 'a' to 'b/c' ... OK\n")
+            .stderr("");
+
+        src_file.assert("1");
+        dst_file.assert("1");
+        assert!(same_file::is_same_file(src_file.path(), dst_file.path()).unwrap());
+    }
+
+    #[test]
+    fn relative_symlink() {
+        let dir = temp_dir();
+        write(dir.child("a"), "1");
+        let dst_file = dir.child("b/c");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--symlink")
+            .arg("--relative")
+            .write_stdin("<a\n>b/c")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        dst_file.assert("1");
+        assert_eq!(
+            fs::read_link(dst_file.path()).unwrap(),
+            std::path::Path::new("../a")
+        );
+    }
+
+    #[test]
+    fn symlink_swap() {
+        let dir = temp_dir();
+        let file_1 = write(dir.child("a"), "1");
+        let file_2 = write(dir.child("b"), "2");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--symlink")
+            .write_stdin("<a\n>b\n<b\n>a")
+            .assert()
+            .failure()
+            .code(3)
+            .stdout("")
+            .stderr("error: Paths 'a' -> 'b' -> 'a' form a cycle\n");
+
+        file_1.assert("1");
+        file_2.assert("2");
+        assert!(!fs::symlink_metadata(file_1.path())
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(!fs::symlink_metadata(file_2.path())
+            .unwrap()
+            .file_type()
+            .is_symlink());
+    }
+
+    #[test]
+    fn symlink_chain() {
+        let dir = temp_dir();
+        let file_1 = write(dir.child("1"), "1");
+        let file_2 = write(dir.child("2"), "2");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--symlink")
+            .write_stdin("<1\n>2\n<2\n>3")
+            .assert()
+            .failure()
+            .code(3)
+            .stdout("")
+            .stderr(
+                "error: Path '2' would be overwritten by '1' before being transferred to '3'\n",
+            );
+
+        file_1.assert("1");
+        file_2.assert("2");
+        dir.child("3").assert(predicates::path::missing());
+    }
+
+    #[test]
+    fn hard_link_chain_reversed() {
+        let dir = temp_dir();
+        let file_1 = write(dir.child("1"), "1");
+        let file_2 = write(dir.child("2"), "2");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--link")
+            .write_stdin("<2\n>3\n<1\n>2")
+            .assert()
+            .failure()
+            .code(3)
+            .stdout("")
+            .stderr("error: Path '2' would be overwritten by '1' after being linked to '3'\n");
+
+        file_1.assert("1");
+        file_2.assert("2");
+        dir.child("3").assert(predicates::path::missing());
+    }
+}
+
+mod symlinks {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn keep() {
+        let dir = temp_dir();
+        write(dir.child("a/b"), "1");
+        std::os::unix::fs::symlink("b", dir.child("a/c").path()).unwrap();
+
+        cpb()
+            .current_dir(dir.path())
+            .write_stdin("<a\n>d")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        dir.child("d/b").assert("1");
+        assert_eq!(
+            fs::read_link(dir.child("d/c").path()).unwrap(),
+            Path::new("b")
+        );
+    }
+
+    #[test]
+    fn dereference() {
+        let dir = temp_dir();
+        write(dir.child("a/b"), "1");
+        std::os::unix::fs::symlink("b", dir.child("a/c").path()).unwrap();
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--dereference")
+            .write_stdin("<a\n>d")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        dir.child("d/b").assert("1");
+        dir.child("d/c").assert("1");
+        assert!(fs::read_link(dir.child("d/c").path()).is_err());
+    }
+}
+
+mod preserve {
+    use filetime::FileTime;
+
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        let dir = temp_dir();
+        let src_file = write(dir.child("a"), "1");
+        let dst_file = dir.child("b");
+        let time = FileTime::from_unix_time(1_000_000_000, 0);
+        filetime::set_file_mtime(src_file.path(), time).unwrap();
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--preserve=timestamps")
+            .write_stdin("<a\n>b")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        dst_file.assert("1");
+        let metadata = dst_file.path().metadata().unwrap();
+        assert_eq!(FileTime::from_last_modification_time(&metadata), time);
+    }
+}
+
+#[test]
+fn verify() {
+    let dir = temp_dir();
+    write(dir.child("a/b"), "1");
+    write(dir.child("a/c/d"), "2");
+
+    cpb()
+        .current_dir(dir.path())
+        .arg("--verify")
+        .arg("--verbose")
+        .write_stdin("<a\n>e")
+        .assert()
+        .success()
+        .stdout("Copying 'a' to 'e' ... OK\n")
+        .stderr("");
+
+    dir.child("e/b").assert("1");
+    dir.child("e/c/d").assert("2");
+}
+
+#[test]
+fn progress() {
+    let dir = temp_dir();
+    write(dir.child("a/b"), "1");
+    write(dir.child("a/c/d"), "2");
+    write(dir.child("e"), "34");
+
+    let stderr = indoc! {r"
+        ^\[1/2\] 1 B / 4 B \(25%\)( at \S+ \S+/s(, ETA \S+)?)? 'f/(b|c/d)'
+        \[1/2\] 2 B / 4 B \(50%\)( at \S+ \S+/s(, ETA \S+)?)? 'f/(b|c/d)'
+        \[2/2\] 4 B / 4 B \(100%\)( at \S+ \S+/s(, ETA \S+)?)? 'g'
+        $"};
+
+    cpb()
+        .current_dir(dir.path())
+        .arg("--progress")
+        .write_stdin("<a\n>f\n<e\n>g")
+        .assert()
+        .success()
+        .stdout("")
+        .stderr(predicate::str::is_match(stderr).unwrap());
+
+    dir.child("f/b").assert("1");
+    dir.child("f/c/d").assert("2");
+    dir.child("g").assert("34");
+}
+
+#[test]
+fn report() {
+    let dir = temp_dir();
+    write(dir.child("a"), "12");
+    write(dir.child("b"), "3");
+
+    cpb()
+        .current_dir(dir.path())
+        .arg("--report=jsonl")
+        .arg("--fail-at-end")
+        .arg("--no-clobber")
+        .write_stdin("<a\n>c\n<x\n>d\n<b\n>a")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(indoc! {r#"
+            {"src":"a","dst":"c","action":"copy","result":"created","error":null}
+            {"src":"x","dst":"d","action":"copy","result":"failed","error":"Path 'x' not found or user lacks permission"}
+            {"src":"b","dst":"a","action":"copy","result":"skipped","error":null}
+            {"summary":{"completed":1,"skipped":1,"failed":1,"pending":0,"bytes":2}}
+        "#})
+        .stderr("error: Path 'x' not found or user lacks permission\n");
+
+    dir.child("c").assert("12");
+}
+
+mod jobs {
+    use super::*;
+
+    #[test]
+    fn ordered_output() {
+        let dir = temp_dir();
+        let mut input = String::new();
+        let mut output = String::new();
+
+        for index in 0..20 {
+            write(dir.child(format!("a{}", index)), &index.to_string());
+            input.push_str(&format!("<a{}\n>b/{}\n", index, index));
+            output.push_str(&format!("Copying 'a{}' to 'b/{}' ... OK\n", index, index));
+        }
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--jobs=4")
+            .arg("--verbose")
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout(output)
+            .stderr("");
+
+        for index in 0..20 {
+            dir.child(format!("b/{}", index)).assert(index.to_string());
+        }
+    }
+
+    #[test]
+    fn failure_at_end() {
+        let dir = temp_dir();
+        write(dir.child("a"), "1");
+        write(dir.child("b"), "2");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--jobs=2")
+            .arg("--fail-at-end")
+            .arg("--verbose")
+            .write_stdin("<x\n>y\n<a\n>c\n<b\n>d")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout(indoc! {"
+                Copying 'x' to 'y' ... FAILED
+                Copying 'a' to 'c' ... OK
+                Copying 'b' to 'd' ... OK
+            "})
+            .stderr("error: Path 'x' not found or user lacks permission\n");
+
+        dir.child("y").assert(predicates::path::missing());
+        dir.child("c").assert("1");
+        dir.child("d").assert("2");
+    }
+}
+
+mod overwrite {
+    use super::*;
+
+    #[test]
+    fn no_clobber() {
+        let dir = temp_dir();
+
+        let src_file = write(dir.child("a"), "1");
+        let dst_file = write(dir.child("b"), "2");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--no-clobber")
+            .write_stdin("<a\n>b")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        src_file.assert("1");
+        dst_file.assert("2");
+    }
+
+    #[test]
+    fn fail_if_exists() {
+        let dir = temp_dir();
+
+        let src_file = write(dir.child("a"), "1");
+        let dst_file = write(dir.child("b"), "2");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--fail-if-exists")
+            .write_stdin("<a\n>b")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout("")
+            .stderr("error: Path 'b' already exists\n");
+
+        src_file.assert("1");
+        dst_file.assert("2");
+    }
+
+    #[test]
+    fn backup() {
+        let dir = temp_dir();
+
+        let src_file = write(dir.child("a"), "1");
+        let dst_file = write(dir.child("b"), "2");
+        let backup_file = write(dir.child("b.~1~"), "3");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--backup=numbered")
+            .write_stdin("<a\n>b")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        src_file.assert("1");
+        dst_file.assert("1");
+        backup_file.assert("3");
+        dir.child("b.~2~").assert("2");
+    }
+
+    #[test]
+    fn trash() {
+        let dir = temp_dir();
+
+        let src_file = write(dir.child("a"), "1");
+        let dst_file = write(dir.child("b"), "2");
+        let trash_dir = dir.child("trash");
+        write(trash_dir.child("b"), "3");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--trash=trash")
+            .write_stdin("<a\n>b")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        src_file.assert("1");
+        dst_file.assert("1");
+        trash_dir.child("b").assert("3");
+        trash_dir.child("b.2").assert("2");
+    }
+
+    #[test]
+    fn no_merge() {
+        let dir = temp_dir();
+
+        let src_dir = dir.child("a");
+        let dst_dir = dir.child("b");
+        write(src_dir.child("c"), "1");
+        dst_dir.create_dir_all().unwrap();
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--no-merge")
+            .write_stdin("<a\n>b")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout("")
+            .stderr("error: Cannot merge directory 'a' into existing directory 'b'\n");
+
+        src_dir.child("c").assert("1");
+        dst_dir.child("c").assert(predicates::path::missing());
+    }
+}
+
+mod dry_run {
+    use super::*;
+
+    #[test]
+    fn actions() {
+        let dir = temp_dir();
+
+        let src_file_1 = write(dir.child("a1"), "1");
+        let src_file_2 = write(dir.child("a2"), "2");
+        let src_dir = dir.child("a3");
+        write(src_dir.child("f"), "3");
+
+        let dst_file_1 = write(dir.child("b1"), "4");
+        let dst_file_2 = dir.child("b2/c");
+        let dst_dir = dir.child("b3");
+        dst_dir.create_dir_all().unwrap();
+
+        dir.child("a5").create_dir_all().unwrap();
+        write(dir.child("b5"), "5");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--dry-run")
+            .write_stdin("<a1\n>b1\n<a2\n>b2/c\n<a3\n>b3\n<a4\n>b4\n<a5\n>b5")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout(indoc! {"
+                Copying 'a1' to 'b1' ... would-overwrite
+                Creating directory 'b2' ... would-create-dir
+                Copying 'a2' to 'b2/c' ... would-copy
+                Copying 'a3' to 'b3' ... would-merge-dir
+                Copying 'a4' to 'b4' ... error
+                Copying 'a5' to 'b5' ... error
+            "})
+            .stderr(indoc! {"
+                error: Path 'a4' not found or user lacks permission
+                error: Cannot to overwrite file 'b5' with directory 'a5'
+            "});
+
+        src_file_1.assert("1");
+        src_file_2.assert("2");
+        src_dir.child("f").assert("3");
+
+        dst_file_1.assert("4");
+        dst_file_2.assert(predicates::path::missing());
+        dir.child("b2").assert(predicates::path::missing());
+        dst_dir.child("f").assert(predicates::path::missing());
+    }
+
+    #[test]
+    fn chain() {
+        let dir = temp_dir();
+        let src_file = write(dir.child("a"), "1");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--dry-run")
+            .write_stdin("<a\n>b\n<b\n>c")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout(indoc! {"
+                Copying 'b' to 'c' ... error
+                Copying 'a' to 'b' ... would-copy
+            "})
+            .stderr("error: Path 'b' not found or user lacks permission\n");
+
+        src_file.assert("1");
+    }
+}
+
+mod journal {
+    use super::*;
+
+    #[test]
+    fn undo() {
+        let dir = temp_dir();
+        let journal_file = dir.child("journal");
+
+        let src_file = write(dir.child("a"), "1");
+        let dst_file = dir.child("b/c");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--journal")
+            .arg(journal_file.path())
+            .write_stdin("<a\n>b/c")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        src_file.assert("1");
+        dst_file.assert("1");
+
+        journal_file.assert(format!(
+            "<{}\n>{}\n",
+            dst_file.path().to_string_lossy(),
+            src_file.path().to_string_lossy(),
+        ));
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--undo")
+            .arg(journal_file.path())
+            .arg("--verbose")
+            .assert()
+            .success()
+            .stdout(format!(
+                "Removing '{}' ... OK\n",
+                dst_file.path().to_string_lossy(),
+            ))
+            .stderr("");
+
+        src_file.assert("1");
+        dst_file.assert(predicates::path::missing());
+    }
+
+    #[test]
+    fn undo_report() {
+        let dir = temp_dir();
+        let journal_file = write(dir.child("journal"), "<b\n>a\n");
+        write(dir.child("a"), "1");
+        write(dir.child("b"), "1");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--undo")
+            .arg(journal_file.path())
+            .arg("--report=jsonl")
+            .assert()
+            .success()
+            .stdout(indoc! {r#"
+                {"src":"b","dst":null,"action":"remove","result":"removed","error":null}
+                {"summary":{"completed":1,"skipped":0,"failed":0,"pending":0,"bytes":0}}
+            "#})
+            .stderr("");
+
+        dir.child("a").assert("1");
+        dir.child("b").assert(predicates::path::missing());
+    }
+
+    #[test]
+    fn undo_overwrite() {
+        let dir = temp_dir();
+        let journal_file = dir.child("journal");
+
+        let src_file = write(dir.child("a"), "1");
+        let dst_file = write(dir.child("b"), "2");
+        let new_file = dir.child("c");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--journal")
+            .arg(journal_file.path())
+            .write_stdin("<a\n>b\n<a\n>c")
+            .assert()
+            .success();
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--undo")
+            .arg(journal_file.path())
+            .arg("--verbose")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout(format!(
+                "Removing '{}' ... OK\n",
+                new_file.path().to_string_lossy(),
+            ))
+            .stderr(format!(
+                "error: Path '{}' existed before and cannot be restored\n",
+                dst_file.path().to_string_lossy(),
+            ));
+
+        src_file.assert("1");
+        dst_file.assert("1");
+        new_file.assert(predicates::path::missing());
+    }
+
+    #[test]
+    fn undo_cycle() {
+        let dir = temp_dir();
+        let journal_file = dir.child("journal");
+
+        let file_1 = write(dir.child("a"), "1");
+        let file_2 = write(dir.child("b"), "2");
+        let file_3 = dir.child("c");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--journal")
+            .arg(journal_file.path())
+            .write_stdin("<a\n>b\n<b\n>a\n<a\n>c")
+            .assert()
+            .success();
+
+        file_1.assert("2");
+        file_2.assert("1");
+        file_3.assert("1");
+
+        cpb()
+            .current_dir(dir.path())
+            .arg("--undo")
+            .arg(journal_file.path())
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        file_1.assert("1");
+        file_2.assert("2");
+        file_3.assert(predicates::path::missing());
+        assert_eq!(dir.path().read_dir().unwrap().count(), 3);
+    }
+}
+
 #[test]
 fn help() {
     cpb()
@@ -2,8 +2,9 @@
 mod utils;
 
 use assert_fs::prelude::*;
+use indoc::indoc;
 use predicates::prelude::*;
-use utils::{mvb, temp_dir, write};
+use utils::{mvb, rew, temp_dir, write};
 
 #[test]
 fn no_input() {
@@ -51,6 +52,248 @@ mod input_terminator {
         src_file.assert(predicates::path::missing());
         dst_file.assert("1");
     }
+
+    #[test]
+    fn custom() {
+        let dir = temp_dir();
+
+        let src_file = write(dir.child("a"), "1");
+        let dst_file = dir.child("b");
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--read=|")
+            .write_stdin("<a|>b|")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        src_file.assert(predicates::path::missing());
+        dst_file.assert("1");
+    }
+}
+
+#[cfg(unix)]
+mod invalid_utf8 {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    use super::*;
+
+    #[test]
+    fn fail() {
+        mvb()
+            .write_stdin(&b"<a\xFF\n>b"[..])
+            .assert()
+            .failure()
+            .code(1)
+            .stdout("")
+            .stderr("error: Value does not have UTF-8 encoding (offset 2)\n");
+    }
+
+    #[test]
+    fn escape_diff() {
+        let dir = temp_dir();
+
+        let src_file = write(dir.child(OsStr::from_bytes(b"a\xFF")), "1");
+        let dst_file = dir.child(OsStr::from_bytes(b"b\xFF%25"));
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--invalid-utf8=escape")
+            .write_stdin(&b"<a\xFF\n>b\xFF%25"[..])
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        src_file.assert(predicates::path::missing());
+        dst_file.assert("1");
+    }
+
+    #[test]
+    fn escape_report() {
+        let dir = temp_dir();
+
+        let src_file = write(dir.child(OsStr::from_bytes(b"a\xFF")), "1");
+        let dst_file = dir.child(OsStr::from_bytes(b"b\xFF%"));
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--invalid-utf8=escape")
+            .arg("--report=jsonl")
+            .write_stdin(&b"<a\xFF\n>b\xFF%"[..])
+            .assert()
+            .success()
+            .stdout(indoc! {r#"
+                {"src":"a%FF","dst":"b%FF%25","action":"move","result":"created","error":null}
+                {"summary":{"completed":1,"skipped":0,"failed":0,"pending":0,"bytes":0}}
+            "#})
+            .stderr("");
+
+        src_file.assert(predicates::path::missing());
+        dst_file.assert("1");
+    }
+
+    #[test]
+    fn escape_json_lines() {
+        let dir = temp_dir();
+
+        let src_file = write(dir.child(OsStr::from_bytes(b"a\xFF")), "1");
+        let dst_file = dir.child(OsStr::from_bytes(b"a\xFF.b"));
+
+        let output = rew()
+            .current_dir(dir.path())
+            .arg("--invalid-utf8=escape")
+            .arg("--json-lines")
+            .arg("{}.b")
+            .write_stdin(&b"a\xFF"[..])
+            .output()
+            .unwrap();
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--invalid-utf8=escape")
+            .arg("--input-format=jsonl")
+            .write_stdin(output.stdout)
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        src_file.assert(predicates::path::missing());
+        dst_file.assert("1");
+    }
+}
+
+mod input_files {
+    use super::*;
+
+    #[test]
+    fn multiple() {
+        let dir = temp_dir();
+
+        write(dir.child("plan_1"), "<a\n>b\n");
+        write(dir.child("plan_2"), "<e\n>f\n");
+        write(dir.child("a"), "1");
+        write(dir.child("c"), "2");
+        write(dir.child("e"), "3");
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("plan_1")
+            .arg("-")
+            .arg("plan_2")
+            .write_stdin("<c\n>d\n")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        dir.child("b").assert("1");
+        dir.child("d").assert("2");
+        dir.child("f").assert("3");
+    }
+
+    #[test]
+    fn missing() {
+        let dir = temp_dir();
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("plan")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout("")
+            .stderr(predicate::str::starts_with(
+                "error: Cannot read instructions from 'plan': ",
+            ));
+    }
+
+    #[test]
+    fn invalid() {
+        let dir = temp_dir();
+
+        write(dir.child("plan"), "<a\n");
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("plan")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout("")
+            .stderr(
+                "error: Cannot read instructions from 'plan': Expected '>' (item #2 at offset 3)\n",
+            );
+    }
+}
+
+mod input_format {
+    use super::*;
+
+    #[test]
+    fn json_lines() {
+        let dir = temp_dir();
+
+        let src_file = write(dir.child("a\nb"), "1");
+        let dst_file = dir.child("c\"d");
+
+        let output = rew()
+            .current_dir(dir.path())
+            .arg("--read-nul")
+            .arg("--json-lines")
+            .arg("c\"d")
+            .write_stdin("a\nb\0")
+            .output()
+            .unwrap();
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--input-format=jsonl")
+            .write_stdin(output.stdout)
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        src_file.assert(predicates::path::missing());
+        dst_file.assert("1");
+    }
+
+    #[test]
+    fn json_lines_invalid() {
+        mvb()
+            .arg("--input-format=jsonl")
+            .write_stdin("{\"in\":\"a\"}")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout("")
+            .stderr("error: Expected string field 'out' (item #1 at offset 0)\n");
+    }
+
+    #[test]
+    fn tsv() {
+        let dir = temp_dir();
+
+        let src_file = write(dir.child("a b"), "1");
+        let dst_file = dir.child("c");
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--input-format=tsv")
+            .write_stdin("a b\tc\n")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        src_file.assert(predicates::path::missing());
+        dst_file.assert("1");
+    }
 }
 
 mod failure {
@@ -110,6 +353,190 @@ mod failure {
     }
 }
 
+#[test]
+fn progress() {
+    let dir = temp_dir();
+    write(dir.child("a"), "1");
+    write(dir.child("b"), "2");
+
+    mvb()
+        .current_dir(dir.path())
+        .arg("--progress")
+        .write_stdin("<a\n>c\n<b\n>d")
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("[1/2] 'c'\n[2/2] 'd'\n");
+
+    dir.child("c").assert("1");
+    dir.child("d").assert("2");
+}
+
+#[test]
+fn progress_swap() {
+    let dir = temp_dir();
+    write(dir.child("a"), "1");
+    write(dir.child("b"), "2");
+
+    let stderr = mvb()
+        .current_dir(dir.path())
+        .arg("--progress")
+        .write_stdin("<a\n>b\n<b\n>a")
+        .assert()
+        .success()
+        .stdout("")
+        .get_output()
+        .stderr
+        .clone();
+
+    // Staging to a temporary path is not counted as an instruction.
+    let stderr = String::from_utf8(stderr).unwrap();
+    let counts = stderr
+        .lines()
+        .map(|line| line.split(' ').next().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(counts, ["[1/2]", "[2/2]"]);
+
+    dir.child("a").assert("2");
+    dir.child("b").assert("1");
+}
+
+#[test]
+fn jobs() {
+    let dir = temp_dir();
+    write(dir.child("a"), "1");
+    write(dir.child("b"), "2");
+    write(dir.child("c/d"), "3");
+
+    mvb()
+        .current_dir(dir.path())
+        .arg("--jobs=3")
+        .write_stdin("<a\n>b\n<b\n>a\n<c\n>e\n<e/d\n>f")
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+
+    dir.child("a").assert("2");
+    dir.child("b").assert("1");
+    dir.child("c").assert(predicates::path::missing());
+    dir.child("e").assert(predicates::path::is_dir());
+    dir.child("f").assert("3");
+}
+
+mod remaining {
+    use super::*;
+
+    #[test]
+    fn immediate_failure() {
+        let dir = temp_dir();
+        let remaining_file = dir.child("remaining");
+        write(dir.child("a2"), "2");
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--remaining")
+            .arg(remaining_file.path())
+            .write_stdin("<a1\n>b1\n<a2\n>b2")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout("")
+            .stderr("error: Path 'a1' not found or user lacks permission\n");
+
+        remaining_file.assert(format!(
+            "<{}\n>{}\n<{}\n>{}\n",
+            dir.child("a1").path().to_string_lossy(),
+            dir.child("b1").path().to_string_lossy(),
+            dir.child("a2").path().to_string_lossy(),
+            dir.child("b2").path().to_string_lossy(),
+        ));
+    }
+
+    #[test]
+    fn failure_at_end() {
+        let dir = temp_dir();
+        let remaining_file = dir.child("remaining");
+        write(dir.child("a2"), "2");
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--remaining")
+            .arg(remaining_file.path())
+            .arg("--fail-at-end")
+            .write_stdin("<a1\n>b1\n<a2\n>b2")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout("")
+            .stderr("error: Path 'a1' not found or user lacks permission\n");
+
+        dir.child("b2").assert("2");
+        remaining_file.assert(format!(
+            "<{}\n>{}\n",
+            dir.child("a1").path().to_string_lossy(),
+            dir.child("b1").path().to_string_lossy(),
+        ));
+    }
+}
+
+#[test]
+fn resume_staged() {
+    let dir = temp_dir();
+    let state_file = dir.child("state");
+    let staged_file = write(dir.child(".a.12345678.tmp"), "1");
+    let file_1 = write(dir.child("a"), "2");
+    let file_2 = dir.child("b");
+
+    // Swap was interrupted after the first pair was staged and the second one finished.
+    write(
+        dir.child("state"),
+        &format!("0 \"{}\"\n1\n", staged_file.path().to_string_lossy()),
+    );
+
+    mvb()
+        .current_dir(dir.path())
+        .arg("--resume")
+        .arg(state_file.path())
+        .write_stdin("<a\n>b\n<b\n>a")
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+
+    file_1.assert("2");
+    file_2.assert("1");
+    staged_file.assert(predicates::path::missing());
+    state_file.assert(format!(
+        "0 \"{}\"\n1\n0\n",
+        staged_file.path().to_string_lossy()
+    ));
+}
+
+#[test]
+fn report_cycle() {
+    let dir = temp_dir();
+    let file_1 = write(dir.child("a"), "1");
+    let file_2 = write(dir.child("b"), "2");
+
+    // Staging of the cycle is reported as a part of its pair.
+    mvb()
+        .current_dir(dir.path())
+        .arg("--report=jsonl")
+        .write_stdin("<a\n>b\n<b\n>a")
+        .assert()
+        .success()
+        .stdout(indoc! {r#"
+            {"src":"b","dst":"a","action":"move","result":"created","error":null}
+            {"src":"a","dst":"b","action":"move","result":"created","error":null}
+            {"summary":{"completed":2,"skipped":0,"failed":0,"pending":0,"bytes":0}}
+        "#})
+        .stderr("");
+
+    file_1.assert("2");
+    file_2.assert("1");
+}
+
 mod verbose {
     use super::*;
 
@@ -193,6 +620,677 @@ mod verbose {
     }
 }
 
+mod ordering {
+    use super::*;
+
+    #[test]
+    fn duplicate_destination() {
+        let dir = temp_dir();
+
+        let src_file_1 = write(dir.child("a1"), "1");
+        let src_file_2 = write(dir.child("a2"), "2");
+        let dst_file = dir.child("b");
+
+        mvb()
+            .current_dir(dir.path())
+            .write_stdin("<a1\n>b\n<a2\n>b")
+            .assert()
+            .failure()
+            .code(3)
+            .stdout("")
+            .stderr("error: Paths 'a1', 'a2' would be transferred to the same destination 'b'\n");
+
+        src_file_1.assert("1");
+        src_file_2.assert("2");
+        dst_file.assert(predicates::path::missing());
+    }
+
+    #[test]
+    fn chain() {
+        let dir = temp_dir();
+
+        let file_1 = write(dir.child("1"), "1");
+        let file_2 = write(dir.child("2"), "2");
+        let file_3 = dir.child("3");
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--verbose")
+            .write_stdin("<1\n>2\n<2\n>3")
+            .assert()
+            .success()
+            .stdout("Moving '2' to '3' ... OK\nMoving '1' to '2' ... OK\n")
+            .stderr("");
+
+        file_1.assert(predicates::path::missing());
+        file_2.assert("1");
+        file_3.assert("2");
+    }
+
+    #[test]
+    fn swap() {
+        let dir = temp_dir();
+
+        let file_1 = write(dir.child("a"), "1");
+        let file_2 = write(dir.child("b"), "2");
+
+        mvb()
+            .current_dir(dir.path())
+            .write_stdin("<a\n>b\n<b\n>a")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        file_1.assert("2");
+        file_2.assert("1");
+        assert_eq!(dir.path().read_dir().unwrap().count(), 2);
+    }
+
+    #[test]
+    fn rotation() {
+        let dir = temp_dir();
+
+        let file_1 = write(dir.child("1"), "1");
+        let file_2 = write(dir.child("2"), "2");
+        let file_3 = write(dir.child("3"), "3");
+
+        mvb()
+            .current_dir(dir.path())
+            .write_stdin("<1\n>2\n<2\n>3\n<3\n>1")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        file_1.assert("3");
+        file_2.assert("1");
+        file_3.assert("2");
+        assert_eq!(dir.path().read_dir().unwrap().count(), 3);
+    }
+}
+
+mod atomic {
+    use super::*;
+
+    #[test]
+    fn rollback() {
+        let dir = temp_dir();
+
+        let src_file_1 = write(dir.child("a1"), "1");
+        let src_file_2 = dir.child("a2");
+
+        let dst_file_1 = write(dir.child("b1"), "2");
+        let dst_file_2 = dir.child("b2");
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--atomic")
+            .arg("--verbose")
+            .write_stdin("<a1\n>b1\n<a2\n>b2")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout(indoc! {"
+                Moving 'a1' to 'b1' ... OK
+                Moving 'a2' to 'b2' ... FAILED
+                Reverting move of 'a1' to 'b1' ... OK
+            "})
+            .stderr(indoc! {"
+                error: Path 'a2' not found or user lacks permission
+                Rolled back 1 of 1 completed transfer(s) ... OK
+            "});
+
+        src_file_1.assert("1");
+        src_file_2.assert(predicates::path::missing());
+
+        dst_file_1.assert("2");
+        dst_file_2.assert(predicates::path::missing());
+
+        assert_eq!(dir.path().read_dir().unwrap().count(), 2);
+    }
+
+    #[test]
+    fn commit() {
+        let dir = temp_dir();
+
+        let src_file = write(dir.child("a"), "1");
+        let dst_file = write(dir.child("b"), "2");
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--atomic")
+            .write_stdin("<a\n>b")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        src_file.assert(predicates::path::missing());
+        dst_file.assert("1");
+
+        assert_eq!(dir.path().read_dir().unwrap().count(), 1);
+    }
+}
+
+mod symlinks {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn dangling() {
+        let dir = temp_dir();
+        std::os::unix::fs::symlink("x", dir.child("a").path()).unwrap();
+
+        mvb()
+            .current_dir(dir.path())
+            .write_stdin("<a\n>b/c")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        dir.child("a").assert(predicates::path::missing());
+        assert_eq!(
+            fs::read_link(dir.child("b/c").path()).unwrap(),
+            Path::new("x")
+        );
+    }
+
+    #[test]
+    fn dereference() {
+        let dir = temp_dir();
+        let dst_file = dir.child("c");
+        assert_dereference(dir.path(), dst_file.path());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn dereference_across_file_systems() {
+        // Shared memory is usually a different file system than the temporary directory.
+        let other_dir = Path::new("/dev/shm").join(format!("mvb-{}", std::process::id()));
+        if fs::create_dir(&other_dir).is_err() {
+            return;
+        }
+
+        let dir = temp_dir();
+        assert_dereference(dir.path(), &other_dir.join("c"));
+        fs::remove_dir_all(&other_dir).unwrap();
+    }
+
+    fn assert_dereference(dir: &Path, dst_path: &Path) {
+        fs::write(dir.join("a"), "1").unwrap();
+        std::os::unix::fs::symlink("a", dir.join("b")).unwrap();
+
+        mvb()
+            .current_dir(dir)
+            .arg("--dereference")
+            .write_stdin(format!("<b\n>{}", dst_path.to_string_lossy()))
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        assert!(!dst_path
+            .symlink_metadata()
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(dst_path).unwrap(), "1");
+        assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "1");
+        assert!(dir.join("b").symlink_metadata().is_err());
+    }
+}
+
+mod overwrite {
+    use super::*;
+
+    #[test]
+    fn no_clobber() {
+        let dir = temp_dir();
+
+        let src_file = write(dir.child("a"), "1");
+        let dst_file = write(dir.child("b"), "2");
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--no-clobber")
+            .write_stdin("<a\n>b")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        src_file.assert("1");
+        dst_file.assert("2");
+    }
+
+    #[test]
+    fn fail_if_exists() {
+        let dir = temp_dir();
+
+        let src_file = write(dir.child("a"), "1");
+        let dst_file = write(dir.child("b"), "2");
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--fail-if-exists")
+            .write_stdin("<a\n>b")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout("")
+            .stderr("error: Path 'b' already exists\n");
+
+        src_file.assert("1");
+        dst_file.assert("2");
+    }
+
+    #[test]
+    fn backup() {
+        let dir = temp_dir();
+
+        let src_file = write(dir.child("a"), "1");
+        let dst_file = write(dir.child("b"), "2");
+        let backup_file = write(dir.child("b.~1~"), "3");
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--backup=numbered")
+            .write_stdin("<a\n>b")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        src_file.assert(predicates::path::missing());
+        dst_file.assert("1");
+        backup_file.assert("3");
+        dir.child("b.~2~").assert("2");
+    }
+
+    #[test]
+    fn trash() {
+        let dir = temp_dir();
+
+        let src_file = write(dir.child("a"), "1");
+        let dst_file = write(dir.child("b"), "2");
+        let trash_dir = dir.child("data/Trash");
+
+        mvb()
+            .current_dir(dir.path())
+            .env("XDG_DATA_HOME", dir.child("data").path())
+            .arg("--trash")
+            .write_stdin("<a\n>b")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        src_file.assert(predicates::path::missing());
+        dst_file.assert("1");
+        trash_dir.child("files/b").assert("2");
+        trash_dir
+            .child("info/b.trashinfo")
+            .assert(predicates::str::contains(format!(
+                "Path={}\n",
+                dst_file.path().to_string_lossy()
+            )));
+    }
+
+    #[test]
+    fn trash_dir_jobs() {
+        let dir = temp_dir();
+        let trash_dir = dir.child("trash");
+        let mut input = String::new();
+
+        for index in 0..500 {
+            write(dir.child(format!("a{}", index)), "1");
+            write(dir.child(format!("{}/b", index)), "2");
+            input.push_str(&format!("<a{}\n>{}/b\n", index, index));
+        }
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--trash=trash")
+            .arg("--jobs=8")
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        // Each overwritten file gets its own name in trash directory.
+        assert_eq!(trash_dir.path().read_dir().unwrap().count(), 500);
+    }
+
+    #[test]
+    fn no_merge() {
+        let dir = temp_dir();
+
+        let src_dir = dir.child("a");
+        let dst_dir = dir.child("b");
+        write(src_dir.child("c"), "1");
+        dst_dir.create_dir_all().unwrap();
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--no-merge")
+            .write_stdin("<a\n>b")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout("")
+            .stderr("error: Cannot merge directory 'a' into existing directory 'b'\n");
+
+        src_dir.child("c").assert("1");
+        dst_dir.child("c").assert(predicates::path::missing());
+    }
+}
+
+mod dry_run {
+    use super::*;
+
+    #[test]
+    fn actions() {
+        let dir = temp_dir();
+
+        let src_file_1 = write(dir.child("a1"), "1");
+        let src_file_2 = write(dir.child("a2"), "2");
+        let src_dir = dir.child("a3");
+        write(src_dir.child("f"), "3");
+
+        let dst_file_1 = write(dir.child("b1"), "4");
+        let dst_file_2 = dir.child("b2/c");
+        let dst_dir = dir.child("b3");
+        dst_dir.create_dir_all().unwrap();
+
+        dir.child("a5").create_dir_all().unwrap();
+        write(dir.child("b5"), "5");
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--dry-run")
+            .write_stdin("<a1\n>b1\n<a2\n>b2/c\n<a3\n>b3\n<a4\n>b4\n<a5\n>b5")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout(indoc! {"
+                Moving 'a1' to 'b1' ... would-overwrite
+                Creating directory 'b2' ... would-create-dir
+                Moving 'a2' to 'b2/c' ... would-move
+                Moving 'a3' to 'b3' ... would-merge-dir
+                Moving 'a4' to 'b4' ... error
+                Moving 'a5' to 'b5' ... error
+            "})
+            .stderr(indoc! {"
+                error: Path 'a4' not found or user lacks permission
+                error: Cannot to overwrite file 'b5' with directory 'a5'
+            "});
+
+        src_file_1.assert("1");
+        src_file_2.assert("2");
+        src_dir.child("f").assert("3");
+
+        dst_file_1.assert("4");
+        dst_file_2.assert(predicates::path::missing());
+        dir.child("b2").assert(predicates::path::missing());
+        dst_dir.child("f").assert(predicates::path::missing());
+    }
+
+    #[test]
+    fn chain() {
+        let dir = temp_dir();
+        let src_file = write(dir.child("a"), "1");
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--dry-run")
+            .write_stdin("<a\n>b\n<b\n>c")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout(indoc! {"
+                Moving 'b' to 'c' ... error
+                Moving 'a' to 'b' ... would-move
+            "})
+            .stderr("error: Path 'b' not found or user lacks permission\n");
+
+        src_file.assert("1");
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod interactive {
+    use std::path::Path;
+
+    use assert_cmd::cargo::cargo_bin;
+    use assert_cmd::Command;
+
+    use super::*;
+
+    // Answers are read from a terminal which is emulated by the `script` utility.
+    fn mvb_tty(dir: &Path) -> Command {
+        let mut command = Command::new("script");
+        command
+            .current_dir(dir)
+            .arg("--quiet")
+            .arg("--return")
+            .arg("--echo")
+            .arg("never")
+            .arg("--command")
+            .arg(format!(
+                "{} --interactive --color=never instructions",
+                cargo_bin("mvb").to_string_lossy()
+            ))
+            .arg("/dev/null");
+        command
+    }
+
+    #[test]
+    fn chain() {
+        let dir = temp_dir();
+        write(dir.child("instructions"), "<a\n>b\n<b\n>c\n");
+
+        let file_1 = write(dir.child("a"), "1");
+        let file_2 = write(dir.child("b"), "2");
+        let file_3 = write(dir.child("c"), "3");
+
+        mvb_tty(dir.path())
+            .write_stdin("y\nn\n")
+            .assert()
+            .success()
+            .stdout(
+                "Move 'b' to 'c' (overwrite)? [y]es, [n]o, [a]ll, [q]uit: \
+                 Move 'a' to 'b'? [y]es, [n]o, [a]ll, [q]uit: ",
+            );
+
+        file_1.assert("1");
+        file_2.assert(predicates::path::missing());
+        file_3.assert("2");
+    }
+
+    #[test]
+    fn cycle() {
+        let dir = temp_dir();
+        write(dir.child("instructions"), "<a\n>b\n<b\n>a\n");
+
+        let file_1 = write(dir.child("a"), "1");
+        let file_2 = write(dir.child("b"), "2");
+
+        mvb_tty(dir.path())
+            .write_stdin("y\ny\n")
+            .assert()
+            .success()
+            .stdout(
+                "Move 'b' to 'a'? [y]es, [n]o, [a]ll, [q]uit: \
+                 Move 'a' to 'b' (staged)? [y]es, [n]o, [a]ll, [q]uit: ",
+            );
+
+        file_1.assert("2");
+        file_2.assert("1");
+    }
+
+    #[test]
+    fn quit() {
+        let dir = temp_dir();
+        write(dir.child("instructions"), "<a\n>b\n<c\n>d\n");
+
+        let file_1 = write(dir.child("a"), "1");
+        let file_2 = write(dir.child("c"), "2");
+
+        mvb_tty(dir.path()).write_stdin("y\nq\n").assert().success();
+
+        file_1.assert("1");
+        file_2.assert("2");
+        dir.child("b").assert(predicates::path::missing());
+        dir.child("d").assert(predicates::path::missing());
+    }
+
+    #[test]
+    fn closed_terminal() {
+        let dir = temp_dir();
+        write(dir.child("instructions"), "<a\n>b\n<c\n>d\n");
+
+        let file_1 = write(dir.child("a"), "1");
+        let file_2 = write(dir.child("c"), "2");
+
+        mvb_tty(dir.path())
+            .write_stdin("y\n\x04")
+            .assert()
+            .success();
+
+        file_1.assert("1");
+        file_2.assert("2");
+        dir.child("b").assert(predicates::path::missing());
+        dir.child("d").assert(predicates::path::missing());
+    }
+}
+
+mod journal {
+    use super::*;
+
+    #[test]
+    fn undo() {
+        let dir = temp_dir();
+        let journal_file = dir.child("journal");
+
+        let file_1 = write(dir.child("a"), "1");
+        let file_2 = write(dir.child("b"), "2");
+        let file_3 = dir.child("c/d");
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--journal")
+            .arg(journal_file.path())
+            .write_stdin("<a\n>b\n<b\n>c/d")
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        file_1.assert(predicates::path::missing());
+        file_2.assert("1");
+        file_3.assert("2");
+
+        journal_file.assert(format!(
+            "<{}\n>{}\n!\n<{}\n>{}\n",
+            file_3.path().to_string_lossy(),
+            file_2.path().to_string_lossy(),
+            file_2.path().to_string_lossy(),
+            file_1.path().to_string_lossy(),
+        ));
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--undo")
+            .arg(journal_file.path())
+            .arg("--verbose")
+            .assert()
+            .success()
+            .stdout(format!(
+                "Moving '{}' to '{}' ... OK\nMoving '{}' to '{}' ... OK\n",
+                file_2.path().to_string_lossy(),
+                file_1.path().to_string_lossy(),
+                file_3.path().to_string_lossy(),
+                file_2.path().to_string_lossy(),
+            ))
+            .stderr("");
+
+        file_1.assert("1");
+        file_2.assert("2");
+        file_3.assert(predicates::path::missing());
+    }
+
+    #[test]
+    fn undo_overwrite() {
+        let dir = temp_dir();
+        let journal_file = dir.child("journal");
+
+        let src_file = write(dir.child("a"), "1");
+        let dst_file = write(dir.child("b"), "2");
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--journal")
+            .arg(journal_file.path())
+            .write_stdin("<a\n>b")
+            .assert()
+            .success();
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--undo")
+            .arg(journal_file.path())
+            .assert()
+            .failure()
+            .code(1)
+            .stdout("")
+            .stderr(format!(
+                "error: Path '{}' existed before and cannot be restored\n",
+                dst_file.path().to_string_lossy(),
+            ));
+
+        src_file.assert("1");
+        dst_file.assert("1");
+    }
+
+    #[test]
+    fn undo_cycle() {
+        let dir = temp_dir();
+        let journal_file = dir.child("journal");
+
+        let file_1 = write(dir.child("a"), "1");
+        let file_2 = write(dir.child("b"), "2");
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--journal")
+            .arg(journal_file.path())
+            .write_stdin("<a\n>b\n<b\n>a")
+            .assert()
+            .success();
+
+        file_1.assert("2");
+        file_2.assert("1");
+
+        mvb()
+            .current_dir(dir.path())
+            .arg("--undo")
+            .arg(journal_file.path())
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        file_1.assert("1");
+        file_2.assert("2");
+        assert_eq!(dir.path().read_dir().unwrap().count(), 3);
+    }
+}
+
 #[test]
 fn help() {
     mvb()
@@ -148,6 +148,64 @@ mod input_terminator {
     }
 }
 
+mod walk {
+    use assert_fs::prelude::*;
+
+    use super::*;
+    use utils::temp_dir;
+
+    #[test]
+    fn default() {
+        let dir = temp_dir();
+        dir.child("a/b").touch().unwrap();
+        dir.child("c").touch().unwrap();
+        dir.child(".d").touch().unwrap();
+
+        rew()
+            .current_dir(dir.path())
+            .arg("--walk=.")
+            .assert()
+            .success()
+            .stdout("./a\n./a/b\n./c\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn options() {
+        let dir = temp_dir();
+        dir.child("a/b.txt").touch().unwrap();
+        dir.child("a/c.txt").touch().unwrap();
+        dir.child("a/d/e.txt").touch().unwrap();
+        dir.child("f.txt").touch().unwrap();
+        dir.child("a/.gitignore").write_str("c.txt\n").unwrap();
+
+        rew()
+            .current_dir(dir.path())
+            .arg("--walk=a")
+            .arg("--contents-first")
+            .arg("--max-depth=2")
+            .arg("--exclude=d")
+            .arg("--include=*.txt")
+            .arg("-d")
+            .arg("{B}.md")
+            .assert()
+            .success()
+            .stdout("<a/b.txt\n>a/b.md\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn conflicting_values() {
+        rew()
+            .arg("--walk=.")
+            .arg("{}")
+            .arg("a")
+            .assert()
+            .failure()
+            .code(2);
+    }
+}
+
 mod output_terminator {
     use super::*;
 
@@ -562,6 +620,273 @@ fn explain() {
         .stderr("");
 }
 
+mod columns {
+    use super::*;
+
+    #[test]
+    fn csv() {
+        rew()
+            .arg("--read-csv")
+            .arg("--diff")
+            .arg("{2} ({3}).{1|e}")
+            .write_stdin("a.txt,\"Hello, \"\"World\"\"\",2021\nb.md,Bye,\n")
+            .assert()
+            .success()
+            .stdout("<a.txt\n>Hello, \"World\" (2021).txt\n<b.md\n>Bye ().md\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn tsv() {
+        rew()
+            .arg("--read-tsv")
+            .arg("{1}-{2}-{3}")
+            .write_stdin("a\tb,c\n")
+            .assert()
+            .success()
+            .stdout("a-b,c-\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn invalid() {
+        rew()
+            .arg("--read-csv")
+            .arg("{2}")
+            .write_stdin(&b"a,b\xFF"[..])
+            .assert()
+            .failure()
+            .code(1)
+            .stdout("")
+            .stderr("error: Value does not have UTF-8 encoding (offset 1)\n");
+    }
+
+    #[test]
+    fn conflicting_values() {
+        for flag in &["--read-csv", "--read-tsv"] {
+            rew()
+                .arg(flag)
+                .arg("{2}")
+                .arg("a,b")
+                .assert()
+                .failure()
+                .code(2);
+        }
+    }
+
+    #[test]
+    fn without_records() {
+        rew()
+            .arg("{2}")
+            .write_stdin("a,b")
+            .assert()
+            .failure()
+            .code(3)
+            .stdout("")
+            .stderr(indoc! {"
+                error: Invalid pattern: Column filter requires '--read-csv' or '--read-tsv' input
+                
+                {2}
+                 ^
+            "});
+    }
+}
+
+mod json_lines {
+    use super::*;
+
+    #[test]
+    fn variables() {
+        rew()
+            .arg("--read-jsonl=path")
+            .arg("{.dir}/{.year}-{f}")
+            .write_stdin("{\"path\":\"a.txt\",\"dir\":\"x\",\"year\":2021}\n{\"dir\":\"y\",\"path\":\"b.txt\"}\n")
+            .assert()
+            .success()
+            .stdout("x/2021-a.txt\ny/-b.txt\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn missing_key() {
+        rew()
+            .arg("--read-jsonl=path")
+            .arg("{}")
+            .write_stdin("{\"path\":\"a\"}\n{\"file\":\"b\"}\n")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout("a\n")
+            .stderr("error: Expected string field 'path' (item #2)\n");
+    }
+
+    #[test]
+    fn conflicting_values() {
+        rew()
+            .arg("--read-jsonl=path")
+            .arg("{}")
+            .arg("{\"path\":\"a\"}")
+            .assert()
+            .failure()
+            .code(2);
+    }
+
+    #[test]
+    fn without_records() {
+        rew()
+            .arg("{.dir}")
+            .write_stdin("{\"dir\":\"a\"}")
+            .assert()
+            .failure()
+            .code(3)
+            .stdout("")
+            .stderr(indoc! {"
+                error: Invalid pattern: Variable filter requires '--read-jsonl' input
+                
+                {.dir}
+                 ^^^^
+            "});
+    }
+}
+
+mod sort {
+    use super::*;
+
+    #[test]
+    fn natural() {
+        rew()
+            .arg("--sort=natural")
+            .arg("{C}:{}")
+            .write_stdin("img10\nimg2\nimg1\n")
+            .assert()
+            .success()
+            .stdout("1:img1\n2:img2\n3:img10\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn reverse_unique() {
+        rew()
+            .arg("--sort=lexical")
+            .arg("--reverse")
+            .arg("--unique")
+            .arg("{C}:{}")
+            .arg("b")
+            .arg("a")
+            .arg("b")
+            .arg("c")
+            .assert()
+            .success()
+            .stdout("1:c\n2:b\n3:a\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn size_missing() {
+        rew()
+            .arg("--sort=size")
+            .write_stdin("_missing_\n")
+            .assert()
+            .failure()
+            .code(1)
+            .stdout("")
+            .stderr(predicate::str::starts_with(
+                "error: Cannot read metadata of '_missing_': ",
+            ));
+    }
+}
+
+mod invalid_utf8 {
+    use super::*;
+
+    #[test]
+    fn fail() {
+        rew()
+            .arg("{}")
+            .write_stdin(&b"a\xFF%"[..])
+            .assert()
+            .failure()
+            .code(1)
+            .stdout("")
+            .stderr("error: Value does not have UTF-8 encoding (offset 1)\n");
+    }
+
+    #[test]
+    fn lossy() {
+        rew()
+            .arg("--invalid-utf8=lossy")
+            .arg("{}")
+            .write_stdin(&b"a\xFF%"[..])
+            .assert()
+            .success()
+            .stdout("a\u{FFFD}%\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn escape() {
+        rew()
+            .arg("--invalid-utf8=escape")
+            .arg("--diff")
+            .arg("{}.{#1}")
+            .write_stdin(&b"a\xFF%"[..])
+            .assert()
+            .success()
+            .stdout(&b"<a\xFF%\n>a\xFF%.a\n"[..])
+            .stderr("");
+    }
+
+    #[test]
+    fn escape_args() {
+        rew()
+            .arg("--invalid-utf8=escape")
+            .arg("--json-lines")
+            .arg("{}")
+            .arg("100%41")
+            .assert()
+            .success()
+            .stdout("{\"in\":\"100%2541\",\"out\":\"100%2541\"}\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn escape_args_round_trip() {
+        rew()
+            .arg("--invalid-utf8=escape")
+            .arg("{}")
+            .arg("100%41")
+            .assert()
+            .success()
+            .stdout("100%41\n")
+            .stderr("");
+    }
+
+    #[test]
+    fn escape_filters() {
+        rew()
+            .arg("--invalid-utf8=escape")
+            .arg("{#1-4} {#-1} {s:E:Z} {^} {r:%%:-}")
+            .write_stdin(&b"caf\xE9%"[..])
+            .assert()
+            .success()
+            .stdout(&b"caf\xE9 % caf\xE9% CAF\xE9% caf\xE9-\n"[..])
+            .stderr("");
+    }
+
+    #[test]
+    fn escape_json_lines() {
+        rew()
+            .arg("--invalid-utf8=escape")
+            .arg("--json-lines")
+            .arg("{}")
+            .write_stdin(&b"a\xFF%"[..])
+            .assert()
+            .success()
+            .stdout("{\"in\":\"a%FF%25\",\"out\":\"a%FF%25\"}\n")
+            .stderr("");
+    }
+}
+
 mod blns {
     use naughty_strings::BLNS;
 